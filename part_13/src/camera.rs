@@ -0,0 +1,141 @@
+use ecs::Ecs;
+use ecs::component::Position;
+use map_objects::map::GameMap;
+use settings::Settings;
+
+/// The part of the map which is shown on the screen.
+///
+/// The camera keeps the player in the middle of the viewport, but stops at the edges of the map,
+/// so no space is wasted beyond them. A map which is smaller than the viewport is drawn in the
+/// top left corner.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    /// The map position shown in the top left corner of the viewport
+    pub position: (i32, i32),
+    pub dimensions: (i32, i32),
+    map_dimensions: (i32, i32),
+}
+
+impl Camera {
+    /// Center a viewport of `dimensions` on `target`, as far as the map allows it
+    pub fn new(dimensions: (i32, i32), map_dimensions: (i32, i32), target: (i32, i32)) -> Camera {
+        let axis = |view: i32, map: i32, target: i32| {
+            if map <= view {
+                0
+            } else {
+                (target - view / 2).max(0).min(map - view)
+            }
+        };
+
+        Camera {
+            position: (axis(dimensions.0, map_dimensions.0, target.0),
+                       axis(dimensions.1, map_dimensions.1, target.1)),
+            dimensions,
+            map_dimensions,
+        }
+    }
+
+    /// The camera following the player on the current floor
+    pub fn following_player(ecs: &Ecs, map: &GameMap, settings: &Settings) -> Camera {
+        let target = ecs.get_component::<Position>(ecs.player_entity_id)
+            .map(|p| p.position)
+            .unwrap_or((0, 0));
+
+        Camera::new(settings.viewport_dimensions(), map.dimensions, target)
+    }
+
+    /// Convert a map position to the position on the screen, if it is inside the viewport
+    pub fn to_screen(&self, position: (i32, i32)) -> Option<(i32, i32)> {
+        let screen = (position.0 - self.position.0, position.1 - self.position.1);
+
+        if self.is_on_map(position) && self.is_in_viewport(screen) {
+            Some(screen)
+        } else {
+            None
+        }
+    }
+
+    /// Convert a position on the screen to the map position shown there, if there is any
+    pub fn to_world(&self, screen: (i32, i32)) -> Option<(i32, i32)> {
+        let position = (screen.0 + self.position.0, screen.1 + self.position.1);
+
+        if self.is_on_map(position) && self.is_in_viewport(screen) {
+            Some(position)
+        } else {
+            None
+        }
+    }
+
+    fn is_in_viewport(&self, screen: (i32, i32)) -> bool {
+        screen.0 >= 0 && screen.1 >= 0 && screen.0 < self.dimensions.0 && screen.1 < self.dimensions.1
+    }
+
+    fn is_on_map(&self, position: (i32, i32)) -> bool {
+        position.0 >= 0 && position.1 >= 0 && position.0 < self.map_dimensions.0 && position.1 < self.map_dimensions.1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEWPORT: (i32, i32) = (20, 10);
+    const MAP: (i32, i32) = (100, 50);
+
+    #[test]
+    fn camera_is_centered_on_the_target() {
+        assert_eq!(Camera::new(VIEWPORT, MAP, (50, 25)).position, (40, 20));
+    }
+
+    #[test]
+    fn camera_stops_at_the_edges_of_the_map() {
+        // Left and top edge
+        assert_eq!(Camera::new(VIEWPORT, MAP, (2, 25)).position, (0, 20));
+        assert_eq!(Camera::new(VIEWPORT, MAP, (50, 1)).position, (40, 0));
+        assert_eq!(Camera::new(VIEWPORT, MAP, (0, 0)).position, (0, 0));
+        // Right and bottom edge
+        assert_eq!(Camera::new(VIEWPORT, MAP, (97, 25)).position, (80, 20));
+        assert_eq!(Camera::new(VIEWPORT, MAP, (50, 48)).position, (40, 40));
+        assert_eq!(Camera::new(VIEWPORT, MAP, (99, 49)).position, (80, 40));
+    }
+
+    #[test]
+    fn small_map_is_drawn_in_the_top_left_corner() {
+        let camera = Camera::new(VIEWPORT, (15, 8), (14, 7));
+        assert_eq!(camera.position, (0, 0));
+        assert_eq!(camera.to_screen((14, 7)), Some((14, 7)));
+
+        // The part of the viewport beyond the map shows nothing
+        assert_eq!(camera.to_world((15, 3)), None);
+        assert_eq!(camera.to_world((3, 8)), None);
+        assert_eq!(camera.to_screen((15, 3)), None);
+    }
+
+    #[test]
+    fn screen_and_map_positions_are_converted_both_ways() {
+        let camera = Camera::new(VIEWPORT, MAP, (60, 30));
+
+        for &position in &[(50, 25), (69, 34), (55, 30)] {
+            let screen = camera.to_screen(position).unwrap();
+            assert_eq!(camera.to_world(screen), Some(position));
+        }
+        assert_eq!(camera.to_screen((50, 25)), Some((0, 0)));
+        assert_eq!(camera.to_screen((69, 34)), Some((19, 9)));
+    }
+
+    #[test]
+    fn positions_outside_of_the_viewport_are_not_converted() {
+        let camera = Camera::new(VIEWPORT, MAP, (60, 30));
+
+        assert_eq!(camera.to_screen((49, 30)), None);
+        assert_eq!(camera.to_screen((70, 30)), None);
+        assert_eq!(camera.to_screen((60, 24)), None);
+        assert_eq!(camera.to_screen((60, 35)), None);
+        assert_eq!(camera.to_screen((-1, -1)), None);
+
+        assert_eq!(camera.to_world((-1, 0)), None);
+        assert_eq!(camera.to_world((0, -1)), None);
+        assert_eq!(camera.to_world((20, 0)), None);
+        assert_eq!(camera.to_world((0, 10)), None);
+    }
+}
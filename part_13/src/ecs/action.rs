@@ -4,12 +4,12 @@ use ecs::component::Position;
 use ecs::component::Actor;
 use ecs::component::Render;
 use tcod::colors;
-use ecs::component::MonsterAi;
+use ecs::component::{MonsterAi, AiBehavior};
 use ecs::component::Corpse;
 use render::RenderOrder;
-use message::{Message, MessageLog};
+use message::Message;
+use events::{EventBus, GameEvent};
 use ecs::component::Name;
-use std::rc::Rc;
 use ecs::component::Inventory;
 use ecs::component::Item;
 use game::state::GameState;
@@ -21,6 +21,7 @@ use settings::Settings;
 use ecs::component::Equipment;
 use ecs::component::Equippable;
 use ecs::component::EquipmentSlot;
+use ecs::status::{StatusEffect, StatusEffects, Venomous};
 
 /// This struct defines the Result of one single action. A message can be created, and also
 /// a reaction can happen.
@@ -45,6 +46,7 @@ impl ActionResult {
 #[derive(PartialEq)]
 pub enum EntityAction {
     MeleeAttack(EntityId, EntityId),
+    RangedAttack(EntityId, EntityId),
     TakeDamage(EntityId, u32, EntityId),
     MoveTo(EntityId, (i32, i32)),
     MoveRelative(EntityId, (i32, i32)),
@@ -60,42 +62,45 @@ pub enum EntityAction {
     LevelUp(EntityId),
     UpdateFov(EntityId),
     LookForTarget(EntityId),
+    AddStatusEffect(EntityId, StatusEffect),
     Idle,
 }
 
 impl EntityAction {
     /// Execute the action
-    pub fn execute(&self, ecs: &mut Ecs, fov_map: &Map, log: Rc<MessageLog>, settings: &Settings) -> Option<GameState> {
+    pub fn execute(&self, ecs: &mut Ecs, fov_map: &Map, events: &EventBus, settings: &Settings) -> Option<GameState> {
         let result = match *self {
             EntityAction::MoveTo(entity_id, pos) => self.move_to_action(ecs, entity_id, pos),
             EntityAction::MoveRelative(entity_id, delta) => self.move_relative_action(ecs, entity_id, delta),
             EntityAction::MeleeAttack(attacker_id, target_id) => self.melee_attack_action(ecs, attacker_id, target_id),
-            EntityAction::TakeDamage(entity_id, damage, attacker_id) => self.take_damage_action(ecs, entity_id, damage, attacker_id),
-            EntityAction::Die(entity_id) => self.die_action(ecs, entity_id),
+            EntityAction::RangedAttack(attacker_id, target_id) => self.ranged_attack_action(ecs, attacker_id, target_id),
+            EntityAction::TakeDamage(entity_id, damage, attacker_id) => self.take_damage_action(ecs, events, entity_id, damage, attacker_id),
+            EntityAction::Die(entity_id) => self.die_action(ecs, events, entity_id),
             EntityAction::PickUpItem(entity_id, item_id) => self.pick_up_item_action(ecs, entity_id, item_id),
             EntityAction::DropItem(entity_id, item_number) => self.drop_item_action(ecs, entity_id, item_number),
-            EntityAction::AddItemToInventory(entity_id, item_id) => self.add_item_to_inventory_action(ecs, entity_id, item_id),
+            EntityAction::AddItemToInventory(entity_id, item_id) => self.add_item_to_inventory_action(ecs, events, entity_id, item_id),
             EntityAction::RemoveItemFromInventory(entity_id, item_id) => self.remove_item_from_inventory_action(ecs, entity_id, item_id),
             EntityAction::UseItem(entity_id, item_number) => self.use_item_action(ecs, fov_map, entity_id, item_number),
             EntityAction::SetAiTarget(entity_id, target_id) => self.set_ai_target_action(ecs, entity_id, target_id),
-            EntityAction::RewardXp(entity_id, xp) => self.reward_xp(ecs, entity_id, xp),
-            EntityAction::LevelUp(entity_id) => self.level_up(ecs, entity_id),
+            EntityAction::RewardXp(entity_id, xp) => self.reward_xp(ecs, events, entity_id, xp),
+            EntityAction::LevelUp(entity_id) => self.level_up(ecs, events, entity_id),
             EntityAction::LookForTarget(entity_id)  => self.look_for_target_action(ecs, entity_id, settings),
             EntityAction::UpdateFov(entity_id) => self.update_fov_action(ecs, entity_id, settings),
             EntityAction::ToggleEquipment(entity_id, item_number) => self.toggle_item_action(ecs, entity_id, item_number),
+            EntityAction::AddStatusEffect(entity_id, effect) => self.add_status_effect_action(ecs, entity_id, effect),
             EntityAction::Idle => ActionResult::none() // Idle - do nothing
         };
 
         if let Some(messages) = result.message {
             for message in messages {
-                log.add(message);
+                events.publish(GameEvent::Message(message));
             }
         }
 
 
         let mut resulting_state = None;
         for reaction in result.reactions {
-            resulting_state = if let Some(state) = reaction.execute(ecs, fov_map, Rc::clone(&log), settings) {
+            resulting_state = if let Some(state) = reaction.execute(ecs, fov_map, events, settings) {
                 Some(state)
             } else {
                 resulting_state
@@ -120,9 +125,18 @@ impl EntityAction {
             Some(actor) => {
                 match actor.calculate_attack(ecs, target_id) {
                     Some(damage) => {
+                        let mut messages = vec![Message::new(format!("The {} attacks the {} .", attacker_name, target_name), colors::WHITE)];
+                        let mut reactions = vec![EntityAction::TakeDamage(target_id, damage, attacker_id)];
+
+                        // Venom only gets through if the attack hurts
+                        if let Some(venom) = ecs.get_component::<Venomous>(attacker_id).filter(|_| damage > 0) {
+                            messages.push(Message::new(format!("The {} is {}!", target_name.to_uppercase(), venom.effect.kind.name()), venom.effect.kind.color()));
+                            reactions.push(EntityAction::AddStatusEffect(target_id, venom.effect));
+                        }
+
                         ActionResult {
-                            message: Some(vec![Message::new(format!("The {} attacks the {} .", attacker_name, target_name), colors::WHITE)]),
-                            reactions: vec![EntityAction::TakeDamage(target_id, damage, attacker_id)],
+                            message: Some(messages),
+                            reactions,
                             state: None,
                         }
                     }
@@ -133,9 +147,29 @@ impl EntityAction {
         }
     }
 
-    fn reward_xp(&self, ecs: &mut Ecs, entity_id: EntityId, xp: u32) -> ActionResult {
-        let entity_name = EntityAction::get_entity_name(ecs, entity_id).to_uppercase();
+    /// Fire the projectile of a ranged monster at the target. It hits as hard as a melee attack.
+    fn ranged_attack_action(&self, ecs: &mut Ecs, attacker_id: EntityId, target_id: EntityId) -> ActionResult {
+        let attacker_name = EntityAction::get_entity_name(ecs, attacker_id).to_uppercase();
+        let target_name = EntityAction::get_entity_name(ecs, target_id).to_uppercase();
+
+        let projectile = match ecs.get_component::<MonsterAi>(attacker_id).map(|ai| ai.behavior) {
+            Some(AiBehavior::Ranged { projectile, .. }) => projectile,
+            _ => return ActionResult::none()
+        };
+
+        match ecs.get_component::<Actor>(attacker_id).and_then(|actor| actor.calculate_attack(ecs, target_id)) {
+            Some(damage) => {
+                ActionResult {
+                    message: Some(vec![Message::new(format!("The {} shoots {} at the {}.", attacker_name, projectile.name(), target_name), colors::LIGHT_ORANGE)]),
+                    reactions: vec![EntityAction::TakeDamage(target_id, damage, attacker_id)],
+                    state: None,
+                }
+            }
+            None => ActionResult::none()
+        }
+    }
 
+    fn reward_xp(&self, ecs: &mut Ecs, events: &EventBus, entity_id: EntityId, xp: u32) -> ActionResult {
         if let Some(l) = ecs.get_component_mut::<Level>(entity_id) {
             let reactions = if l.reward_xp(xp) {
                 vec![EntityAction::LevelUp(entity_id)]
@@ -143,11 +177,11 @@ impl EntityAction {
                 vec![]
             };
 
-            let message = Message::new(format!("{} gains {} XP", entity_name, xp), colors::WHITE);
+            events.publish(GameEvent::XpGained(entity_id, xp));
 
             ActionResult {
                 reactions,
-                message: Some(vec![message]),
+                message: None,
                 state: None,
             }
         } else {
@@ -155,17 +189,15 @@ impl EntityAction {
         }
     }
 
-    fn level_up(&self, ecs: &mut Ecs, entity_id: EntityId) -> ActionResult {
-        let entity_name = EntityAction::get_entity_name(ecs, entity_id).to_uppercase();
-
+    fn level_up(&self, ecs: &mut Ecs, events: &EventBus, entity_id: EntityId) -> ActionResult {
         if let Some(l) = ecs.get_component_mut::<Level>(entity_id) {
 
             l.level_up();
-            let message = Message::new(format!("{} feels stronger: Reached level {}.", entity_name, l.level), colors::YELLOW);
+            events.publish(GameEvent::LevelGained(entity_id, l.level));
 
             ActionResult {
                 reactions: vec![],
-                message: Some(vec![message]),
+                message: None,
                 state: Some(GameState::ShowLeveUpMenu),
             }
         } else {
@@ -188,16 +220,11 @@ impl EntityAction {
         ActionResult::none()
     }
 
-    fn take_damage_action(&self, ecs: &mut Ecs, entity_id: EntityId, damage: u32, attacker_id: EntityId) -> ActionResult {
-        let entity_name = EntityAction::get_entity_name(ecs, entity_id).to_uppercase();
+    fn take_damage_action(&self, ecs: &mut Ecs, events: &EventBus, entity_id: EntityId, damage: u32, attacker_id: EntityId) -> ActionResult {
         if let Some(e) = ecs.get_component_mut::<Actor>(entity_id) {
             e.take_damage(damage);
 
-            let message = Message::new(if damage > 0 {
-                format!("The {} takes {}  damage.", entity_name, damage)
-            } else {
-                format!("The {} takes no damage.", entity_name)
-            }, colors::WHITE);
+            events.publish(GameEvent::DamageDealt(entity_id, damage));
 
             return if e.hp <= 0 {
                 ActionResult {
@@ -205,15 +232,11 @@ impl EntityAction {
                         EntityAction::Die(entity_id),
                         EntityAction::RewardXp(attacker_id, e.xp_reward)
                     ],
-                    message: Some(vec![message]),
+                    message: None,
                     state: None,
                 }
             } else {
-                ActionResult {
-                    reactions: vec![],
-                    message: Some(vec![message]),
-                    state: None,
-                }
+                ActionResult::none()
             };
         }
         ActionResult::none()
@@ -307,7 +330,7 @@ impl EntityAction {
         let entity_name = EntityAction::get_entity_name(ecs, entity_id).to_uppercase();
 
         let mut item_name = "".to_string();
-        let mut item_id = 0;
+        let mut item_id = EntityId::none();
 
         let spell = if let Some(inventory) = ecs.get_component::<Inventory>(entity_id) {
             if inventory.items.len() > item_number as usize {
@@ -380,7 +403,7 @@ impl EntityAction {
     fn drop_item_action(&self, ecs: &mut Ecs, entity_id: EntityId, item_number: u8) -> ActionResult {
         let entity_name = EntityAction::get_entity_name(ecs, entity_id).to_uppercase();
         let mut item_name = "".to_string();
-        let mut item_id = 0;
+        let mut item_id = EntityId::none();
 
         let item_position = if let Some(inventory) = ecs.get_component::<Inventory>(entity_id) {
             if inventory.items.len() > item_number as usize {
@@ -424,17 +447,13 @@ impl EntityAction {
     }
 
     fn pick_up_item_action(&self, ecs: &mut Ecs, entity_id: EntityId, item_id: EntityId) -> ActionResult {
-        let entity_name = EntityAction::get_entity_name(ecs, entity_id).to_uppercase();
         let item_name = EntityAction::get_entity_name(ecs, item_id).to_uppercase();
 
         if let Some(inventory) = ecs.get_component::<Inventory>(entity_id) {
             if inventory.free_space() > 0 {
-                let message = Message::new(format!("{} picked up the {}", entity_name, item_name),
-                                           colors::BLUE);
-
                 ActionResult {
                     reactions: vec![EntityAction::AddItemToInventory(entity_id, item_id)],
-                    message: Some(vec![message]),
+                    message: None,
                     state: None,
                 }
             } else {
@@ -452,29 +471,23 @@ impl EntityAction {
         }
     }
 
-    fn add_item_to_inventory_action(&self, ecs: &mut Ecs, entity_id: EntityId, item_id: EntityId) -> ActionResult {
+    fn add_item_to_inventory_action(&self, ecs: &mut Ecs, events: &EventBus, entity_id: EntityId, item_id: EntityId) -> ActionResult {
         ecs.remove_component::<Position>(item_id);
 
         if let Some(inventory) = ecs.get_component_mut::<Inventory>(entity_id) {
             inventory.add_item(item_id);
+            events.publish(GameEvent::ItemPickedUp(entity_id, item_id));
         }
         ActionResult::none()
     }
 
 
-    fn die_action(&self, ecs: &mut Ecs, entity_id: EntityId) -> ActionResult {
-        let entity_name = EntityAction::get_entity_name(ecs, entity_id).to_uppercase();
-
-        let message = if entity_id == ecs.player_entity_id {
-            Message::new("YOU DIED".to_string(), colors::RED)
-        } else {
-            Message::new(format!("The {} died.", entity_name), colors::ORANGE)
-        };
-
+    fn die_action(&self, ecs: &mut Ecs, events: &EventBus, entity_id: EntityId) -> ActionResult {
         // Override the Rendering with the default corpse glyph
         ecs.register_component(entity_id, Render::new(entity_id, '%', colors::DARK_CRIMSON, RenderOrder::Corpse));
         // Remove the AI and the Creature components
         ecs.remove_component::<MonsterAi>(entity_id);
+        ecs.remove_component::<StatusEffects>(entity_id);
         // Add the Corpse component
         ecs.register_component(entity_id, Corpse {});
         // Set non blocking
@@ -483,11 +496,19 @@ impl EntityAction {
             None => ()
         }
 
-        ActionResult {
-            reactions: vec![],
-            message: Some(vec![message]),
-            state: None,
+        events.publish(GameEvent::EntityDied(entity_id));
+
+        ActionResult::none()
+    }
+
+    fn add_status_effect_action(&self, ecs: &mut Ecs, entity_id: EntityId, effect: StatusEffect) -> ActionResult {
+        if !ecs.has_component::<StatusEffects>(entity_id) {
+            ecs.register_component(entity_id, StatusEffects::new());
         }
+        if let Some(effects) = ecs.get_component_mut::<StatusEffects>(entity_id) {
+            effects.add(effect);
+        }
+        ActionResult::none()
     }
 
     fn set_ai_target_action(&self, ecs: &mut Ecs, entity_id: EntityId, target_id: EntityId) -> ActionResult {
@@ -512,8 +533,19 @@ impl EntityAction {
             }
         } ;
 
+        // Remember where the target was seen, so the monster can follow it once it is gone
+        let target_position = ecs.get_component::<MonsterAi>(entity_id)
+            .and_then(|ai| ai.target_id())
+            .and_then(|id| ecs.get_component::<Position>(id))
+            .map(|p| p.position)
+            .filter(|_| target_in_fov);
+        let position = ecs.get_component::<Position>(entity_id).map(|p| p.position);
+
         if let Some(ai) = ecs.get_component_mut::<MonsterAi>(entity_id) {
             ai.set_chasing_target(target_in_fov);
+            if let Some(position) = position {
+                ai.update_memory(position, target_position, settings);
+            }
         }
 
 
@@ -558,4 +590,43 @@ impl EntityAction {
             None => format!("nameless entity (#{})", id)
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use ecs::creature::CreatureTemplate;
+    use game::simulation::Simulation;
+    use settings::Settings;
+    use game::input::InputAction;
+    use game::state::GameState;
+
+    #[test]
+    fn melee_attack_damages_monster() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let (orc_id, direction) = sim.spawn_next_to_player(CreatureTemplate::Orc);
+
+        sim.send(InputAction::MovePlayer(direction.0, direction.1));
+
+        let orc_hp = sim.hp_of(orc_id);
+        assert!(orc_hp < 20);
+        assert!(sim.has_message(&format!("The ORC takes {}  damage.", 20 - orc_hp)));
+        assert_eq!(sim.state(), GameState::PlayersTurn);
+    }
+
+    #[test]
+    fn monsters_strike_back() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let (troll_id, direction) = sim.spawn_next_to_player(CreatureTemplate::Troll);
+        let hp = sim.player_hp();
+
+        sim.send(InputAction::MovePlayer(direction.0, direction.1));
+
+        assert_eq!(sim.hp_of(troll_id), 28);
+        assert_eq!(sim.player_hp(), hp - 7);
+        assert!(sim.has_message("The PLAYER takes 7  damage."));
+    }
+}
@@ -7,7 +7,6 @@ use ecs::id::EntityId;
 use ecs::Ecs;
 
 use tcod::colors::Color;
-use tcod::Console;
 use tcod::BackgroundFlag;
 use tcod::map::Map;
 use tcod::pathfinding::AStar;
@@ -17,16 +16,27 @@ use json::JsonValue;
 use ecs::action::EntityAction;
 use map_objects::map::GameMap;
 use render::RenderOrder;
+use terminal::Terminal;
+use camera::Camera;
 use ecs::spell::Spell;
 
-use savegame::{Serialize, Deserialize};
+use savegame::{Serialize, Deserialize, LoadError, ReadJson};
 use map_objects::fov::initialize_fov;
-use map_objects::fov::recompute_fov;
+use map_objects::fov::update_tile;
 use settings::Settings;
+use ecs::status::random_direction;
+use random_utils::GameRng;
+use game::system::ACTION_ENERGY;
 use std::collections::HashMap;
 
 /// Used to indentify an Component
-pub trait Component: Any + Serialize {}
+///
+/// Every `Component` needs to be added to the `ComponentRegistry`, otherwise it can't be added
+/// to an `Entity`.
+pub trait Component: Any + Serialize + Deserialize {
+    /// The name under which the component is stored in save files
+    const TAG: &'static str;
+}
 
 /// A Component which contains informations of an `Entity`s position on the Map, and methods to
 /// interact with it
@@ -48,14 +58,14 @@ impl Position {
 
     /// Checks if a position is already blocked by an `Entity` and returns the id of the blocker.
     pub fn is_blocked_by(ecs: &Ecs, position: (i32, i32)) -> Vec<EntityId> {
-        ecs.get_all::<Self>().iter().filter(|(entity_id, p)| {
+        ecs.query::<(&Self, Option<&Actor>)>().filter(|(_, (p, actor))| {
             let is_blocking = p.position.0 == position.0 && p.position.1 == position.1 && p.is_blocking;
-            if let Some(a) = ecs.get_component::<Actor>(**entity_id) {
+            if let Some(a) = actor {
                 is_blocking && !a.is_dead()
             } else {
                 is_blocking
             }
-        }).map(|(i, _)| *i).collect()
+        }).map(|(i, _)| i).collect()
     }
 
     /// Change the Position of the Entity relative to its current position
@@ -90,52 +100,96 @@ impl Position {
         let vel = (dx as i32, dy as i32);
         let target = (self.position.0 + vel.0, self.position.1 + vel.1);
 
-        if map.is_move_blocked(target.0, target.1) || !Self::is_blocked_by(ecs, target).is_empty() {
+        if map.is_move_blocked(target.0, target.1) || map.tile_kind(target.0, target.1).is_hazard()
+            || !Self::is_blocked_by(ecs, target).is_empty() {
             return None;
         }
         Some(target)
     }
 
+    /// Find the neighbouring tile which leads farthest away from `threat`, if any tile is farther
+    /// away than the current one
+    pub fn calculate_move_away(&self, ecs: &Ecs, map: &GameMap, threat: (i32, i32)) -> Option<(i32, i32)> {
+        let mut best = None;
+        let mut best_distance = self.distance_to(threat);
+
+        for dx in -1..2 {
+            for dy in -1..2 {
+                let step = Position::new(self.entity_id, false);
+                let step = Position { position: (self.position.0 + dx, self.position.1 + dy), ..step };
+                let distance = step.distance_to(threat);
+
+                if distance <= best_distance || map.is_move_blocked(step.x(), step.y())
+                    || map.tile_kind(step.x(), step.y()).is_hazard()
+                    || !Self::is_blocked_by(ecs, step.position).is_empty() {
+                    continue;
+                }
+
+                best = Some(step.position);
+                best_distance = distance;
+            }
+        }
+
+        best
+    }
+
     /// Calculate the next movement step with A*
+    ///
+    /// Every step costs the movement cost of the tile it leads to, so slow tiles like water are
+    /// avoided if there is a way around them. Hazards are never entered.
     pub fn calculate_move_astar(&self, ecs: &Ecs, map: &GameMap, target_id: EntityId) -> Option<(i32, i32)> {
-        let target = match ecs.get_component::<Position>(target_id) {
-            Some(p) => p,
-            _ => return None
-        };
+        match ecs.get_component::<Position>(target_id) {
+            Some(target) => self.calculate_path_step(ecs, map, target.position, target_id),
+            _ => None
+        }
+    }
+
+    /// Calculate the next movement step with A* towards a position instead of an `Entity`
+    pub fn calculate_move_astar_to(&self, ecs: &Ecs, map: &GameMap, target: (i32, i32)) -> Option<(i32, i32)> {
+        self.calculate_path_step(ecs, map, target, EntityId::none())
+    }
 
-        let mut fov = Map::new(map.dimensions.0, map.dimensions.1);
+    /// The first step on the way to `target`. `target_id` is the `Entity` standing there, which
+    /// doesn't block the way.
+    fn calculate_path_step(&self, ecs: &Ecs, map: &GameMap, target: (i32, i32), target_id: EntityId) -> Option<(i32, i32)> {
+        let width = map.dimensions.0;
+        let mut costs = vec![0.0; (map.dimensions.0 * map.dimensions.1) as usize];
 
         for x in 0..map.dimensions.0 {
             for y in 0..map.dimensions.1 {
-                let tile = map.get_tile(x as usize, y as usize);
-                fov.set(x, y, !tile.block_sight, !tile.block_move);
+                let kind = map.tile_kind(x, y);
+                if !kind.is_hazard() {
+                    costs[(y * width + x) as usize] = kind.movement_cost() as f32;
+                }
             }
         }
 
-        ecs.get_all::<Position>().iter().filter(|(id, p)| {
+        ecs.query::<(&Position, Option<&Actor>)>().filter(|(id, (p, actor))| {
             // Filter out all entities which can be ignored for pathfinding:
             // - the entity itself
             // - dead actors
             // - non-blocking entities
-            let is_self = **id != target_id && **id != self.entity_id;
+            let is_self = *id != target_id && *id != self.entity_id;
             let is_blocking = p.is_blocking;
-            if let Some(a) = ecs.get_component::<Actor>(**id) {
+            if let Some(a) = actor {
                 is_self && is_blocking && !a.is_dead()
             } else {
                 is_self && is_blocking
             }
 
-        }).for_each(|(_, p)| {
-            fov.set(p.position.0, p.position.1, true, !p.is_blocking);
+        }).for_each(|(_, (p, _))| {
+            costs[(p.position.1 * width + p.position.0) as usize] = 0.0;
         });
 
-        let mut path = AStar::new_from_map(fov, 1.41);
-        path.find((self.position.0, self.position.1), (target.position.0, target.position.1));
+        // A cost of 0 means the tile can't be entered
+        let mut path = AStar::new_from_callback(map.dimensions.0, map.dimensions.1,
+                                                move |_from, (x, y)| costs[(y * width + x) as usize], 1.41);
+        path.find((self.position.0, self.position.1), target);
 
         if !path.is_empty() && path.len() < 25 {
             path.iter().next()
         } else {
-            self.calculate_move_towards(ecs, map, (target.position.0, target.position.1))
+            self.calculate_move_towards(ecs, map, target)
         }
     }
 
@@ -151,28 +205,27 @@ impl Position {
 impl Serialize for Position {
     fn serialize(&self) -> JsonValue {
         object!(
-        "type" => "Position",
-        "data" => object!(
-                "id" => self.entity_id,
-                "x" => self.position.0,
-                "y" => self.position.1,
-                "blocking" => self.is_blocking,
-            )
+            "id" => self.entity_id,
+            "x" => self.position.0,
+            "y" => self.position.1,
+            "blocking" => self.is_blocking,
         )
     }
 }
 
 impl Deserialize for Position {
-    fn deserialize(json: &JsonValue) -> Self {
-        Position {
-            entity_id: json["id"].as_u16().unwrap(),
-            position: (json["x"].as_i32().unwrap(), json["y"].as_i32().unwrap()),
-            is_blocking: json["blocking"].as_bool().unwrap()
-        }
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(Position {
+            entity_id: EntityId::deserialize(&json["id"])?,
+            position: (json["x"].read_i32()?, json["y"].read_i32()?),
+            is_blocking: json["blocking"].read_bool()?
+        })
     }
 }
 
-impl Component for Position {}
+impl Component for Position {
+    const TAG: &'static str = "Position";
+}
 
 
 /// This component handles the rendering of an Entity onto the map
@@ -193,10 +246,10 @@ impl Render {
         }
     }
 
-    pub fn draw(&self, ecs: &Ecs, console: &mut Console) {
-        if let Some(p) = ecs.get_component::<Position>(self.entity_id) {
-            console.set_default_foreground(self.color);
-            console.put_char(p.position.0, p.position.1, self.glyph, BackgroundFlag::None);
+    pub fn draw(&self, ecs: &Ecs, terminal: &mut Terminal, camera: &Camera) {
+        if let Some((x, y)) = ecs.get_component::<Position>(self.entity_id).and_then(|p| camera.to_screen(p.position)) {
+            terminal.set_default_foreground(self.color);
+            terminal.put_char(x, y, self.glyph, BackgroundFlag::None);
         }
     }
 }
@@ -205,34 +258,30 @@ impl Render {
 impl Serialize for Render {
     fn serialize(&self) -> JsonValue {
         object!(
-        "type" => "Render",
-        "data" => object!(
             "id" => self.entity_id,
             "glyph" => self.glyph.to_string(),
             "order" => self.order.to_string(),
             "color" => array![self.color.r, self.color.g, self.color.b]
-            )
         )
     }
 }
 
 impl Deserialize for Render {
-    fn deserialize(json: &JsonValue) -> Self {
-        Render {
-            entity_id: json["id"].as_u16().unwrap(),
-            glyph: json["glyph"].as_str().unwrap().chars().next().unwrap(),
-            order: RenderOrder::deserialize(&json["order"]),
-            color: Color {
-                r : json["color"][0].as_u8().unwrap(),
-                g : json["color"][1].as_u8().unwrap(),
-                b : json["color"][2].as_u8().unwrap(),
-            }
-        }
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(Render {
+            entity_id: EntityId::deserialize(&json["id"])?,
+            glyph: json["glyph"].read_str()?.chars().next()
+                .ok_or_else(|| LoadError::invalid("a glyph", &json["glyph"]))?,
+            order: RenderOrder::deserialize(&json["order"])?,
+            color: Color::deserialize(&json["color"])?,
+        })
     }
 }
 
 
-impl Component for Render {}
+impl Component for Render {
+    const TAG: &'static str = "Render";
+}
 
 /// The name and other textual data refering to an entity
 pub struct Name {
@@ -242,23 +291,22 @@ pub struct Name {
 impl Serialize for Name {
     fn serialize(&self) -> JsonValue {
         object!(
-        "type" => "Name",
-        "data" => object!(
-                "name" => self.name.clone(),
-            )
+            "name" => self.name.clone(),
         )
     }
 }
 
 impl Deserialize for Name {
-    fn deserialize(json: &JsonValue) -> Self {
-        Name {
-            name: json["name"].as_str().unwrap().to_string(),
-        }
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(Name {
+            name: json["name"].read_str()?.to_string(),
+        })
     }
 }
 
-impl Component for Name {}
+impl Component for Name {
+    const TAG: &'static str = "Name";
+}
 
 
 /// Basic stats for any creature
@@ -269,10 +317,14 @@ pub struct Actor {
     power: i32,
     defense: i32,
     pub xp_reward: u32,
+    /// The energy gained per turn. A speed of `ACTION_ENERGY` acts once per turn.
+    pub speed: i32,
+    /// The actor can act as long as it has at least `ACTION_ENERGY` left
+    pub energy: i32,
 }
 
 impl Actor {
-    pub fn new(entity_id: EntityId, max_hp: u32, power: i32, defense: i32, xp_reward: u32) -> Actor {
+    pub fn new(entity_id: EntityId, max_hp: u32, power: i32, defense: i32, xp_reward: u32, speed: i32) -> Actor {
         Actor {
             entity_id,
             max_hp,
@@ -280,9 +332,15 @@ impl Actor {
             power,
             defense,
             xp_reward,
+            speed,
+            energy: speed,
         }
     }
 
+    pub fn can_act(&self) -> bool {
+        self.energy >= ACTION_ENERGY
+    }
+
     /// Take a specific amount of damage.
     pub fn take_damage(&mut self, damage: u32) {
         if self.hp < damage {
@@ -381,39 +439,160 @@ impl Serialize for Actor {
     fn serialize(&self) -> JsonValue {
 
         object!(
-        "type" => "Actor",
-        "data" => object!(
-                "id" => self.entity_id,
-                "max_hp" => self.max_hp,
-                "hp" => self.hp,
-                "power" => self.power,
-                "defense" => self.defense,
-                "xp_reward" => self.xp_reward,
-            )
+            "id" => self.entity_id,
+            "max_hp" => self.max_hp,
+            "hp" => self.hp,
+            "power" => self.power,
+            "defense" => self.defense,
+            "xp_reward" => self.xp_reward,
+            "speed" => self.speed,
+            "energy" => self.energy,
         )
     }
 }
 
 impl Deserialize for Actor {
-    fn deserialize(json: &JsonValue) -> Self {
-        Actor {
-            entity_id: json["id"].as_u16().unwrap(),
-            max_hp: json["max_hp"].as_u32().unwrap(),
-            hp: json["hp"].as_u32().unwrap(),
-            power: json["power"].as_i32().unwrap(),
-            defense: json["defense"].as_i32().unwrap(),
-            xp_reward: json["xp_reward"].as_u32().unwrap(),
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(Actor {
+            entity_id: EntityId::deserialize(&json["id"])?,
+            max_hp: json["max_hp"].read_u32()?,
+            hp: json["hp"].read_u32()?,
+            power: json["power"].read_i32()?,
+            defense: json["defense"].read_i32()?,
+            xp_reward: json["xp_reward"].read_u32()?,
+            speed: json["speed"].read_i32()?,
+            energy: json["energy"].read_i32()?,
+        })
+    }
+}
+
+impl Component for Actor {
+    const TAG: &'static str = "Actor";
+}
+
+/// What a ranged monster fires at its target
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Projectile {
+    Arrow,
+    Firebolt,
+}
+
+impl Projectile {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Projectile::Arrow => "an arrow",
+            Projectile::Firebolt => "a firebolt",
+        }
+    }
+}
+
+/// How a monster fights its target
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum AiBehavior {
+    /// Walk up to the target and attack it in melee
+    Melee,
+    /// Shoot at the target from afar. The monster has to see its target and be within `range`,
+    /// and backs off if the target comes closer than `preferred_distance`.
+    Ranged { range: u8, preferred_distance: u8, projectile: Projectile },
+}
+
+impl Serialize for AiBehavior {
+    fn serialize(&self) -> JsonValue {
+        match *self {
+            AiBehavior::Melee => object!("type" => "Melee"),
+            AiBehavior::Ranged { range, preferred_distance, projectile } => object!(
+                "type" => "Ranged",
+                "range" => range,
+                "preferred_distance" => preferred_distance,
+                "projectile" => format!("{:?}", projectile),
+            ),
+        }
+    }
+}
+
+impl Deserialize for AiBehavior {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(match json["type"].read_str()? {
+            "Melee" => AiBehavior::Melee,
+            "Ranged" => AiBehavior::Ranged {
+                range: json["range"].read_u8()?,
+                preferred_distance: json["preferred_distance"].read_u8()?,
+                projectile: match json["projectile"].read_str()? {
+                    "Arrow" => Projectile::Arrow,
+                    "Firebolt" => Projectile::Firebolt,
+                    _ => return Err(LoadError::invalid("a projectile", &json["projectile"]))
+                },
+            },
+            _ => return Err(LoadError::invalid("an AI behavior", &json["type"]))
+        })
+    }
+}
+
+/// How willing a monster is to fight its target
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Morale {
+    Steady,
+    /// Badly wounded and running away from its target
+    Fleeing,
+    /// Was cornered while fleeing and fights back, until it is healed again
+    Desperate,
+}
+
+/// What a monster remembers about its target while it can't see it
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum AiMemory {
+    /// Has no idea where its target is, and wanders around
+    Forgotten,
+    /// Walks to the position where it saw its target the last time
+    LastSeen((i32, i32)),
+    /// Searches the area around the position where it lost its target for a few more turns
+    Searching { around: (i32, i32), turns: u32 },
+}
+
+/// How far a searching monster strays from the position where it lost its target
+const SEARCH_RADIUS: f64 = 3.0;
+
+impl Serialize for AiMemory {
+    fn serialize(&self) -> JsonValue {
+        match *self {
+            AiMemory::Forgotten => object!("type" => "Forgotten"),
+            AiMemory::LastSeen(position) => object!(
+                "type" => "LastSeen",
+                "x" => position.0,
+                "y" => position.1,
+            ),
+            AiMemory::Searching { around, turns } => object!(
+                "type" => "Searching",
+                "x" => around.0,
+                "y" => around.1,
+                "turns" => turns,
+            ),
         }
     }
 }
 
-impl Component for Actor {}
+impl Deserialize for AiMemory {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(match json["type"].read_str()? {
+            "Forgotten" => AiMemory::Forgotten,
+            "LastSeen" => AiMemory::LastSeen((json["x"].read_i32()?, json["y"].read_i32()?)),
+            "Searching" => AiMemory::Searching {
+                around: (json["x"].read_i32()?, json["y"].read_i32()?),
+                turns: json["turns"].read_u32()?,
+            },
+            _ => return Err(LoadError::invalid("an AI memory", &json["type"]))
+        })
+    }
+}
 
 pub struct MonsterAi {
     entity_id: EntityId,
     target_id: Option<EntityId>,
     fov_map: Map,
-    chase_target: bool
+    chase_target: bool,
+    pub behavior: AiBehavior,
+    morale: Morale,
+    memory: AiMemory,
 }
 
 impl MonsterAi {
@@ -423,19 +602,88 @@ impl MonsterAi {
             entity_id,
             target_id:None,
             fov_map: Map::new(1,1),
-            chase_target: false
+            chase_target: false,
+            behavior: AiBehavior::Melee,
+            morale: Morale::Steady,
+            memory: AiMemory::Forgotten,
         }
     }
 
+    /// Create the AI of a monster which shoots at its target from a distance
+    pub fn ranged(entity_id: EntityId, range: u8, preferred_distance: u8, projectile: Projectile) -> MonsterAi {
+        MonsterAi {
+            behavior: AiBehavior::Ranged { range, preferred_distance, projectile },
+            ..MonsterAi::new(entity_id)
+        }
+    }
+
+    pub fn is_fleeing(&self) -> bool {
+        self.morale == Morale::Fleeing
+    }
+
+    /// Let the monster flee once it is badly wounded, and regain its courage once it is healed.
+    ///
+    /// Returns the new morale if it changed.
+    pub fn update_morale(&mut self, hp: u32, max_hp: u32, settings: &Settings) -> Option<Morale> {
+        let percent = hp * 100 / max_hp.max(1);
+        let morale = match self.morale {
+            Morale::Steady if percent <= settings.flee_hp_percent() => Morale::Fleeing,
+            Morale::Fleeing | Morale::Desperate if percent >= settings.courage_hp_percent() => Morale::Steady,
+            morale => morale
+        };
+
+        if morale == self.morale {
+            None
+        } else {
+            self.morale = morale;
+            Some(morale)
+        }
+    }
+
+    /// The monster has nowhere left to run and fights back
+    pub fn set_cornered(&mut self) {
+        self.morale = Morale::Desperate;
+    }
+
+    #[cfg(test)]
+    pub fn memory(&self) -> AiMemory {
+        self.memory
+    }
+
+    /// Remember where the target was seen, or count down how long the monster keeps searching
+    /// for it. `target_position` is `None` while the monster can't see its target.
+    pub fn update_memory(&mut self, position: (i32, i32), target_position: Option<(i32, i32)>, settings: &Settings) {
+        self.memory = match (target_position, self.memory) {
+            (Some(target), _) => AiMemory::LastSeen(target),
+            (None, AiMemory::LastSeen(last_seen)) => {
+                let arrived = Position { position, ..Position::new(self.entity_id, false) }.distance_to(last_seen) < 1.5;
+                if arrived {
+                    AiMemory::Searching { around: last_seen, turns: settings.monster_search_turns() }
+                } else {
+                    AiMemory::LastSeen(last_seen)
+                }
+            }
+            (None, AiMemory::Searching { turns: 0, .. }) => AiMemory::Forgotten,
+            (None, AiMemory::Searching { around, turns }) => AiMemory::Searching { around, turns: turns - 1 },
+            (None, AiMemory::Forgotten) => AiMemory::Forgotten,
+        };
+    }
+
     pub fn set_target(&mut self, target_id: EntityId) {
         self.target_id = Some(target_id)
     }
 
+    pub fn target_id(&self) -> Option<EntityId> {
+        self.target_id
+    }
+
     pub fn has_no_target(&self) -> bool {
         return self.target_id.is_none()
     }
 
-    pub fn calculate_turn(&self, ecs: &Ecs, map: &GameMap, settings: &Settings) -> EntityAction {
+    /// Chase the target while it is in sight. Otherwise walk to where it was seen the last
+    /// time, search the area around it, and start to wander once the target is forgotten.
+    pub fn calculate_turn(&self, ecs: &Ecs, map: &GameMap, settings: &Settings, rng: &mut GameRng) -> EntityAction {
 
         if !self.is_within_ai_distance(ecs, settings) {
             EntityAction::Idle
@@ -446,13 +694,38 @@ impl MonsterAi {
                 (true, Some(monster_position)) => {
                     self.calculate_movement(ecs, monster_position, map)
                 }
+                (false, Some(monster_position)) => match self.memory {
+                    AiMemory::LastSeen(last_seen) => {
+                        match monster_position.calculate_move_astar_to(ecs, map, last_seen) {
+                            Some(pos) => EntityAction::MoveTo(self.entity_id, pos),
+                            None => EntityAction::Idle
+                        }
+                    }
+                    AiMemory::Searching { around, .. } => self.random_step(ecs, map, monster_position, Some(around), rng),
+                    AiMemory::Forgotten => self.random_step(ecs, map, monster_position, None, rng),
+                },
                 _ => EntityAction::Idle
             }
         }
     }
 
-    pub fn is_chasing_target(&self) -> bool {
-        self.chase_target
+    /// Step into a random direction, without leaving the area around `around` if it is given.
+    /// The monster stays where it is if the step is blocked.
+    fn random_step(&self, ecs: &Ecs, map: &GameMap, monster_position: &Position, around: Option<(i32, i32)>, rng: &mut GameRng) -> EntityAction {
+        let (dx, dy) = random_direction(rng);
+        let destination = (monster_position.x() + dx, monster_position.y() + dy);
+
+        let strays = around.map_or(false, |around| {
+            Position { position: destination, ..Position::new(self.entity_id, false) }.distance_to(around) > SEARCH_RADIUS
+        });
+
+        if strays || map.is_move_blocked(destination.0, destination.1)
+            || map.tile_kind(destination.0, destination.1).is_hazard()
+            || !Position::is_blocked_by(ecs, destination).is_empty() {
+            EntityAction::Idle
+        } else {
+            EntityAction::MoveTo(self.entity_id, destination)
+        }
     }
 
 
@@ -490,14 +763,12 @@ impl MonsterAi {
 
 
     pub fn initialize_fov(&mut self, game_map: &GameMap) {
-        self.fov_map = Map::new(game_map.dimensions.0, game_map.dimensions.1);
+        self.fov_map = initialize_fov(game_map);
+    }
 
-        for x in 0..game_map.dimensions.0 {
-            for y in 0..game_map.dimensions.1 {
-                let tile = game_map.get_tile(x as usize, y as usize);
-                self.fov_map.set(x, y, !tile.block_sight, !tile.block_move);
-            }
-        }
+    /// Update what the monster can see after a tile of the map changed, e.g. a door was closed
+    pub fn update_fov_tile(&mut self, game_map: &GameMap, position: (i32, i32)) {
+        update_tile(&mut self.fov_map, game_map, position);
     }
 
     pub fn recompute_fov(&mut self, settings: &Settings, origin_x: i32, origin_y: i32) {
@@ -514,6 +785,18 @@ impl MonsterAi {
                 let target = (player_position.position.0, player_position.position.1);
                 let distance = monster_position.distance_to(target);
 
+                if let AiBehavior::Ranged { range, preferred_distance, .. } = self.behavior {
+                    if distance <= range as f64 && self.fov_map.is_in_fov(target.0, target.1) {
+                        if distance < preferred_distance as f64 {
+                            if let Some(pos) = monster_position.calculate_move_away(ecs, map, target) {
+                                return EntityAction::MoveTo(self.entity_id, pos);
+                            }
+                        }
+                        // Shoot from where it stands if there is no way to back off
+                        return EntityAction::RangedAttack(self.entity_id, self.target_id.unwrap());
+                    }
+                }
+
                 if distance >= 2.0 {
                     match monster_position.calculate_move_astar(ecs, map, self.target_id.unwrap()) {
                         Some(pos) => return EntityAction::MoveTo(self.entity_id, pos),
@@ -533,48 +816,61 @@ impl MonsterAi {
 impl Serialize for MonsterAi {
     fn serialize(&self) -> JsonValue {
         object!(
-        "type" => "MonsterAi",
-        "data" => object!(
             "id" => self.entity_id,
             "target" => self.target_id,
-            "chase_target" => self.chase_target
-            )
+            "chase_target" => self.chase_target,
+            "behavior" => self.behavior.serialize(),
+            "morale" => format!("{:?}", self.morale),
+            "memory" => self.memory.serialize(),
         )
     }
 }
 
 impl Deserialize for MonsterAi {
-    fn deserialize(json: &JsonValue) -> Self {
-        MonsterAi {
-            entity_id: json["id"].as_u16().unwrap(),
-            target_id: json["target"].as_u16(),
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(MonsterAi {
+            entity_id: EntityId::deserialize(&json["id"])?,
+            target_id: if json["target"].is_null() {
+                None
+            } else {
+                Some(EntityId::deserialize(&json["target"])?)
+            },
             fov_map: Map::new(1,1 ),
-            chase_target: json["chase_target"].as_bool().unwrap_or(false)
-        }
+            chase_target: json["chase_target"].as_bool().unwrap_or(false),
+            behavior: AiBehavior::deserialize(&json["behavior"])?,
+            morale: match json["morale"].read_str()? {
+                "Steady" => Morale::Steady,
+                "Fleeing" => Morale::Fleeing,
+                "Desperate" => Morale::Desperate,
+                _ => return Err(LoadError::invalid("a morale", &json["morale"]))
+            },
+            memory: AiMemory::deserialize(&json["memory"])?,
+        })
     }
 }
 
-impl Component for MonsterAi {}
+impl Component for MonsterAi {
+    const TAG: &'static str = "MonsterAi";
+}
 
 pub struct Corpse {}
 
 impl Serialize for Corpse {
     fn serialize(&self) -> JsonValue {
-        object!(
-        "type" => "Corpse",
-        "data" => object!()
-        )
+        object!()
     }
 }
 
 impl Deserialize for Corpse {
-    fn deserialize(_json: &JsonValue) -> Self {
-        Corpse {
-        }
+    fn deserialize(_json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(Corpse {
+        })
     }
 }
 
-impl Component for Corpse {}
+impl Component for Corpse {
+    const TAG: &'static str = "Corpse";
+}
 
 pub struct Item {
     spell: Option<Spell>,
@@ -601,32 +897,28 @@ impl Item {
 impl Serialize for Item {
     fn serialize(&self) -> JsonValue {
 
-        let data = match self.spell {
+        match self.spell {
             Some(spell) => object!("spell" => spell.serialize()),
             _ => JsonValue::Null
-        };
-
-
-        object!(
-        "type" => "Item",
-        "data" => data
-        )
+        }
     }
 }
 
 impl Deserialize for Item {
-    fn deserialize(json: &JsonValue) -> Self {
-        Item {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(Item {
             spell: if json["spell"].is_null() {
                 None
             }  else {
-                Some(Spell::deserialize(&json["spell"]))
+                Some(Spell::deserialize(&json["spell"])?)
             }
-        }
+        })
     }
 }
 
-impl Component for Item {}
+impl Component for Item {
+    const TAG: &'static str = "Item";
+}
 
 pub struct Inventory {
     max_items: usize,
@@ -679,51 +971,86 @@ impl Serialize for Inventory {
         });
 
         object!(
-        "type" => "Inventory",
-        "data" => object!(
-                "max_items" => self.max_items,
-                "items" => items
-            )
+            "max_items" => self.max_items,
+            "items" => items
         )
 
     }
 }
 
 impl Deserialize for Inventory {
-    fn deserialize(json: &JsonValue) -> Self {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
         let mut items = vec![];
         for item_json in json["items"].members() {
-            items.push(item_json.as_u16().unwrap());
+            items.push(EntityId::deserialize(item_json)?);
         }
 
-        Inventory {
-            max_items: json["max_items"].as_usize().unwrap(),
+        Ok(Inventory {
+            max_items: json["max_items"].read_usize()?,
             items
-        }
+        })
     }
 }
 
-impl Component for Inventory {}
+impl Component for Inventory {
+    const TAG: &'static str = "Inventory";
+}
+
+/// The direction in which `Stairs` lead
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum StairsDirection {
+    Down,
+    Up,
+}
+
+/// Stairs which lead to another floor of the dungeon
+pub struct Stairs {
+    pub direction: StairsDirection,
+    /// The number of the floor the stairs lead to
+    pub destination: u8,
+}
 
-pub struct Stairs {}
+impl Stairs {
+    pub fn new(direction: StairsDirection, destination: u8) -> Stairs {
+        Stairs {
+            direction,
+            destination,
+        }
+    }
+}
 
 impl Serialize for Stairs {
     fn serialize(&self) -> JsonValue {
+        let direction = match self.direction {
+            StairsDirection::Down => "Down",
+            StairsDirection::Up => "Up",
+        };
+
         object!(
-        "type" => "Stair",
-        "data" => object!()
+            "direction" => direction,
+            "destination" => self.destination
         )
     }
 }
 
 impl Deserialize for Stairs {
-    fn deserialize(_json: &JsonValue) -> Self {
-        Stairs {
-        }
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        let direction = match json["direction"].read_str()? {
+            "Down" => StairsDirection::Down,
+            "Up" => StairsDirection::Up,
+            _ => return Err(LoadError::invalid("Down or Up", &json["direction"]))
+        };
+
+        Ok(Stairs {
+            direction,
+            destination: json["destination"].read_u8()?,
+        })
     }
 }
 
-impl Component for Stairs {}
+impl Component for Stairs {
+    const TAG: &'static str = "Stairs";
+}
 
 pub struct Level {
     entity_id: EntityId,
@@ -764,31 +1091,30 @@ impl Level {
 impl Serialize for Level {
     fn serialize(&self) -> JsonValue {
         object!(
-        "type" => "Level",
-            "data" => object!(
-                "id" => self.entity_id,
-                "xp" => self.xp_total,
-                "level" => self.level,
-                "base" => self.base,
-                "factor" => self.factor,
-            )
+            "id" => self.entity_id,
+            "xp" => self.xp_total,
+            "level" => self.level,
+            "base" => self.base,
+            "factor" => self.factor,
         )
     }
 }
 
 impl Deserialize for Level {
-    fn deserialize(json: &JsonValue) -> Self {
-        Level {
-            entity_id: json["id"].as_u16().unwrap(),
-            xp_total: json["xp"].as_u32().unwrap(),
-            level: json["level"].as_u8().unwrap(),
-            base: json["base"].as_u32().unwrap(),
-            factor: json["factor"].as_f32().unwrap(),
-        }
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(Level {
+            entity_id: EntityId::deserialize(&json["id"])?,
+            xp_total: json["xp"].read_u32()?,
+            level: json["level"].read_u8()?,
+            base: json["base"].read_u32()?,
+            factor: json["factor"].read_f32()?,
+        })
     }
 }
 
-impl Component for Level {}
+impl Component for Level {
+    const TAG: &'static str = "Level";
+}
 
 #[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
 pub enum EquipmentSlot {
@@ -805,14 +1131,14 @@ impl Serialize for EquipmentSlot {
 }
 
 impl Deserialize for EquipmentSlot {
-    fn deserialize(json: &JsonValue) -> Self {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
 
-        match json["type"].as_str().unwrap() {
+        Ok(match json["type"].read_str()? {
             "MainHand" =>  EquipmentSlot::MainHand,
             "OffHand" =>  EquipmentSlot::OffHand,
             "Armor" =>  EquipmentSlot::Armor,
             _ => EquipmentSlot::None
-        }
+        })
     }
 }
 
@@ -837,32 +1163,31 @@ impl Equippable {
     }
 }
 
-impl Component for Equippable {}
+impl Component for Equippable {
+    const TAG: &'static str = "Equippable";
+}
 
 impl Serialize for Equippable {
     fn serialize(&self) -> JsonValue {
         object!(
-        "type" => "Equippable",
-            "data" => object!(
-                "id" => self.entity_id,
-                "bonus_power" => self.bonus_power,
-                "bonus_defense" => self.bonus_defense,
-                "bonus_max_hp" => self.bonus_max_hp,
-                "slot" => self.slot.serialize(),
-            )
+            "id" => self.entity_id,
+            "bonus_power" => self.bonus_power,
+            "bonus_defense" => self.bonus_defense,
+            "bonus_max_hp" => self.bonus_max_hp,
+            "slot" => self.slot.serialize(),
         )
     }
 }
 
 impl Deserialize for Equippable {
-    fn deserialize(json: &JsonValue) -> Self {
-        Equippable {
-            entity_id: json["id"].as_u16().unwrap(),
-            bonus_power: json["bonus_power"].as_i32().unwrap(),
-            bonus_defense: json["bonus_defense"].as_i32().unwrap(),
-            bonus_max_hp: json["bonus_max_hp"].as_u32().unwrap(),
-            slot: EquipmentSlot::deserialize(&json["slot"]),
-        }
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(Equippable {
+            entity_id: EntityId::deserialize(&json["id"])?,
+            bonus_power: json["bonus_power"].read_i32()?,
+            bonus_defense: json["bonus_defense"].read_i32()?,
+            bonus_max_hp: json["bonus_max_hp"].read_u32()?,
+            slot: EquipmentSlot::deserialize(&json["slot"])?,
+        })
     }
 }
 
@@ -902,7 +1227,9 @@ impl Equipment {
 
 }
 
-impl Component for Equipment {}
+impl Component for Equipment {
+    const TAG: &'static str = "Equipment";
+}
 
 impl Serialize for Equipment {
 
@@ -916,35 +1243,148 @@ impl Serialize for Equipment {
         });
 
         object!(
-        "type" => "Equipment",
-        "data" => object!(
             "id" => self.entity_id,
             "slots" => slots
         )
-        )
     }
 }
 
 impl Deserialize for Equipment {
-    fn deserialize(json: &JsonValue) -> Self {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
 
         let mut slots : HashMap<EquipmentSlot, EntityId> = HashMap::new();
 
         for entity_json in json["slots"].members() {
 
-            let slot = EquipmentSlot::deserialize(&entity_json["slot"]);
-            let id = entity_json["id"].as_u16().unwrap();
+            let slot = EquipmentSlot::deserialize(&entity_json["slot"])?;
+            let id = EntityId::deserialize(&entity_json["id"])?;
 
             slots.insert(slot, id);
         }
 
 
-        Equipment {
-            entity_id: json["id"].as_u16().unwrap(),
+        Ok(Equipment {
+            entity_id: EntityId::deserialize(&json["id"])?,
             slots,
-        }
+        })
 
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecs::creature::CreatureTemplate;
+    use map_objects::tile::TileKind;
+    use game::simulation::Simulation;
+
+    #[test]
+    fn archers_shoot_from_a_distance() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let archer_position = sim.clear_area_around_player(4);
+        sim.spawn_creature(CreatureTemplate::Archer, archer_position);
+        let hp = sim.player_hp();
+
+        sim.wait(1);
+        assert!(sim.has_message("The ARCHER shoots an arrow at the PLAYER."));
+        assert_eq!(sim.player_hp(), hp - 3);
+    }
+
+    #[test]
+    fn archers_back_off() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let archer_position = sim.clear_area_around_player(2);
+        let archer_id = sim.spawn_creature(CreatureTemplate::Archer, archer_position);
+
+        sim.wait(1);
+        assert!(sim.distance_to_player(archer_id) > 2.0);
+        assert_eq!(sim.count_messages("The ARCHER shoots"), 0);
+    }
+
+    #[test]
+    fn wounded_monsters_flee() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+        sim.clear_area_around_player(5);
 
+        let (orc_id, _) = sim.spawn_next_to_player(CreatureTemplate::Orc);
+        sim.set_hp(orc_id, 4);
+
+        sim.wait(2);
+        assert!(sim.distance_to_player(orc_id) >= 2.0);
+        assert!(sim.has_message("The ORC flees!"));
+        assert_eq!(sim.count_messages("The ORC attacks"), 0);
+    }
+
+    #[test]
+    fn cornered_monsters_fight_back() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+        sim.clear_area_around_player(3);
+
+        let (x, y) = sim.player_position();
+        let orc_position = (x + 1, y);
+        for dx in -1..2 {
+            for dy in -1..2 {
+                let position = (orc_position.0 + dx, orc_position.1 + dy);
+                if position != orc_position && position != (x, y) {
+                    sim.set_tile_kind(position, TileKind::Wall);
+                }
+            }
+        }
+
+        let orc_id = sim.spawn_creature(CreatureTemplate::Orc, orc_position);
+        sim.set_hp(orc_id, 4);
+
+        sim.wait(1);
+        assert_eq!(sim.position_of(orc_id), Some(orc_position));
+        assert!(sim.has_message("The ORC is cornered and fights back!"));
+        assert!(sim.count_messages("The ORC attacks") > 0);
+    }
+
+    #[test]
+    fn monsters_search_where_they_lost_their_target() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+        sim.clear_area_around_player(6);
+
+        let (x, y) = sim.player_position();
+        let orc_id = sim.spawn_creature(CreatureTemplate::Orc, (x + 4, y));
+        sim.wait(1);
+        assert_eq!(sim.ai_memory(orc_id), Some(AiMemory::LastSeen((x, y))));
+
+        // Hide behind a wall
+        sim.build_wall(-2, 6);
+        sim.teleport_player((x - 4, y));
+
+        sim.wait(3);
+        match sim.ai_memory(orc_id) {
+            Some(AiMemory::Searching { around, .. }) => assert_eq!(around, (x, y)),
+            memory => panic!("The orc should search for the player, but remembers {:?}", memory)
+        }
+
+        let search_turns = sim.settings().monster_search_turns();
+        sim.wait(search_turns + 1);
+        assert_eq!(sim.ai_memory(orc_id), Some(AiMemory::Forgotten));
+        assert_eq!(sim.count_messages("The ORC attacks"), 0);
+    }
+
+    #[test]
+    fn unaware_monsters_wander() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+        sim.clear_area_around_player(6);
+        sim.build_wall(2, 6);
+
+        let (x, y) = sim.player_position();
+        let orc_id = sim.spawn_creature(CreatureTemplate::Orc, (x + 4, y));
+
+        sim.wait(5);
+        assert_ne!(sim.position_of(orc_id), Some((x + 4, y)));
+        assert_eq!(sim.ai_memory(orc_id), Some(AiMemory::Forgotten));
+    }
+}
@@ -1,18 +1,32 @@
 use tcod::colors;
 use ecs::Ecs;
-use ecs::component::{Position, Render, Name, MonsterAi, Actor, Inventory, Level};
+use ecs::component::{Position, Render, Name, MonsterAi, Actor, Inventory, Level, Projectile};
 use ecs::id::EntityId;
 use std::borrow::Cow;
 use render::RenderOrder;
 use map_objects::map::GameMap;
 use random_utils::random_choice_index;
 use random_utils::by_dungeon_level;
+use random_utils::GameRng;
 use ecs::component::Equipment;
+use game::system::ACTION_ENERGY;
+use ecs::status::{StatusEffect, StatusKind, Venomous};
 
 /// Templates for common Creature types
+#[derive(Clone)]
 pub enum CreatureTemplate {
     Troll,
     Orc,
+    /// Weak, but acts twice per turn
+    Bat,
+    /// Tough, but only acts every other turn
+    Zombie,
+    /// Its bite is poisonous
+    Spider,
+    /// Shoots arrows and keeps its distance
+    Archer,
+    /// Hurls firebolts from afar
+    Shaman,
     Player, // The player must stay on the last position, otherwise the random creation will create players
 }
 
@@ -23,6 +37,26 @@ impl CreatureTemplate {
             CreatureTemplate::Player => CreatureTemplate::create_player_from_template(ecs),
             CreatureTemplate::Troll => CreatureTemplate::create_troll_from_template(ecs),
             CreatureTemplate::Orc => CreatureTemplate::create_orc_from_template(ecs),
+            CreatureTemplate::Bat => CreatureTemplate::create_bat_from_template(ecs),
+            CreatureTemplate::Zombie => CreatureTemplate::create_zombie_from_template(ecs),
+            CreatureTemplate::Spider => CreatureTemplate::create_spider_from_template(ecs),
+            CreatureTemplate::Archer => CreatureTemplate::create_archer_from_template(ecs),
+            CreatureTemplate::Shaman => CreatureTemplate::create_shaman_from_template(ecs),
+        }
+    }
+
+    /// Find the template of a creature by its name, e.g. for the legend of a vault. The player
+    /// can't be created this way.
+    pub fn from_name(name: &str) -> Option<CreatureTemplate> {
+        match name {
+            "Orc" => Some(CreatureTemplate::Orc),
+            "Troll" => Some(CreatureTemplate::Troll),
+            "Bat" => Some(CreatureTemplate::Bat),
+            "Zombie" => Some(CreatureTemplate::Zombie),
+            "Spider" => Some(CreatureTemplate::Spider),
+            "Archer" => Some(CreatureTemplate::Archer),
+            "Shaman" => Some(CreatureTemplate::Shaman),
+            _ => None
         }
     }
 
@@ -41,17 +75,22 @@ impl CreatureTemplate {
     }
 
     /// Create a random creature
-    pub fn create_random(ecs: &mut Ecs, game_map: &GameMap, pos: (i32, i32), floor_number: u8) -> Option<EntityId>  {
+    pub fn create_random(ecs: &mut Ecs, game_map: &GameMap, pos: (i32, i32), floor_number: u8, rng: &mut GameRng) -> Option<EntityId>  {
         let available_creatures = vec![
             (CreatureTemplate::Orc, 80),
             (CreatureTemplate::Troll, by_dungeon_level(Cow::Owned(vec![(15, 3), (30, 5), (60, 7)]), floor_number)),
+            (CreatureTemplate::Bat, by_dungeon_level(Cow::Owned(vec![(20, 2), (30, 4)]), floor_number)),
+            (CreatureTemplate::Zombie, by_dungeon_level(Cow::Owned(vec![(10, 3), (25, 5)]), floor_number)),
+            (CreatureTemplate::Spider, by_dungeon_level(Cow::Owned(vec![(15, 3)]), floor_number)),
+            (CreatureTemplate::Archer, by_dungeon_level(Cow::Owned(vec![(15, 2), (25, 5)]), floor_number)),
+            (CreatureTemplate::Shaman, by_dungeon_level(Cow::Owned(vec![(10, 4), (20, 6)]), floor_number)),
         ];
 
         let chances = available_creatures.iter().map(|(_,chance)|{
             *chance
         }).collect();
 
-        let ref selection: (CreatureTemplate, i32) = available_creatures[random_choice_index(chances)];
+        let ref selection: (CreatureTemplate, i32) = available_creatures[random_choice_index(chances, rng)];
         selection.0.create_on_position(ecs, game_map, pos)
     }
 
@@ -64,7 +103,7 @@ impl CreatureTemplate {
         ecs.register_component(id, Position::new(id, true));
         ecs.register_component(id, Render::new(id, '@', colors::WHITE, RenderOrder::Actor));
         ecs.register_component(id, Name { name: "Player".to_string()});
-        ecs.register_component(id, Actor::new(id, 100, 4, 1, 0));
+        ecs.register_component(id, Actor::new(id, 100, 4, 1, 0, ACTION_ENERGY));
         ecs.register_component(id, Level::new(id, 1, 200, 0.75));
         Some(id)
     }
@@ -74,7 +113,7 @@ impl CreatureTemplate {
         ecs.register_component(id, Position::new(id, true));
         ecs.register_component(id, Render::new(id, 'o', colors::DESATURATED_GREEN, RenderOrder::Actor));
         ecs.register_component(id, Name { name: "Orc".to_string() });
-        ecs.register_component(id, Actor::new(id, 20, 4, 0,35));
+        ecs.register_component(id, Actor::new(id, 20, 4, 0,35, ACTION_ENERGY));
         ecs.register_component(id, Level::new(id, 1, 0, 0.0));
         ecs.register_component(id, MonsterAi::new(id));
         Some(id)
@@ -85,9 +124,65 @@ impl CreatureTemplate {
         ecs.register_component(id, Position::new(id, true));
         ecs.register_component(id, Render::new(id, 'T', colors::DARKER_GREEN, RenderOrder::Actor));
         ecs.register_component(id, Name { name: "Troll".to_string()});
-        ecs.register_component(id, Actor::new(id, 30, 8, 2,100));
+        ecs.register_component(id, Actor::new(id, 30, 8, 2,100, ACTION_ENERGY));
+        ecs.register_component(id, Level::new(id, 1, 0, 0.0));
+        ecs.register_component(id, MonsterAi::new(id));
+        Some(id)
+    }
+
+    fn create_bat_from_template(ecs: &mut Ecs) -> Option<EntityId> {
+        let id = ecs.create_entity();
+        ecs.register_component(id, Position::new(id, true));
+        ecs.register_component(id, Render::new(id, 'b', colors::LIGHT_SEPIA, RenderOrder::Actor));
+        ecs.register_component(id, Name { name: "Bat".to_string()});
+        ecs.register_component(id, Actor::new(id, 6, 3, 0, 20, 2 * ACTION_ENERGY));
+        ecs.register_component(id, Level::new(id, 1, 0, 0.0));
+        ecs.register_component(id, MonsterAi::new(id));
+        Some(id)
+    }
+
+    fn create_zombie_from_template(ecs: &mut Ecs) -> Option<EntityId> {
+        let id = ecs.create_entity();
+        ecs.register_component(id, Position::new(id, true));
+        ecs.register_component(id, Render::new(id, 'Z', colors::CHARTREUSE, RenderOrder::Actor));
+        ecs.register_component(id, Name { name: "Zombie".to_string()});
+        ecs.register_component(id, Actor::new(id, 35, 7, 1, 60, ACTION_ENERGY / 2));
+        ecs.register_component(id, Level::new(id, 1, 0, 0.0));
+        ecs.register_component(id, MonsterAi::new(id));
+        Some(id)
+    }
+
+    fn create_spider_from_template(ecs: &mut Ecs) -> Option<EntityId> {
+        let id = ecs.create_entity();
+        ecs.register_component(id, Position::new(id, true));
+        ecs.register_component(id, Render::new(id, 's', colors::DARK_SEPIA, RenderOrder::Actor));
+        ecs.register_component(id, Name { name: "Spider".to_string()});
+        ecs.register_component(id, Actor::new(id, 12, 3, 0, 50, ACTION_ENERGY));
         ecs.register_component(id, Level::new(id, 1, 0, 0.0));
         ecs.register_component(id, MonsterAi::new(id));
+        ecs.register_component(id, Venomous { effect: StatusEffect::new(StatusKind::Poison, 5, 2) });
         Some(id)
     }
-}
\ No newline at end of file
+
+    fn create_archer_from_template(ecs: &mut Ecs) -> Option<EntityId> {
+        let id = ecs.create_entity();
+        ecs.register_component(id, Position::new(id, true));
+        ecs.register_component(id, Render::new(id, 'a', colors::DARK_ORANGE, RenderOrder::Actor));
+        ecs.register_component(id, Name { name: "Archer".to_string()});
+        ecs.register_component(id, Actor::new(id, 14, 4, 0, 45, ACTION_ENERGY));
+        ecs.register_component(id, Level::new(id, 1, 0, 0.0));
+        ecs.register_component(id, MonsterAi::ranged(id, 6, 4, Projectile::Arrow));
+        Some(id)
+    }
+
+    fn create_shaman_from_template(ecs: &mut Ecs) -> Option<EntityId> {
+        let id = ecs.create_entity();
+        ecs.register_component(id, Position::new(id, true));
+        ecs.register_component(id, Render::new(id, 'S', colors::FLAME, RenderOrder::Actor));
+        ecs.register_component(id, Name { name: "Shaman".to_string()});
+        ecs.register_component(id, Actor::new(id, 16, 6, 0, 70, ACTION_ENERGY));
+        ecs.register_component(id, Level::new(id, 1, 0, 0.0));
+        ecs.register_component(id, MonsterAi::ranged(id, 5, 3, Projectile::Firebolt));
+        Some(id)
+    }
+}
@@ -1,28 +1,263 @@
-/// A unique type for `Entity` IDs. This is just here to make sure it's easy to change
-/// to another actual primitive type if necessary.
-pub type EntityId = u16;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use json::JsonValue;
+
+use savegame::{Serialize, Deserialize, LoadError, ReadJson};
+
+/// A handle which identifies an `Entity`.
+///
+/// The handle consists of an index, which is reused after the `Entity` was destroyed, and the
+/// generation of that index. Each time an index is freed, its generation is increased, so a
+/// handle to a destroyed `Entity` will never point to another `Entity` which got the same index.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Debug, Default)]
+pub struct EntityId {
+    index: u32,
+    generation: u32,
+}
+
+impl EntityId {
+    /// A handle which never belongs to a living `Entity`
+    pub fn none() -> EntityId {
+        EntityId {
+            index: 0,
+            generation: 0,
+        }
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl Display for EntityId {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.index, self.generation)
+    }
+}
+
+impl From<EntityId> for JsonValue {
+    fn from(id: EntityId) -> JsonValue {
+        id.serialize()
+    }
+}
+
+impl Serialize for EntityId {
+    fn serialize(&self) -> JsonValue {
+        array![self.index, self.generation]
+    }
+}
+
+impl Deserialize for EntityId {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        // Save files from older versions only contain a plain number. These IDs were never
+        // reused, so they all belong to the first generation.
+        if let Some(index) = json.as_u32() {
+            return Ok(EntityId {
+                index,
+                generation: 1,
+            });
+        }
+
+        Ok(EntityId {
+            index: json[0].read_u32()?,
+            generation: json[1].read_u32()?,
+        })
+    }
+}
 
 /// Generator for IDs which are used to identify an `Entity`
+///
+/// Indices of destroyed entities are kept in a free list and reused by the next generated ID.
+/// An index whose generation can't be increased anymore is retired instead of being reused.
 pub struct IdGenerator {
-    id: EntityId
+    /// The current generation of every index which was handed out so far. Retired indices have
+    /// the generation 0, which no generated ID uses.
+    generations: Vec<u32>,
+    /// Whether the entity of an index is currently alive
+    alive: Vec<bool>,
+    /// All indices which are currently not in use
+    free: Vec<u32>,
 }
 
 impl IdGenerator {
     pub fn new() -> IdGenerator {
         IdGenerator {
-            id: 0,
+            generations: vec![],
+            alive: vec![],
+            free: vec![],
         }
     }
 
-    pub fn start_with(id: EntityId) -> IdGenerator {
-        IdGenerator {
-            id,
+    /// Rebuild a generator from the IDs of all living entities
+    ///
+    /// Every index which isn't used by any of the IDs will be added to the free list.
+    pub fn from_ids(ids: &[EntityId]) -> IdGenerator {
+        let mut generator = IdGenerator::new();
+
+        for id in ids {
+            let index = id.index as usize;
+            if generator.generations.len() <= index {
+                generator.generations.resize(index + 1, 1);
+                generator.alive.resize(index + 1, false);
+            }
+            generator.generations[index] = id.generation;
+            generator.alive[index] = true;
         }
+
+        generator.free = (0..generator.generations.len() as u32).rev().filter(|index| {
+            !generator.alive[*index as usize]
+        }).collect();
+
+        generator
     }
 
     /// Generate a new ID
     pub fn get_next_id(&mut self) -> EntityId {
-        self.id+=1;
-        self.id
+        match self.free.pop() {
+            Some(index) => {
+                self.alive[index as usize] = true;
+                EntityId {
+                    index,
+                    generation: self.generations[index as usize],
+                }
+            }
+            None => {
+                self.generations.push(1);
+                self.alive.push(true);
+                EntityId {
+                    index: self.generations.len() as u32 - 1,
+                    generation: 1,
+                }
+            }
+        }
     }
-}
\ No newline at end of file
+
+    /// Release an ID, so its index can be reused.
+    ///
+    /// Releasing an ID which isn't alive anymore does nothing. Once the generation of an index
+    /// reaches its maximum, the index is retired, so old handles can never become valid again.
+    pub fn release(&mut self, id: EntityId) {
+        if self.is_alive(id) {
+            let index = id.index as usize;
+            self.alive[index] = false;
+
+            match self.generations[index].checked_add(1) {
+                Some(generation) => {
+                    self.generations[index] = generation;
+                    self.free.push(id.index);
+                }
+                None => self.generations[index] = 0
+            }
+        }
+    }
+
+    /// Check if an ID was generated and not released yet
+    pub fn is_alive(&self, id: EntityId) -> bool {
+        match self.generations.get(id.index as usize) {
+            Some(generation) => *generation == id.generation && self.alive[id.index as usize],
+            None => false
+        }
+    }
+}
+
+impl Serialize for IdGenerator {
+    fn serialize(&self) -> JsonValue {
+        let mut generations = JsonValue::new_array();
+        for generation in self.generations.iter() {
+            generations.push(*generation);
+        }
+
+        let mut free = JsonValue::new_array();
+        for index in self.free.iter() {
+            free.push(*index);
+        }
+
+        object!(
+            "generations" => generations,
+            "free" => free
+        )
+    }
+}
+
+impl Deserialize for IdGenerator {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        let generations: Vec<u32> = json["generations"].members().map(|g| g.read_u32()).collect::<Result<_, _>>()?;
+        let free: Vec<u32> = json["free"].members().map(|i| i.read_u32()).collect::<Result<_, _>>()?;
+
+        let mut alive: Vec<bool> = generations.iter().map(|generation| *generation != 0).collect();
+        for index in free.iter() {
+            match alive.get_mut(*index as usize) {
+                Some(a) => *a = false,
+                None => return Err(LoadError::InvalidValue(format!("the free index {} was never used", index)))
+            }
+        }
+
+        Ok(IdGenerator {
+            generations,
+            alive,
+            free,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn released_ids_are_stale_after_their_index_is_reused() {
+        let mut generator = IdGenerator::new();
+        let first = generator.get_next_id();
+        generator.release(first);
+
+        let second = generator.get_next_id();
+        assert_eq!(second.index(), first.index());
+        assert_ne!(second.generation(), first.generation());
+        assert!(!generator.is_alive(first));
+        assert!(generator.is_alive(second));
+
+        // Releasing the stale handle must not free the index of the new entity
+        generator.release(first);
+        assert!(generator.is_alive(second));
+        assert_ne!(generator.get_next_id().index(), second.index());
+    }
+
+    #[test]
+    fn exhausted_indices_are_retired() {
+        let mut generator = IdGenerator::new();
+        let id = generator.get_next_id();
+        generator.generations[id.index() as usize] = u32::max_value();
+        let last = EntityId { index: id.index(), generation: u32::max_value() };
+
+        generator.release(last);
+        assert!(!generator.is_alive(last));
+        assert!(!generator.is_alive(EntityId { index: id.index(), generation: 1 }));
+        assert_ne!(generator.get_next_id().index(), id.index());
+    }
+
+    #[test]
+    fn generator_round_trips() {
+        let mut generator = IdGenerator::new();
+        let ids: Vec<EntityId> = (0..4).map(|_| generator.get_next_id()).collect();
+        generator.release(ids[1]);
+        generator.release(ids[2]);
+        generator.generations[ids[3].index() as usize] = u32::max_value();
+        generator.release(EntityId { index: ids[3].index(), generation: u32::max_value() });
+
+        let loaded = IdGenerator::deserialize(&generator.serialize()).unwrap();
+        assert!(loaded.is_alive(ids[0]));
+        assert!(!loaded.is_alive(ids[1]));
+        assert!(!loaded.is_alive(ids[2]));
+        assert_eq!(loaded.serialize(), generator.serialize());
+
+        let mut loaded = loaded;
+        assert_eq!(loaded.get_next_id(), EntityId { index: ids[2].index(), generation: 2 });
+        assert_eq!(loaded.get_next_id(), EntityId { index: ids[1].index(), generation: 2 });
+        assert_eq!(loaded.get_next_id().index(), 4);
+    }
+}
@@ -10,16 +10,20 @@ use ecs::component::Item;
 use ecs::spell::Spell;
 use random_utils::random_choice_index;
 use random_utils::by_dungeon_level;
+use random_utils::GameRng;
 use ecs::component::EquipmentSlot;
 use ecs::component::Equippable;
 use tcod::Color;
 
 /// Templates for common Creature types
+#[derive(Clone)]
 pub enum ItemTemplate {
     HealthPotion(u32),
     LightningScroll(u8, u32),
     FireballScroll(u8, u32),
     ConfusionScroll,
+    ParalysisScroll,
+    RegenerationPotion,
     Weapon(String, i32,),
     Shield(String, i32,),
     Armor(String, u32, )
@@ -33,12 +37,36 @@ impl ItemTemplate {
             ItemTemplate::LightningScroll(range, damage) => ItemTemplate::create_lightning_scroll_from_template(ecs, range, damage),
             ItemTemplate::FireballScroll(radius, damage) => ItemTemplate::create_fireball_scroll_from_template(ecs, radius, damage),
             ItemTemplate::ConfusionScroll => ItemTemplate::create_confusion_scroll_from_template(ecs),
+            ItemTemplate::ParalysisScroll => ItemTemplate::create_paralysis_scroll_from_template(ecs),
+            ItemTemplate::RegenerationPotion => ItemTemplate::create_regeneration_potion_from_template(ecs),
             ItemTemplate::Weapon(ref name, power) => ItemTemplate::create_weapon_from_template(ecs, name.clone(), power),
             ItemTemplate::Shield(ref name, defense) => ItemTemplate::create_shield_from_template(ecs, name.clone(), defense),
             ItemTemplate::Armor(ref name, hp) => ItemTemplate::create_armor_from_template(ecs, name.clone(), hp),
         }
     }
 
+    /// Find the template of an item by its name, e.g. for the legend of a vault
+    pub fn from_name(name: &str) -> Option<ItemTemplate> {
+        match name {
+            "Healing Potion" => Some(ItemTemplate::HealthPotion(40)),
+            "Lightning Scroll" => Some(ItemTemplate::LightningScroll(5, 40)),
+            "Fireball Scroll" => Some(ItemTemplate::FireballScroll(3, 25)),
+            "Confusion Scroll" => Some(ItemTemplate::ConfusionScroll),
+            "Paralysis Scroll" => Some(ItemTemplate::ParalysisScroll),
+            "Regeneration Potion" => Some(ItemTemplate::RegenerationPotion),
+            "Copper Dagger" => Some(ItemTemplate::Weapon(name.to_string(), 1)),
+            "Iron Axe" => Some(ItemTemplate::Weapon(name.to_string(), 2)),
+            "Mithril Sword" => Some(ItemTemplate::Weapon(name.to_string(), 4)),
+            "Wooden Buckler" => Some(ItemTemplate::Shield(name.to_string(), 1)),
+            "Iron Shield" => Some(ItemTemplate::Shield(name.to_string(), 2)),
+            "Mithril Shield" => Some(ItemTemplate::Shield(name.to_string(), 4)),
+            "Leather Armor" => Some(ItemTemplate::Armor(name.to_string(), 20)),
+            "Iron Armor" => Some(ItemTemplate::Armor(name.to_string(), 40)),
+            "Mithril Armor" => Some(ItemTemplate::Armor(name.to_string(), 60)),
+            _ => None
+        }
+    }
+
     /// Creates the Entity on a given Position
     pub fn create_on_position(&self, ecs: &mut Ecs, pos: (i32, i32)) -> Option<EntityId> {
         match self.create(ecs) {
@@ -54,10 +82,12 @@ impl ItemTemplate {
     }
 
     /// Create a random item
-    pub fn create_random(ecs: &mut Ecs, pos: (i32, i32), floor_number: u8) -> Option<EntityId>  {
+    pub fn create_random(ecs: &mut Ecs, pos: (i32, i32), floor_number: u8, rng: &mut GameRng) -> Option<EntityId>  {
         let available_creatures = vec![
             (ItemTemplate::HealthPotion(40), 70),
             (ItemTemplate::ConfusionScroll, by_dungeon_level(Cow::Owned(vec![(25, 4)]), floor_number)),
+            (ItemTemplate::ParalysisScroll, by_dungeon_level(Cow::Owned(vec![(15, 3)]), floor_number)),
+            (ItemTemplate::RegenerationPotion, by_dungeon_level(Cow::Owned(vec![(10, 2)]), floor_number)),
             (ItemTemplate::FireballScroll(3, 25), by_dungeon_level(Cow::Owned(vec![(25, 6)]), floor_number)),
             (ItemTemplate::LightningScroll(5,40), by_dungeon_level(Cow::Owned(vec![(10, 2)]), floor_number)),
             (ItemTemplate::Armor("Leather Armor".to_string(), 20), by_dungeon_level(Cow::Owned(vec![(10, 1),(0, 4)]), floor_number)),
@@ -75,7 +105,7 @@ impl ItemTemplate {
             *chance
         }).collect();
 
-        let ref selection: (ItemTemplate, i32) = available_creatures[random_choice_index(chances)];
+        let ref selection: (ItemTemplate, i32) = available_creatures[random_choice_index(chances, rng)];
         selection.0.create_on_position(ecs, pos)
     }
 
@@ -115,6 +145,24 @@ impl ItemTemplate {
         Some(id)
     }
 
+    fn create_paralysis_scroll_from_template(ecs: &mut Ecs) -> Option<EntityId> {
+        let id = ecs.create_entity();
+        ecs.register_component(id, Item::consumable(Spell::Paralysis(id)));
+        ecs.register_component(id, Position::new(id, false));
+        ecs.register_component(id, Render::new(id, '#', colors::LIGHT_BLUE, RenderOrder::Item));
+        ecs.register_component(id, Name { name: "Paralysis Scroll".to_string() });
+        Some(id)
+    }
+
+    fn create_regeneration_potion_from_template(ecs: &mut Ecs) -> Option<EntityId> {
+        let id = ecs.create_entity();
+        ecs.register_component(id, Item::consumable(Spell::Regeneration(id)));
+        ecs.register_component(id, Position::new(id, false));
+        ecs.register_component(id, Render::new(id, '!', colors::LIGHT_GREEN, RenderOrder::Item));
+        ecs.register_component(id, Name { name: "Regeneration Potion".to_string() });
+        Some(id)
+    }
+
     fn create_equippable(ecs: &mut Ecs, name: String, glyph: char, color: Color, power: i32, defense: i32, hp: u32, slot: EquipmentSlot ) -> Option<EntityId> {
         let id = ecs.create_entity();
         ecs.register_component(id, Item::equippable());
@@ -137,4 +185,32 @@ impl ItemTemplate {
     fn create_armor_from_template(ecs: &mut Ecs, name: String, hp: u32) -> Option<EntityId> {
         ItemTemplate::create_equippable(ecs, name, ')', colors::LIGHTER_CRIMSON, 0,0 , hp, EquipmentSlot::Armor)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::simulation::Simulation;
+    use settings::Settings;
+    use game::input::InputAction;
+    use game::state::GameState;
+
+    #[test]
+    fn pick_up_and_use_health_potion() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let position = sim.player_position();
+        sim.spawn_item(ItemTemplate::HealthPotion(40), position);
+
+        sim.send(InputAction::PickUp);
+        assert_eq!(sim.inventory_names(), vec!["Healing Potion".to_string()]);
+
+        sim.set_player_hp(10);
+        sim.send_all(vec![InputAction::ShowInventory, InputAction::SelectOption('a')]);
+
+        assert_eq!(sim.player_hp(), 50);
+        assert!(sim.inventory_names().is_empty());
+        assert_eq!(sim.state(), GameState::PlayersTurn);
+    }
+}
@@ -5,20 +5,26 @@ pub mod component;
 pub mod action;
 pub mod item;
 pub mod spell;
+pub mod status;
+pub mod query;
+pub mod registry;
 
 use json::JsonValue;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, BTreeMap};
 use std::any::TypeId;
 use std::any::Any;
 
-use savegame::{Serialize, Deserialize};
+use savegame::{Serialize, Deserialize, LoadError};
 
 use ecs::id::{IdGenerator, EntityId};
 use ecs::component::*;
+use ecs::query::{Query, Fetch};
+use ecs::registry::{ComponentRegistry, Registered};
 
 
-struct EcsStorage {
+/// The components of a single `Entity`, indexed by their type
+pub struct EcsStorage {
     data: HashMap<TypeId, Box<Any>>,
 }
 
@@ -71,75 +77,71 @@ impl EcsStorage {
     }
 }
 
-impl Serialize for EcsStorage {
-    fn serialize(&self) -> JsonValue {
+impl EcsStorage {
+    fn serialize(&self, registry: &ComponentRegistry) -> JsonValue {
+        registry.serialize_components(&self.data)
+    }
 
-        let mut components = JsonValue::new_array();
+    fn deserialize(json: &JsonValue, registry: &ComponentRegistry) -> Result<Self, LoadError> {
+        let mut storage = Self::new();
 
-        if let Some(c) = self.get::<Position>() {
-            components.push(c.serialize());
-        }
-        if let Some(c) = self.get::<Render>() {
-            components.push(c.serialize());
-        }
-        if let Some(c) = self.get::<Name>() {
-            components.push(c.serialize());
-        }
-        if let Some(c) = self.get::<Actor>() {
-            components.push(c.serialize());
-        }
-        if let Some(c) = self.get::<MonsterAi>() {
-            components.push(c.serialize());
-        }
-        if let Some(c) = self.get::<Corpse>() {
-            components.push(c.serialize());
-        }
-        if let Some(c) = self.get::<Item>() {
-            components.push(c.serialize());
-        }
-        if let Some(c) = self.get::<Inventory>() {
-            components.push(c.serialize());
-        }
-        if let Some(c) = self.get::<Stairs>() {
-            components.push(c.serialize());
-        }
-        if let Some(c) = self.get::<Level>() {
-            components.push(c.serialize());
-        }
-        if let Some(c) = self.get::<Equippable>() {
-            components.push(c.serialize());
-        }
-        if let Some(c) = self.get::<Equipment>() {
-            components.push(c.serialize());
+        for component_json in json.members() {
+            let (type_id, component) = registry.deserialize_component(component_json)?;
+            storage.data.insert(type_id, component);
         }
-        components
+
+        Ok(storage)
     }
 }
 
-impl Deserialize for EcsStorage {
-    fn deserialize(json: &JsonValue) -> Self {
-        let mut storage = Self::new();
+fn serialize_entities(storage: &BTreeMap<EntityId, EcsStorage>) -> JsonValue {
+    ComponentRegistry::with(|registry| {
+        let mut entities = JsonValue::new_array();
+        storage.iter().for_each(|(id, components)| {
+            entities.push(object!(
+                "id" => *id,
+                "components" => components.serialize(registry)
+            )).unwrap();
+        });
+        entities
+    })
+}
 
-        for component_json in json.members() {
+fn deserialize_entities(json: &JsonValue) -> Result<BTreeMap<EntityId, EcsStorage>, LoadError> {
+    ComponentRegistry::with(|registry| {
+        let mut storage : BTreeMap<EntityId, EcsStorage> = BTreeMap::new();
 
-            match component_json["type"].as_str().unwrap() {
-                "Position" => storage.register(Position::deserialize(&component_json["data"])),
-                "Render" => storage.register(Render::deserialize(&component_json["data"])),
-                "Name" => storage.register(Name::deserialize(&component_json["data"])),
-                "Actor" => storage.register(Actor::deserialize(&component_json["data"])),
-                "MonsterAi" => storage.register(MonsterAi::deserialize(&component_json["data"])),
-                "Corpse" => storage.register(Corpse::deserialize(&component_json["data"])),
-                "Item" => storage.register(Item::deserialize(&component_json["data"])),
-                "Inventory" => storage.register(Inventory::deserialize(&component_json["data"])),
-                "Stair" => storage.register(Stairs::deserialize(&component_json["data"])),
-                "Level" => storage.register(Level::deserialize(&component_json["data"])),
-                "Equippable" => storage.register(Equippable::deserialize(&component_json["data"])),
-                "Equipment" => storage.register(Equipment::deserialize(&component_json["data"])),
-                _ => ()
-            }
+        for entity_json in json.members() {
+            let id = EntityId::deserialize(&entity_json["id"])?;
+            let components = EcsStorage::deserialize(&entity_json["components"], registry)
+                .map_err(|e| e.context(&format!("entity {}", id)))?;
+
+            storage.insert(id, components);
         }
 
-        storage
+        Ok(storage)
+    })
+}
+
+/// Entities which were taken out of the `Ecs` without destroying them, e.g. because they are on a
+/// floor the player left.
+///
+/// Their ids stay reserved, so they can be attached to the `Ecs` again later.
+pub struct DetachedEntities {
+    storage: BTreeMap<EntityId, EcsStorage>,
+}
+
+impl Serialize for DetachedEntities {
+    fn serialize(&self) -> JsonValue {
+        serialize_entities(&self.storage)
+    }
+}
+
+impl Deserialize for DetachedEntities {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(DetachedEntities {
+            storage: deserialize_entities(json)?,
+        })
     }
 }
 
@@ -148,17 +150,18 @@ pub struct Ecs {
     /// Id of the Entity which represents the player
     pub player_entity_id: EntityId,
     id_generator: IdGenerator,
-    storage: HashMap<EntityId, EcsStorage>,
-    entities: HashMap<EntityId, Entity>,
+    /// Ordered by id, so all entities are always visited in the same order
+    storage: BTreeMap<EntityId, EcsStorage>,
+    entities: BTreeMap<EntityId, Entity>,
 }
 
 impl Ecs {
     pub fn initialize() -> Ecs {
         Ecs {
             id_generator: IdGenerator::new(),
-            player_entity_id: 0,
-            storage: HashMap::new(),
-            entities: HashMap::new(),
+            player_entity_id: EntityId::none(),
+            storage: BTreeMap::new(),
+            entities: BTreeMap::new(),
         }
     }
 
@@ -173,6 +176,8 @@ impl Ecs {
     }
 
     /// Remove an `Entity` from the game.
+    ///
+    /// The id of the entity will be released, so all remaining handles to it become stale.
     pub fn destroy_entity(&mut self, entity_id: &EntityId) {
 
         if self.entities.contains_key(entity_id) {
@@ -182,9 +187,36 @@ impl Ecs {
             self.storage.remove(entity_id);
         }
 
+        self.id_generator.release(*entity_id);
+    }
+
+    /// Take entities out of the `Ecs` without releasing their ids. They can't be accessed until
+    /// they are attached again.
+    pub fn detach_entities(&mut self, ids: &[EntityId]) -> DetachedEntities {
+        let storage = ids.iter().filter_map(|id| {
+            self.entities.remove(id);
+            self.storage.remove(id).map(|components| (*id, components))
+        }).collect();
+
+        DetachedEntities { storage }
+    }
+
+    /// Put detached entities back into the `Ecs`
+    pub fn attach_entities(&mut self, detached: DetachedEntities) {
+        for (id, components) in detached.storage {
+            self.entities.insert(id, Entity {});
+            self.storage.insert(id, components);
+        }
+    }
+
+    /// Check if an id belongs to an `Entity` which wasn't destroyed yet
+    pub fn is_alive(&self, entity_id: EntityId) -> bool {
+        self.id_generator.is_alive(entity_id)
     }
 
     /// Get a reference to a `Component` of a specified entity
+    ///
+    /// Returns `None` if the id is stale, i.e. the entity was destroyed in the meantime.
     pub fn get_component<T>(&self, entity_id: EntityId) -> Option<&T>
         where T: Component + Any {
         if let Some(c) = self.storage.get(&entity_id).map(|storage| {
@@ -208,38 +240,28 @@ impl Ecs {
         }
     }
 
-    /// Get a `HashMap` of the specified `Component` indexed by the `EntitiyId`
-    pub fn get_all<T: Component + Any>(&self) -> HashMap<EntityId, &T>
-        where T: Component {
-        let entity_ids = self.storage.keys().cloned();
-
-        let mut component_map = HashMap::new();
-
-        for entity_id in entity_ids.filter(|id| {
-            self.has_component::<T>(*id)
-        }) {
-            component_map.insert(entity_id, self.get_component::<T>(entity_id).unwrap());
-        }
-        component_map
+    /// Iterate over all entities which own a specific set of `Component`s, e.g.
+    /// `ecs.query::<(&Position, Option<&Actor>)>()`.
+    ///
+    /// Required components are requested as `&T`, optional ones as `Option<&T>`.
+    pub fn query<'a, F>(&'a self) -> Query<'a, F>
+        where F: Fetch<'a> {
+        Query::new(self.storage.iter())
     }
 
-
-    /// Get a `Vector` of  all `EntitiyId`s which own a specific `Component`
+    /// Get a `Vector` of  all `EntitiyId`s which own a specific `Component`, ordered by id
     pub fn get_all_ids<T: Component + Any>(&self) -> Vec<EntityId>
         where T: Component {
-        let entity_ids = self.storage.keys().cloned();
-
-        entity_ids.filter(|id| {
-            self.has_component::<T>(*id)
-        }).collect()
+        self.query::<&T>().map(|(id, _)| id).collect()
     }
 
     /// Register a component for a specific Entity.
     ///
     /// No Error handling if adding a Component to an Entity
-    /// which doesn't exist.
+    /// which doesn't exist. Only components in the `ComponentRegistry` can be added, so every
+    /// component of an entity can be saved.
     pub fn register_component<T>(&mut self, entity_id: EntityId, component: T)
-        where T: Component {
+        where T: Registered {
         match self.storage.get_mut(&entity_id) {
             Some(storage) => {
                 storage.register(component);
@@ -274,49 +296,55 @@ impl Ecs {
 
 impl Serialize for Ecs {
     fn serialize(&self) -> JsonValue {
-        let mut entities = JsonValue::new_array();
-        self.storage.iter().for_each(|(id, components)| {
-            entities.push(object!(
-                "id" => *id,
-                "components" => components.serialize()
-            ));
-
-        });
-
         object!(
         "player" => self.player_entity_id,
-        "entities" => entities
+        "id_generator" => self.id_generator.serialize(),
+        "entities" => serialize_entities(&self.storage)
         )
     }
 }
 
 impl Deserialize for Ecs {
-    fn deserialize(json: &JsonValue) -> Self {
-
-        let mut highest_id : EntityId = 0;
-        let mut storage : HashMap<EntityId, EcsStorage> = HashMap::new();
-        let mut entities : HashMap<EntityId, Entity> = HashMap::new();
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
 
-        for entity_json in json["entities"].members() {
-            let id = entity_json["id"].as_u16().unwrap();
+        let storage = deserialize_entities(&json["entities"])?;
+        let entities = storage.keys().map(|id| (*id, Entity{})).collect();
 
-            if id > highest_id {
-                highest_id = id;
-            }
-
-            entities.insert(id, Entity{});
-            storage.insert(id, EcsStorage::deserialize(&entity_json["components"]));
-        }
-
-        Ecs {
-            id_generator: IdGenerator::start_with(highest_id),
-            player_entity_id: json["player"].as_u16().unwrap(),
+        Ok(Ecs {
+            id_generator: IdGenerator::deserialize(&json["id_generator"])?,
+            player_entity_id: EntityId::deserialize(&json["player"])?,
             storage,
-            entities: HashMap::new(),
-        }
+            entities,
+        })
 
     }
 }
 
 /// A generic representation of things like NPCs, Monsters, Items, ... and of course, of the player, in the game.
-pub struct Entity {}
\ No newline at end of file
+pub struct Entity {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_handles_find_no_components() {
+        let mut ecs = Ecs::initialize();
+        let old = ecs.create_entity();
+        ecs.register_component(old, Name { name: "Old".to_string() });
+        ecs.destroy_entity(&old);
+
+        let new = ecs.create_entity();
+        ecs.register_component(new, Name { name: "New".to_string() });
+        assert_eq!(new.index(), old.index());
+
+        assert!(!ecs.is_alive(old));
+        assert!(ecs.get_component::<Name>(old).is_none());
+        assert!(!ecs.has_component::<Name>(old));
+        assert_eq!(ecs.get_component::<Name>(new).map(|n| n.name.as_str()), Some("New"));
+
+        // Destroying the stale handle again leaves the new entity alone
+        ecs.destroy_entity(&old);
+        assert!(ecs.is_alive(new));
+    }
+}
@@ -0,0 +1,84 @@
+use std::marker::PhantomData;
+use std::collections::btree_map;
+
+use ecs::EcsStorage;
+use ecs::id::EntityId;
+use ecs::component::Component;
+
+/// A set of `Component`s which can be fetched from the storage of a single `Entity`.
+///
+/// Implemented for `&T` (the component is required), `Option<&T>` (the component is optional)
+/// and for tuples of two to four of these.
+pub trait Fetch<'a> {
+    type Item;
+
+    /// Fetch the components, or return `None` if a required component is missing.
+    fn fetch(storage: &'a EcsStorage) -> Option<Self::Item>;
+}
+
+impl<'a, T> Fetch<'a> for &'a T
+    where T: Component {
+    type Item = &'a T;
+
+    fn fetch(storage: &'a EcsStorage) -> Option<Self::Item> {
+        storage.get::<T>()
+    }
+}
+
+impl<'a, T> Fetch<'a> for Option<&'a T>
+    where T: Component {
+    type Item = Option<&'a T>;
+
+    fn fetch(storage: &'a EcsStorage) -> Option<Self::Item> {
+        Some(storage.get::<T>())
+    }
+}
+
+macro_rules! impl_fetch_for_tuple {
+    ($($name:ident),+) => {
+        impl<'a, $($name: Fetch<'a>),+> Fetch<'a> for ($($name,)+) {
+            type Item = ($($name::Item,)+);
+
+            fn fetch(storage: &'a EcsStorage) -> Option<Self::Item> {
+                Some(($($name::fetch(storage)?,)+))
+            }
+        }
+    }
+}
+
+impl_fetch_for_tuple!(A, B);
+impl_fetch_for_tuple!(A, B, C);
+impl_fetch_for_tuple!(A, B, C, D);
+
+/// Iterator over all entities which own a specific set of `Component`s, ordered by their id.
+///
+/// The query borrows the components directly from the `Ecs`, so nothing is allocated while
+/// iterating.
+pub struct Query<'a, F> {
+    entities: btree_map::Iter<'a, EntityId, EcsStorage>,
+    _marker: PhantomData<F>,
+}
+
+impl<'a, F> Query<'a, F>
+    where F: Fetch<'a> {
+    pub fn new(entities: btree_map::Iter<'a, EntityId, EcsStorage>) -> Self {
+        Query {
+            entities,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, F> Iterator for Query<'a, F>
+    where F: Fetch<'a> {
+    type Item = (EntityId, F::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((id, storage)) = self.entities.next() {
+            if let Some(components) = F::fetch(storage) {
+                return Some((*id, components));
+            }
+        }
+        None
+    }
+}
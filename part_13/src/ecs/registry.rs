@@ -0,0 +1,208 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+use tcod::colors;
+
+use json::JsonValue;
+
+use savegame::{LoadError, ReadJson};
+use ecs::id::EntityId;
+use ecs::component::*;
+use ecs::spell::Spell;
+use ecs::status::{StatusEffects, StatusEffect, StatusKind, Venomous};
+use render::RenderOrder;
+
+/// Everything which is needed to save and load a specific type of `Component`
+struct RegisteredComponent {
+    tag: &'static str,
+    type_id: TypeId,
+    serialize: fn(&Any) -> JsonValue,
+    deserialize: fn(&JsonValue) -> Result<Box<Any>, LoadError>,
+    /// A serialized example of the component, used to check if saving and loading works
+    example: JsonValue,
+}
+
+fn serialize_component<T>(component: &Any) -> JsonValue
+    where T: Component {
+    // The registry only calls this with components of the matching `TypeId`
+    component.downcast_ref::<T>().unwrap().serialize()
+}
+
+fn deserialize_component<T>(json: &JsonValue) -> Result<Box<Any>, LoadError>
+    where T: Component {
+    Ok(Box::new(T::deserialize(json)?))
+}
+
+/// Implemented for every `Component` in the `ComponentRegistry`.
+///
+/// Only registered components can be added to an `Entity`, so a component which is missing in
+/// the registry can't be stored in an entity and silently be dropped from save files.
+pub trait Registered: Component {}
+
+/// Register components with an example value each, and mark them as `Registered`
+macro_rules! register_components {
+    ($registry:ident, $($component:ty => $example:expr),* $(,)*) => {
+        $(impl Registered for $component {})*
+
+        fn register_all($registry: &mut ComponentRegistry) {
+            $($registry.register::<$component>($example);)*
+        }
+    }
+}
+
+register_components!(registry,
+    Position => Position::new(EntityId::none(), true),
+    Render => Render::new(EntityId::none(), '@', colors::WHITE, RenderOrder::Actor),
+    Name => Name { name: "Example".to_string() },
+    Actor => Actor::new(EntityId::none(), 10, 2, 1, 5, 100),
+    MonsterAi => MonsterAi::new(EntityId::none()),
+    Corpse => Corpse {},
+    Item => Item::consumable(Spell::Heal(EntityId::none(), 10)),
+    Inventory => Inventory::new(26),
+    Stairs => Stairs::new(StairsDirection::Down, 2),
+    Level => Level::new(EntityId::none(), 1, 200, 0.75),
+    Equippable => Equippable::new(EntityId::none(), 1, 2, 3, EquipmentSlot::MainHand),
+    Equipment => Equipment::new(EntityId::none()),
+    StatusEffects => {
+        let mut effects = StatusEffects::new();
+        effects.add(StatusEffect::new(StatusKind::Poison, 5, 2));
+        effects
+    },
+    Venomous => Venomous { effect: StatusEffect::new(StatusKind::Poison, 5, 2) },
+);
+
+thread_local! {
+    /// The registry is the same for every `Ecs`, so it is only built once
+    static REGISTRY: ComponentRegistry = ComponentRegistry::new();
+}
+
+/// A list of all `Component`s which can be stored in save files.
+///
+/// Each component is registered once with an example value in `register_components!`. Its tag
+/// and (de)serializer are taken from its `Component` implementation.
+pub struct ComponentRegistry {
+    components: Vec<RegisteredComponent>,
+    /// Additional tags under which a component can be loaded, e.g. names used by older versions
+    aliases: Vec<(&'static str, TypeId)>,
+}
+
+impl ComponentRegistry {
+    /// Create a registry which knows all components of the game
+    fn new() -> Self {
+        let mut registry = ComponentRegistry {
+            components: vec![],
+            aliases: vec![],
+        };
+
+        register_all(&mut registry);
+        registry.register_alias::<Stairs>("Stair");
+
+        registry
+    }
+
+    /// Access the registry of the game
+    pub fn with<F, R>(f: F) -> R
+        where F: FnOnce(&ComponentRegistry) -> R {
+        REGISTRY.with(f)
+    }
+
+    fn register<T>(&mut self, example: T)
+        where T: Component {
+        self.components.push(RegisteredComponent {
+            tag: T::TAG,
+            type_id: TypeId::of::<T>(),
+            serialize: serialize_component::<T>,
+            deserialize: deserialize_component::<T>,
+            example: example.serialize(),
+        });
+    }
+
+    fn register_alias<T>(&mut self, alias: &'static str)
+        where T: Component {
+        self.aliases.push((alias, TypeId::of::<T>()));
+    }
+
+    fn find_by_tag(&self, tag: &str) -> Option<&RegisteredComponent> {
+        let type_id = self.aliases.iter()
+            .find(|(alias, _)| *alias == tag)
+            .map(|(_, type_id)| *type_id);
+
+        self.components.iter().find(|c| {
+            c.tag == tag || Some(c.type_id) == type_id
+        })
+    }
+
+    /// Serialize all registered components of an `Entity`, in the order of registration.
+    pub fn serialize_components(&self, components: &HashMap<TypeId, Box<Any>>) -> JsonValue {
+        let mut json = JsonValue::new_array();
+
+        for registered in self.components.iter() {
+            if let Some(component) = components.get(&registered.type_id) {
+                json.push(object!(
+                    "type" => registered.tag,
+                    "data" => (registered.serialize)(component.as_ref())
+                ));
+            }
+        }
+        json
+    }
+
+    /// Deserialize a single component. Fails if the tag is unknown or the data is invalid.
+    pub fn deserialize_component(&self, json: &JsonValue) -> Result<(TypeId, Box<Any>), LoadError> {
+        let tag = json["type"].read_str()?;
+
+        let registered = self.find_by_tag(tag)
+            .ok_or_else(|| LoadError::invalid("a component type", &json["type"]))?;
+
+        let component = (registered.deserialize)(&json["data"])
+            .map_err(|e| e.context(&format!("component {}", tag)))?;
+
+        Ok((registered.type_id, component))
+    }
+
+    /// Check if every registered component is the same after being saved and loaded again.
+    ///
+    /// Returns the tags of all components which failed the check.
+    pub fn check_round_trip(&self) -> Result<(), Vec<&'static str>> {
+        let failed : Vec<&'static str> = self.components.iter().filter(|registered| {
+            match (registered.deserialize)(&registered.example) {
+                Ok(component) => (registered.serialize)(component.as_ref()) != registered.example,
+                Err(_) => true
+            }
+        }).map(|registered| registered.tag).collect();
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(failed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use savegame::Serialize;
+
+    #[test]
+    fn all_components_round_trip() {
+        assert_eq!(ComponentRegistry::with(|registry| registry.check_round_trip()), Ok(()));
+    }
+
+    #[test]
+    fn components_load_under_their_old_tags() {
+        let json = object!(
+            "type" => "Stair",
+            "data" => Stairs::new(StairsDirection::Down, 2).serialize()
+        );
+
+        let (type_id, _) = ComponentRegistry::with(|registry| registry.deserialize_component(&json)).unwrap();
+        assert_eq!(type_id, TypeId::of::<Stairs>());
+    }
+
+    #[test]
+    fn unknown_components_fail_to_load() {
+        let json = object!("type" => "Unknown", "data" => JsonValue::new_object());
+        assert!(ComponentRegistry::with(|registry| registry.deserialize_component(&json)).is_err());
+    }
+}
@@ -11,7 +11,17 @@ use ecs::component::Position;
 use tcod::Map;
 use ecs::action::EntityAction;
 
-use savegame::{Serialize, Deserialize};
+use ecs::status::{StatusEffect, StatusKind};
+
+use savegame::{Serialize, Deserialize, LoadError, ReadJson};
+
+/// The number of turns a confused monster stumbles around
+const CONFUSION_TURNS: u32 = 10;
+/// The number of turns a paralyzed monster can't act
+const PARALYSIS_TURNS: u32 = 5;
+/// The duration and the healing per turn of the regeneration potion
+const REGENERATION_TURNS: u32 = 20;
+const REGENERATION_AMOUNT: u32 = 2;
 
 pub struct SpellResult {
     pub message: Option<Message>,
@@ -68,6 +78,8 @@ pub enum Spell {
     Lightning(EntityId, u8, u32),
     Fireball(EntityId, u8, u32),
     Confusion(EntityId),
+    Paralysis(EntityId),
+    Regeneration(EntityId),
     None,
 }
 
@@ -76,7 +88,8 @@ impl Spell {
         match *self {
             Spell::Heal(item_id, amount) => self.heal(ecs, caster_id, item_id, amount),
             Spell::Lightning(item_id, range, damage) => self.lightning(ecs, fov_map, caster_id, item_id, range, damage),
-            Spell::Fireball(..) | Spell::Confusion(..) => SpellResult::targeting(*self, caster_id),
+            Spell::Regeneration(item_id) => self.regeneration(ecs, caster_id, item_id),
+            Spell::Fireball(..) | Spell::Confusion(..) | Spell::Paralysis(..) => SpellResult::targeting(*self, caster_id),
             _ => SpellResult::fail(None)
         }
     }
@@ -85,6 +98,7 @@ impl Spell {
         match *self {
             Spell::Fireball(item_id, radius, damage) => self.fireball_on_target(ecs, target_id, caster_id, item_id, radius, damage),
             Spell::Confusion(item_id) => self.confusion_on_target(ecs, target_id, caster_id, item_id),
+            Spell::Paralysis(item_id) => self.paralysis_on_target(ecs, target_id, caster_id, item_id),
             _ => SpellResult::fail(None)
         }
     }
@@ -139,10 +153,10 @@ impl Spell {
         // unwrap is safe.
         let target = ecs.get_component::<Position>(target_id).unwrap();
 
-        ecs.get_all::<Position>().iter().filter(|(id, p)| {
-            **id != target_id && ecs.has_component::<Actor>(**id) && p.distance_to(target.position) <= radius as f64
-        }).for_each(|(id, p)| {
-            let reaction = EntityAction::TakeDamage(*id, damage / p.distance_to(target.position) as u32, caster_id);
+        ecs.query::<(&Position, &Actor)>().filter(|(id, (p, _))| {
+            *id != target_id && p.distance_to(target.position) <= radius as f64
+        }).for_each(|(id, (p, _))| {
+            let reaction = EntityAction::TakeDamage(id, damage / p.distance_to(target.position) as u32, caster_id);
             spell_result.add_reaction(reaction);
         });
 
@@ -152,30 +166,30 @@ impl Spell {
     fn confusion_on_target(&self, ecs: &mut Ecs, target_id: EntityId, caster_id: EntityId, item_id: EntityId) -> SpellResult {
         let target_name = Self::get_entity_name(ecs, target_id).to_uppercase();
 
-        let new_ai_target = if let Some(target) = ecs.get_component::<Position>(target_id) {
-            match self.find_target(ecs, None, &target) {
-                Some((entity_id, _)) => {
-                    Some(entity_id)
-                }
-                None => None
-            }
-        } else { None };
+        let message = Message::new(
+            format!("The eyes of the {0} look vacant, as he starts to stumble around!", target_name), colors::PINK,
+        );
+        let reaction = EntityAction::AddStatusEffect(target_id, StatusEffect::new(StatusKind::Confusion, CONFUSION_TURNS, 0));
+        SpellResult::success(caster_id, item_id, Some(message), Some(reaction))
+    }
 
-        match new_ai_target {
-            Some(new_target_id) => {
-                let message = Message::new(
-                    format!("The eyes of the {0} look vacant, as he starts to blindly attack the nearest monster!", target_name), colors::PINK,
-                );
-                let reaction = EntityAction::SetAiTarget(target_id, new_target_id);
-                SpellResult::success(caster_id, item_id, Some(message), Some(reaction))
-            }
-            None => {
-                let message = Message::new(
-                    format!("The eyes of the {0} look vacant, but he finds no other monster to attack", target_name), colors::PINK,
-                );
-                SpellResult::fail(Some(message))
-            }
-        }
+    fn paralysis_on_target(&self, ecs: &mut Ecs, target_id: EntityId, caster_id: EntityId, item_id: EntityId) -> SpellResult {
+        let target_name = Self::get_entity_name(ecs, target_id).to_uppercase();
+
+        let message = Message::new(
+            format!("The {0} freezes in place!", target_name), colors::LIGHT_BLUE,
+        );
+        let reaction = EntityAction::AddStatusEffect(target_id, StatusEffect::new(StatusKind::Paralysis, PARALYSIS_TURNS, 0));
+        SpellResult::success(caster_id, item_id, Some(message), Some(reaction))
+    }
+
+    fn regeneration(&self, ecs: &mut Ecs, caster_id: EntityId, item_id: EntityId) -> SpellResult {
+        let entity_name = Self::get_entity_name(ecs, caster_id);
+
+        let message = Message::new(format!("{} feels the wounds closing", entity_name), colors::GREEN);
+        let reaction = EntityAction::AddStatusEffect(caster_id,
+                                                     StatusEffect::new(StatusKind::Regeneration, REGENERATION_TURNS, REGENERATION_AMOUNT));
+        SpellResult::success(caster_id, item_id, Some(message), Some(reaction))
     }
 
     fn lightning(&self, ecs: &mut Ecs, fov_map: &Map, caster_id: EntityId, item_id: EntityId, range: u8, damage: u32) -> SpellResult {
@@ -207,18 +221,16 @@ impl Spell {
     }
 
     fn find_target(&self, ecs: &Ecs, fov_map: Option<&Map>, caster: &Position) -> Option<(EntityId, u8)> {
-        let mut distances: Vec<(EntityId, u8)> = ecs.get_all::<Position>().iter().filter(|(id, p)| {
+        let mut distances: Vec<(EntityId, u8)> = ecs.query::<(&Position, &Actor)>().filter(|(id, (p, _))| {
             if let Some(fov) = fov_map {
-                **id != caster.entity_id
+                *id != caster.entity_id
                     && fov.is_in_fov(p.position.0, p.position.1)
-                    && ecs.has_component::<Actor>(**id)
             } else {
-                **id != caster.entity_id
-                    && ecs.has_component::<Actor>(**id)
+                *id != caster.entity_id
             }
 
-        }).map(|(id, p)| {
-            (*id, caster.distance_to(p.position) as u8)
+        }).map(|(id, (p, _))| {
+            (id, caster.distance_to(p.position) as u8)
         }).collect();
 
         distances.sort_by(|a, b| {
@@ -248,20 +260,79 @@ impl Serialize for Spell {
             Spell::Lightning(item_id, range, damage) => object!("type" => "Lightning", "data" => array![item_id, range, damage]),
             Spell::Fireball(item_id, radius, damage) => object!("type" => "Fireball", "data" => array![item_id, radius, damage]),
             Spell::Confusion(item_id) => object!("type" => "Confusion", "data" => array![item_id]),
+            Spell::Paralysis(item_id) => object!("type" => "Paralysis", "data" => array![item_id]),
+            Spell::Regeneration(item_id) => object!("type" => "Regeneration", "data" => array![item_id]),
             _ => object!("type" => "", "data" => array![])
         }
     }
 }
 
 impl Deserialize for Spell {
-    fn deserialize(json: &JsonValue) -> Self {
-
-        match json["type"].as_str().unwrap() {
-            "Heal" =>  Spell::Heal(json["data"][0].as_u16().unwrap(),json["data"][1].as_u32().unwrap()),
-            "Lightning" => Spell::Lightning(json["data"][0].as_u16().unwrap(),json["data"][1].as_u8().unwrap(),json["data"][2].as_u32().unwrap()),
-            "Fireball" => Spell::Fireball(json["data"][0].as_u16().unwrap(),json["data"][1].as_u8().unwrap(),json["data"][2].as_u32().unwrap()),
-            "Confusion" =>  Spell::Confusion(json["data"][0].as_u16().unwrap()),
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+
+        Ok(match json["type"].read_str()? {
+            "Heal" =>  Spell::Heal(EntityId::deserialize(&json["data"][0])?,json["data"][1].read_u32()?),
+            "Lightning" => Spell::Lightning(EntityId::deserialize(&json["data"][0])?,json["data"][1].read_u8()?,json["data"][2].read_u32()?),
+            "Fireball" => Spell::Fireball(EntityId::deserialize(&json["data"][0])?,json["data"][1].read_u8()?,json["data"][2].read_u32()?),
+            "Confusion" =>  Spell::Confusion(EntityId::deserialize(&json["data"][0])?),
+            "Paralysis" =>  Spell::Paralysis(EntityId::deserialize(&json["data"][0])?),
+            "Regeneration" =>  Spell::Regeneration(EntityId::deserialize(&json["data"][0])?),
             _ => Spell::None
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ecs::creature::CreatureTemplate;
+    use ecs::item::ItemTemplate;
+    use ecs::status::StatusKind;
+    use game::simulation::Simulation;
+    use settings::Settings;
+    use game::input::InputAction;
+    use game::state::GameState;
+
+    #[test]
+    fn fireball_kills_targeted_monster() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let position = sim.player_position();
+        sim.spawn_item(ItemTemplate::FireballScroll(3, 25), position);
+        sim.send(InputAction::PickUp);
+
+        let (orc_id, _) = sim.spawn_next_to_player(CreatureTemplate::Orc);
+
+        sim.send_all(vec![InputAction::ShowInventory, InputAction::SelectOption('a')]);
+        match sim.state() {
+            GameState::Targeting(..) => (),
+            _ => panic!("The fireball scroll didn't ask for a target")
         }
+
+        let (x, y) = sim.screen_position(sim.position_of(orc_id).unwrap());
+        sim.send(InputAction::SelectEntity(x, y));
+
+        assert!(sim.is_dead(orc_id));
+        assert!(sim.has_message("The ORC died."));
+        assert!(sim.inventory_names().is_empty());
+    }
+
+    #[test]
+    fn confusion_scroll_confuses_targeted_monster() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let position = sim.player_position();
+        sim.spawn_item(ItemTemplate::ConfusionScroll, position);
+        sim.send(InputAction::PickUp);
+
+        let (orc_id, _) = sim.spawn_next_to_player(CreatureTemplate::Orc);
+
+        sim.send_all(vec![InputAction::ShowInventory, InputAction::SelectOption('a')]);
+        let (x, y) = sim.screen_position(sim.position_of(orc_id).unwrap());
+        sim.send(InputAction::SelectEntity(x, y));
+
+        assert!(sim.has_status(orc_id, StatusKind::Confusion));
+        assert!(sim.inventory_names().is_empty());
     }
 }
@@ -0,0 +1,256 @@
+use json::JsonValue;
+use rand::prelude::*;
+use tcod::colors;
+use tcod::Color;
+
+use ecs::Ecs;
+use ecs::component::Component;
+use ecs::id::EntityId;
+use random_utils::GameRng;
+use savegame::{Serialize, Deserialize, LoadError, ReadJson};
+
+/// The kinds of timed effects an `Actor` can be under
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum StatusKind {
+    /// Deals its strength as damage at the end of every turn
+    Poison,
+    /// Every move goes into a random direction
+    Confusion,
+    /// Every turn is skipped
+    Paralysis,
+    /// Heals its strength at the end of every turn
+    Regeneration,
+}
+
+impl StatusKind {
+    /// The adjective used in messages and on the character screen
+    pub fn name(&self) -> &'static str {
+        match *self {
+            StatusKind::Poison => "poisoned",
+            StatusKind::Confusion => "confused",
+            StatusKind::Paralysis => "paralyzed",
+            StatusKind::Regeneration => "regenerating",
+        }
+    }
+
+    /// The short label shown in the panel
+    pub fn label(&self) -> &'static str {
+        match *self {
+            StatusKind::Poison => "Psn",
+            StatusKind::Confusion => "Cnf",
+            StatusKind::Paralysis => "Par",
+            StatusKind::Regeneration => "Reg",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match *self {
+            StatusKind::Poison => colors::CHARTREUSE,
+            StatusKind::Confusion => colors::PINK,
+            StatusKind::Paralysis => colors::LIGHT_BLUE,
+            StatusKind::Regeneration => colors::LIGHT_GREEN,
+        }
+    }
+}
+
+/// A single effect, which lasts for a number of turns
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub turns: u32,
+    /// The damage or healing per turn, unused by effects which don't need it
+    pub strength: u32,
+}
+
+impl StatusEffect {
+    pub fn new(kind: StatusKind, turns: u32, strength: u32) -> StatusEffect {
+        StatusEffect { kind, turns, strength }
+    }
+}
+
+impl Serialize for StatusEffect {
+    fn serialize(&self) -> JsonValue {
+        object!(
+            "kind" => format!("{:?}", self.kind),
+            "turns" => self.turns,
+            "strength" => self.strength,
+        )
+    }
+}
+
+impl Deserialize for StatusEffect {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        let kind = match json["kind"].read_str()? {
+            "Poison" => StatusKind::Poison,
+            "Confusion" => StatusKind::Confusion,
+            "Paralysis" => StatusKind::Paralysis,
+            "Regeneration" => StatusKind::Regeneration,
+            _ => return Err(LoadError::invalid("a status effect", &json["kind"]))
+        };
+
+        Ok(StatusEffect {
+            kind,
+            turns: json["turns"].read_u32()?,
+            strength: json["strength"].read_u32()?,
+        })
+    }
+}
+
+/// All timed effects an `Entity` is under. The `StatusEffectSystem` applies them and counts down
+/// their duration at the end of every turn.
+pub struct StatusEffects {
+    effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    pub fn new() -> StatusEffects {
+        StatusEffects {
+            effects: vec![],
+        }
+    }
+
+    /// Add an effect. An effect of the same kind is only replaced if the new one lasts longer.
+    pub fn add(&mut self, effect: StatusEffect) {
+        match self.effects.iter_mut().find(|e| e.kind == effect.kind) {
+            Some(existing) => if effect.turns > existing.turns {
+                *existing = effect
+            },
+            None => self.effects.push(effect)
+        }
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.effects.iter().any(|e| e.kind == kind)
+    }
+
+    pub fn effects(&self) -> &[StatusEffect] {
+        &self.effects
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// Count down the duration of every effect, and return all effects which ran out
+    pub fn tick(&mut self) -> Vec<StatusEffect> {
+        self.effects.iter_mut().for_each(|e| e.turns = e.turns.saturating_sub(1));
+
+        let expired = self.effects.iter().filter(|e| e.turns == 0).cloned().collect();
+        self.effects.retain(|e| e.turns > 0);
+        expired
+    }
+}
+
+impl Serialize for StatusEffects {
+    fn serialize(&self) -> JsonValue {
+        let mut json = JsonValue::new_array();
+        self.effects.iter().for_each(|e| json.push(e.serialize()).unwrap());
+        json
+    }
+}
+
+impl Deserialize for StatusEffects {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        let effects = json.members()
+            .map(StatusEffect::deserialize)
+            .collect::<Result<Vec<StatusEffect>, LoadError>>()?;
+
+        Ok(StatusEffects { effects })
+    }
+}
+
+impl Component for StatusEffects {
+    const TAG: &'static str = "StatusEffects";
+}
+
+/// The melee attacks of an `Actor` with this component put an effect on the target, e.g. poison
+pub struct Venomous {
+    pub effect: StatusEffect,
+}
+
+impl Serialize for Venomous {
+    fn serialize(&self) -> JsonValue {
+        self.effect.serialize()
+    }
+}
+
+impl Deserialize for Venomous {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(Venomous {
+            effect: StatusEffect::deserialize(json)?,
+        })
+    }
+}
+
+impl Component for Venomous {
+    const TAG: &'static str = "Venomous";
+}
+
+/// Check if an `Entity` is under an effect
+pub fn has_status(ecs: &Ecs, entity_id: EntityId, kind: StatusKind) -> bool {
+    ecs.get_component::<StatusEffects>(entity_id).map_or(false, |s| s.has(kind))
+}
+
+/// The direction a confused `Entity` stumbles into
+pub fn random_direction(rng: &mut GameRng) -> (i32, i32) {
+    [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)][rng.gen_range(0, 8)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecs::creature::CreatureTemplate;
+    use game::simulation::Simulation;
+    use settings::Settings;
+
+    #[test]
+    fn poison_and_regeneration_change_hp_over_time() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+        sim.set_player_hp(50);
+
+        let player = sim.player_id();
+        sim.add_status_effect(player, StatusEffect::new(StatusKind::Poison, 2, 3));
+        sim.add_status_effect(player, StatusEffect::new(StatusKind::Regeneration, 4, 1));
+
+        sim.wait(2);
+        assert_eq!(sim.player_hp(), 50 - 2 * 3 + 2);
+        assert!(sim.has_message("The PLAYER is no longer poisoned."));
+        assert!(!sim.has_status(player, StatusKind::Poison));
+
+        sim.wait(2);
+        assert_eq!(sim.player_hp(), 50 - 2 * 3 + 4);
+        assert!(sim.game().ecs.borrow().get_component::<StatusEffects>(player).is_none());
+    }
+
+    #[test]
+    fn confusion_wears_off() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let (orc_id, _) = sim.spawn_next_to_player(CreatureTemplate::Orc);
+        sim.add_status_effect(orc_id, StatusEffect::new(StatusKind::Confusion, 10, 0));
+        // Keep it from stumbling out of view, where it would lose its confusion unnoticed
+        sim.add_status_effect(orc_id, StatusEffect::new(StatusKind::Paralysis, 20, 0));
+        assert!(sim.has_status(orc_id, StatusKind::Confusion));
+
+        sim.wait(10);
+        assert!(!sim.has_status(orc_id, StatusKind::Confusion));
+        assert!(sim.has_message("The ORC is no longer confused."));
+    }
+
+    #[test]
+    fn paralyzed_monsters_skip_turns() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let (orc_id, _) = sim.spawn_next_to_player(CreatureTemplate::Orc);
+        sim.add_status_effect(orc_id, StatusEffect::new(StatusKind::Paralysis, 3, 0));
+
+        sim.wait(3);
+        assert_eq!(sim.count_messages("The ORC attacks"), 0);
+
+        sim.wait(1);
+        assert_eq!(sim.count_messages("The ORC attacks"), 1);
+    }
+}
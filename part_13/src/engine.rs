@@ -1,109 +1,327 @@
 use std::cell::RefCell;
 
-use tcod::console::{Console, Root};
+use tcod::colors;
 
 use game::{Game, state::GameState};
+use game::input::InputAction;
+use events::GameEvent;
+use message::Message;
+use ecs::registry::ComponentRegistry;
+use map_objects::vault::Vault;
 use render::render_all;
-use savegame;
+use savegame::{LoadError, SaveDirectory, SaveMetadata};
 use settings::Settings;
+use terminal::{Terminal, TcodTerminal};
 
 pub enum EngineAction {
     ToggleFullscreen,
     MousePos(i32, i32),
     StartGame(bool),
+    StartSeededGame(u64),
+    ListSaves,
+    LoadGame(usize),
+    DeleteSave(usize),
     QuitGame(bool),
-    CreateNextFloor,
+    ChangeFloor(u8),
+    /// The player fell down to the next floor
+    FallIntoChasm,
     Exit,
 }
 
 pub struct Engine<'engine> {
     pub game: RefCell<Game<'engine>>,
     pub settings: & 'engine Settings,
-    pub root_console: RefCell<Root>,
+    pub terminal: RefCell<Box<Terminal>>,
+    pub saves: SaveDirectory,
 
     pub state: GameState,
     pub mouse_pos: (i32, i32),
+    /// The metadata of all save slots, read when the list of saved games is opened
+    pub save_slots: Vec<Result<SaveMetadata, LoadError>>,
+    /// The reason why the last action in the main menu failed, shown below the menu
+    pub menu_error: Option<String>,
+    /// The number of turns since the game was saved the last time
+    turns_since_save: u32,
 }
 
 impl<'engine> Engine<'engine> {
-    pub fn run(settings: &'engine Settings) {
-        let mut engine = Engine::initialize(settings);
-
-        engine.game_loop();
+    /// Run the game in a window
+    pub fn run(settings: &'engine Settings, saves: SaveDirectory) {
+        Engine::run_on(settings, Box::new(TcodTerminal::new(settings)), saves);
     }
 
-    fn initialize(settings: &'engine Settings) -> Self {
+    /// Run the game on any `Terminal`, e.g. on a `HeadlessTerminal` without a window
+    pub fn run_on(settings: &'engine Settings, terminal: Box<Terminal>, saves: SaveDirectory) {
+        if let Err(tags) = ComponentRegistry::with(|registry| registry.check_round_trip()) {
+            panic!("Components can't be saved and loaded correctly: {}", tags.join(", "));
+        }
+        let vaults = Vault::load_all().unwrap_or_else(|e| panic!("The vaults can't be loaded: {}", e));
 
-        let root_console = Root::initializer()
-            .size(settings.screen_width(), settings.screen_height())
-            .title(settings.title())
-            .font(settings.font_path(), settings.font_layout())
-            .font_type(settings.font_type())
-            .init();
+        let mut engine = Engine::initialize(settings, terminal, vaults, saves);
 
+        engine.game_loop();
+    }
+
+    pub fn initialize(settings: &'engine Settings, terminal: Box<Terminal>, vaults: Vec<Vault>,
+                  saves: SaveDirectory) -> Self {
         Engine {
-            game: RefCell::new(Game::new(settings)),
+            game: RefCell::new(Game::new(settings, vaults)),
             settings,
-            root_console: RefCell::new(root_console),
+            terminal: RefCell::new(terminal),
+            saves,
             state: GameState::MainMenu,
             mouse_pos: (0, 0),
+            save_slots: vec![],
+            menu_error: None,
+            turns_since_save: 0,
         }
     }
 
-    fn start_game(&self, game: &mut Game , load_game: bool) {
+    /// Start a new game, or continue the game which was saved last. Without any saved game, a
+    /// new game is started.
+    fn start_game(&self, game: &mut Game , load_game: bool) -> Result<(), String> {
         if load_game {
-            match savegame::load() {
-                Some(game_json) => {
-                    game.load(game_json);
-                    return;
-                }
-                None => ()
-            };
+            if let Some(slot) = self.saves.latest_slot() {
+                return self.load_game(game, slot).map_err(|e| e.to_string());
+            }
+        }
+        self.start_new_game(game, None)
+    }
+
+    /// Start a new game in the first free save slot
+    fn start_new_game(&self, game: &mut Game, seed: Option<u64>) -> Result<(), String> {
+        match self.saves.free_slot() {
+            Some(slot) => {
+                game.start_new(seed);
+                game.slot = slot;
+                Ok(())
+            }
+            None => Err("All save slots are in use. Delete a saved game first.".to_string())
         }
-        game.start_new();
+    }
+
+    /// Save the game without leaving it. A failure is reported in the message log.
+    fn autosave(&self, game: &Game) {
+        if let Err(e) = self.saves.save(game) {
+            let text = format!("The game couldn't be saved: {}", e);
+            game.events.publish(GameEvent::Message(Message::new(text, colors::RED)));
+        }
+    }
+
+    fn load_game(&self, game: &mut Game, slot: usize) -> Result<(), LoadError> {
+        let save = self.saves.load(slot)?;
+        game.load(save.json)?;
+        game.slot = slot;
+
+        if let Some(e) = save.broken_save {
+            let text = format!("The saved game is damaged, its previous save was loaded instead. {}", e);
+            game.events.publish(GameEvent::Message(Message::new(text, colors::ORANGE)));
+        }
+        Ok(())
     }
 
     fn game_loop(&mut self) {
+        while !self.terminal.borrow().is_closed() && self.tick() {}
+    }
 
-        let mut game = self.game.borrow_mut();
+    /// Run one iteration of the game loop, with the next input of the terminal. Returns `false`
+    /// once the player exits the game.
+    pub fn tick(&mut self) -> bool {
+        let input_action = self.state.read_input(&mut **self.terminal.borrow_mut());
+        self.update(input_action)
+    }
 
-        'game_loop: while !self.root_console.borrow().window_closed() {
+    /// Draw the game, let the current state handle an input and carry out what it asks the engine
+    /// to do. Returns `false` once the player exits the game.
+    pub fn update(&mut self, input_action: Option<InputAction>) -> bool {
 
-            render_all(&self, &game);
+        let mut game = self.game.borrow_mut();
 
-            let result = self.state.run(&self, &game);
+        render_all(&self, &game);
 
-            if let Some(engine_action) = result.engine_action {
-                match engine_action {
-                    EngineAction::Exit => {
-                        break 'game_loop;
-                    }
-                    EngineAction::QuitGame(save) => {
-                        if save {
-                            savegame::save(&game);
-                        } else {
-                            savegame::delete();
+        let result = self.state.update(input_action, &game);
+        let mut next_state = result.next_state;
+
+        if let Some(engine_action) = result.engine_action {
+            match engine_action {
+                EngineAction::Exit => {
+                    return false;
+                }
+                EngineAction::QuitGame(save) => {
+                    self.turns_since_save = 0;
+                    if save {
+                        if let Err(e) = self.saves.save(&game) {
+                            self.menu_error = Some(format!("The game couldn't be saved: {}", e));
                         }
+                    } else if let Err(e) = self.saves.delete(game.slot) {
+                        self.menu_error = Some(format!("The game couldn't be deleted: {}", e));
                     }
-                    EngineAction::StartGame(load_game) => {
-                        self.root_console.borrow_mut().clear();
-                        self.start_game(&mut game, load_game);
+                }
+                EngineAction::StartGame(load_game) => {
+                    self.terminal.borrow_mut().clear();
+                    self.menu_error = None;
+                    if let Err(e) = self.start_game(&mut game, load_game) {
+                        self.menu_error = Some(e);
+                        next_state = GameState::MainMenu;
                     }
-                    EngineAction::ToggleFullscreen => {
-                        let fullscreen = self.root_console.borrow().is_fullscreen();
-                        self.root_console.borrow_mut().set_fullscreen(!fullscreen)
+                }
+                EngineAction::StartSeededGame(seed) => {
+                    self.terminal.borrow_mut().clear();
+                    self.menu_error = None;
+                    if let Err(e) = self.start_new_game(&mut game, Some(seed)) {
+                        self.menu_error = Some(e);
+                        next_state = GameState::MainMenu;
                     }
-                    EngineAction::MousePos(x, y) => {
-                        self.mouse_pos = (x as i32, y as i32);
+                }
+                EngineAction::ListSaves => {
+                    self.menu_error = None;
+                    self.save_slots = self.saves.list_slots();
+                }
+                EngineAction::LoadGame(slot) => {
+                    self.terminal.borrow_mut().clear();
+                    self.menu_error = None;
+                    if let Err(e) = self.load_game(&mut game, slot) {
+                        self.menu_error = Some(e.to_string());
+                        next_state = GameState::ShowSaveSlots;
                     }
-                    EngineAction::CreateNextFloor => {
-                        self.root_console.borrow_mut().clear();
-                        game.next_floor();
+                }
+                EngineAction::DeleteSave(slot) => {
+                    self.menu_error = None;
+                    if let Err(e) = self.saves.delete(slot) {
+                        self.menu_error = Some(format!("The saved game couldn't be deleted: {}", e));
                     }
+                    self.save_slots = self.saves.list_slots();
+                }
+                EngineAction::ToggleFullscreen => {
+                    let fullscreen = self.terminal.borrow().is_fullscreen();
+                    self.terminal.borrow_mut().set_fullscreen(!fullscreen)
+                }
+                EngineAction::MousePos(x, y) => {
+                    self.mouse_pos = (x as i32, y as i32);
+                }
+                EngineAction::ChangeFloor(destination) => {
+                    self.terminal.borrow_mut().clear();
+                    game.change_floor(destination);
+                    self.turns_since_save = 0;
+                    self.autosave(&game);
+                }
+                EngineAction::FallIntoChasm => {
+                    self.terminal.borrow_mut().clear();
+                    let destination = game.floor_number.saturating_add(1);
+                    game.change_floor(destination);
+                    self.turns_since_save = 0;
+                    self.autosave(&game);
                 }
             }
+        }
+
+        if self.state == GameState::EnemyTurn && next_state == GameState::PlayersTurn {
+            self.turns_since_save += 1;
 
-            self.state = result.next_state;
+            let interval = self.settings.autosave_interval();
+            if interval > 0 && self.turns_since_save >= interval {
+                self.turns_since_save = 0;
+                self.autosave(&game);
+            }
         }
+
+        game.events.dispatch(&game.ecs.borrow());
+
+        self.state = next_state;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use tcod::input::{Event, EventFlags, Key, KeyCode, KEY_PRESS};
+
+    use super::*;
+    use game::simulation::Simulation;
+    use savegame::TempDirectory;
+    use terminal::HeadlessTerminal;
+
+    fn key_press(code: KeyCode, printable: char) -> (EventFlags, Event) {
+        (KEY_PRESS, Event::Key(Key { code, printable, pressed: true, ..Default::default() }))
+    }
+
+    fn char_press(printable: char) -> (EventFlags, Event) {
+        key_press(KeyCode::Char, printable)
+    }
+
+    /// Run the engine until all events are handled, and return the terminal it drew on.
+    ///
+    /// The engine draws the screen before it handles an event, so the terminal shows what the
+    /// screen looked like when the last event arrived.
+    fn run_headless(events: Vec<(EventFlags, Event)>) -> Rc<RefCell<HeadlessTerminal>> {
+        let settings = Settings::new();
+        let directory = TempDirectory::new();
+        let terminal = Rc::new(RefCell::new(HeadlessTerminal::new(settings.screen_width(), settings.screen_height())));
+        events.into_iter().for_each(|event| terminal.borrow_mut().push_event(event));
+
+        Engine::run_on(&settings, Box::new(terminal.clone()), directory.saves());
+        terminal
+    }
+
+    /// Start a new game with a fixed seed from the main menu
+    fn start_game() -> Vec<(EventFlags, Event)> {
+        vec![char_press('b'), char_press('4'), char_press('2'), key_press(KeyCode::Enter, '\r')]
+    }
+
+    #[test]
+    fn main_menu_shows_all_options() {
+        let terminal = run_headless(vec![key_press(KeyCode::Escape, '\u{1b}')]);
+        let text = terminal.borrow().text();
+
+        assert!(text.contains("/r/roguelikedev Tutorial Series 2018"));
+        for option in &["(a) New game", "(b) New game with seed", "(c) Continue last game", "(d) Load game", "(e) Quit"] {
+            assert!(text.contains(option), "The main menu doesn't offer {}", option);
+        }
+    }
+
+    #[test]
+    fn inventory_menu_is_shown() {
+        let mut events = start_game();
+        events.push(char_press('i'));
+        events.push(key_press(KeyCode::Escape, '\u{1b}'));
+
+        let terminal = run_headless(events);
+        let terminal = terminal.borrow();
+
+        assert!(terminal.text().contains("Press the key next to an item to use it"), "{}", terminal.text());
+        assert!(terminal.text().contains("(a) Inventory is empty"));
+        assert!(!terminal.text().contains("New game with seed"));
+    }
+
+    #[test]
+    fn character_screen_is_shown() {
+        let mut events = start_game();
+        events.push(char_press('c'));
+        events.push(key_press(KeyCode::Escape, '\u{1b}'));
+
+        let terminal = run_headless(events);
+        let terminal = terminal.borrow();
+
+        let rows: Vec<String> = (0..terminal.height()).map(|y| terminal.row(y)).collect();
+        assert!(rows.iter().any(|row| row.contains("Character Information")), "{}", terminal.text());
+
+        let level = rows.iter().find(|row| row.contains("Level:")).expect("The level isn't shown");
+        assert!(level.trim_end().ends_with(" 1"));
+    }
+
+    #[test]
+    fn game_is_saved_after_the_autosave_interval() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+        let saves = sim.save_directory().saves();
+        let interval = sim.settings().autosave_interval();
+
+        sim.wait(interval - 1);
+        assert!(match saves.load_metadata(0) { Err(LoadError::NoSaveFile) => true, _ => false });
+
+        sim.wait(1);
+        assert!(saves.load_metadata(0).is_ok());
     }
 }
@@ -0,0 +1,224 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use tcod::colors;
+
+use ecs::Ecs;
+use ecs::id::EntityId;
+use ecs::component::Name;
+use message::{Message, MessageLog};
+
+/// Everything noteworthy which happens during the game.
+///
+/// Events are only informational. The game rules themselves are still implemented by the
+/// `EntityAction`s, so listeners can react to events without changing the outcome of an action.
+pub enum GameEvent {
+    /// A plain text message for the player
+    Message(Message),
+    /// An entity took damage: (target, damage)
+    DamageDealt(EntityId, u32),
+    /// An entity died
+    EntityDied(EntityId),
+    /// An entity picked up an item: (entity, item)
+    ItemPickedUp(EntityId, EntityId),
+    /// An entity gained experience: (entity, xp)
+    XpGained(EntityId, u32),
+    /// An entity reached a new level: (entity, level)
+    LevelGained(EntityId, u8),
+    /// The player went to another floor of the dungeon: (previous floor, entered floor)
+    FloorEntered(u8, u8),
+}
+
+/// Something which wants to be notified about `GameEvent`s
+pub trait EventListener {
+    fn on_event(&mut self, event: &GameEvent, ecs: &Ecs);
+}
+
+/// Queue of all `GameEvent`s which happened since the last dispatch, and the listeners which
+/// will be notified about them.
+pub struct EventBus {
+    queue: RefCell<VecDeque<GameEvent>>,
+    listeners: RefCell<Vec<Box<EventListener>>>,
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        EventBus {
+            queue: RefCell::new(VecDeque::new()),
+            listeners: RefCell::new(vec![]),
+        }
+    }
+
+    /// Add a listener which will be notified about every dispatched event
+    ///
+    /// Listeners can't be added from within `EventListener::on_event`.
+    pub fn subscribe(&self, listener: Box<EventListener>) {
+        self.listeners.borrow_mut().push(listener);
+    }
+
+    /// Add an event to the queue. It won't reach the listeners before the next `dispatch`.
+    pub fn publish(&self, event: GameEvent) {
+        self.queue.borrow_mut().push_back(event);
+    }
+
+    /// Notify all listeners about all queued events, in the order in which they were published.
+    ///
+    /// Events which are published by listeners while dispatching are handled in the same run.
+    pub fn dispatch(&self, ecs: &Ecs) {
+        loop {
+            let event = match self.queue.borrow_mut().pop_front() {
+                Some(event) => event,
+                None => break
+            };
+
+            for listener in self.listeners.borrow_mut().iter_mut() {
+                listener.on_event(&event, ecs);
+            }
+        }
+    }
+}
+
+impl EventListener for Rc<MessageLog> {
+    fn on_event(&mut self, event: &GameEvent, ecs: &Ecs) {
+        let message = match *event {
+            GameEvent::Message(ref message) => message.clone(),
+            GameEvent::DamageDealt(target_id, damage) => {
+                let target_name = get_entity_name(ecs, target_id).to_uppercase();
+                Message::new(if damage > 0 {
+                    format!("The {} takes {}  damage.", target_name, damage)
+                } else {
+                    format!("The {} takes no damage.", target_name)
+                }, colors::WHITE)
+            }
+            GameEvent::EntityDied(entity_id) => {
+                if entity_id == ecs.player_entity_id {
+                    Message::new("YOU DIED".to_string(), colors::RED)
+                } else {
+                    let entity_name = get_entity_name(ecs, entity_id).to_uppercase();
+                    Message::new(format!("The {} died.", entity_name), colors::ORANGE)
+                }
+            }
+            GameEvent::ItemPickedUp(entity_id, item_id) => {
+                let entity_name = get_entity_name(ecs, entity_id).to_uppercase();
+                let item_name = get_entity_name(ecs, item_id).to_uppercase();
+                Message::new(format!("{} picked up the {}", entity_name, item_name), colors::BLUE)
+            }
+            GameEvent::XpGained(entity_id, xp) => {
+                let entity_name = get_entity_name(ecs, entity_id).to_uppercase();
+                Message::new(format!("{} gains {} XP", entity_name, xp), colors::WHITE)
+            }
+            GameEvent::LevelGained(entity_id, level) => {
+                let entity_name = get_entity_name(ecs, entity_id).to_uppercase();
+                Message::new(format!("{} feels stronger: Reached level {}.", entity_name, level), colors::YELLOW)
+            }
+            GameEvent::FloorEntered(previous, floor) if floor < previous => {
+                Message::new("You climb back up...".to_string(), colors::GREEN)
+            }
+            GameEvent::FloorEntered(_, _) => {
+                Message::new("You go down one level deeper...".to_string(), colors::GREEN)
+            }
+        };
+
+        self.add(message);
+    }
+}
+
+fn get_entity_name(ecs: &Ecs, id: EntityId) -> String {
+    match ecs.get_component::<Name>(id) {
+        Some(n) => n.name.clone(),
+        None => format!("nameless entity (#{})", id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Weak;
+
+    /// Writes down every event it is notified about
+    struct Recorder {
+        seen: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl EventListener for Recorder {
+        fn on_event(&mut self, event: &GameEvent, _ecs: &Ecs) {
+            let description = match *event {
+                GameEvent::Message(ref message) => format!("message {}", message.text),
+                GameEvent::DamageDealt(target_id, damage) => format!("damage {} {}", target_id, damage),
+                GameEvent::EntityDied(entity_id) => format!("died {}", entity_id),
+                GameEvent::XpGained(entity_id, xp) => format!("xp {} {}", entity_id, xp),
+                _ => "other".to_string(),
+            };
+            self.seen.borrow_mut().push(description);
+        }
+    }
+
+    /// Publishes an experience reward for every death
+    struct Rewarder {
+        events: Weak<EventBus>,
+        player_id: EntityId,
+    }
+
+    impl EventListener for Rewarder {
+        fn on_event(&mut self, event: &GameEvent, _ecs: &Ecs) {
+            if let GameEvent::EntityDied(_) = *event {
+                if let Some(events) = self.events.upgrade() {
+                    events.publish(GameEvent::XpGained(self.player_id, 35));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_listener_gets_every_event_in_order() {
+        let mut ecs = Ecs::initialize();
+        let orc = ecs.create_entity();
+        let log = Rc::new(MessageLog::new());
+        let seen = Rc::new(RefCell::new(vec![]));
+
+        let events = EventBus::new();
+        events.subscribe(Box::new(Rc::clone(&log)));
+        events.subscribe(Box::new(Recorder { seen: Rc::clone(&seen) }));
+
+        events.publish(GameEvent::Message(Message::new("Hello".to_string(), colors::WHITE)));
+        events.publish(GameEvent::DamageDealt(orc, 4));
+        events.publish(GameEvent::EntityDied(orc));
+        assert!(seen.borrow().is_empty());
+
+        events.dispatch(&ecs);
+
+        assert_eq!(*seen.borrow(), vec![
+            "message Hello".to_string(),
+            format!("damage {} 4", orc),
+            format!("died {}", orc),
+        ]);
+        assert_eq!(log.messages().len(), 3);
+
+        // Dispatched events are gone from the queue
+        events.dispatch(&ecs);
+        assert_eq!(seen.borrow().len(), 3);
+    }
+
+    #[test]
+    fn events_published_while_dispatching_are_delivered_in_the_same_run() {
+        let mut ecs = Ecs::initialize();
+        let player = ecs.create_entity();
+        let orc = ecs.create_entity();
+        let seen = Rc::new(RefCell::new(vec![]));
+
+        let events = Rc::new(EventBus::new());
+        events.subscribe(Box::new(Rewarder { events: Rc::downgrade(&events), player_id: player }));
+        events.subscribe(Box::new(Recorder { seen: Rc::clone(&seen) }));
+
+        events.publish(GameEvent::EntityDied(orc));
+        events.publish(GameEvent::DamageDealt(player, 2));
+        events.dispatch(&ecs);
+
+        assert_eq!(*seen.borrow(), vec![
+            format!("died {}", orc),
+            format!("damage {} 2", player),
+            format!("xp {} 35", player),
+        ]);
+    }
+}
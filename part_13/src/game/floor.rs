@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+
+use json::JsonValue;
+
+use ecs::DetachedEntities;
+use map_objects::map::GameMap;
+use savegame::{Serialize, Deserialize, LoadError, ReadJson};
+
+/// A floor of the dungeon the player left, with everything which was on it
+pub struct Floor {
+    pub map: GameMap,
+    pub entities: DetachedEntities,
+}
+
+impl Serialize for Floor {
+    fn serialize(&self) -> JsonValue {
+        object!(
+            "map" => self.map.serialize(),
+            "entities" => self.entities.serialize()
+        )
+    }
+}
+
+impl Deserialize for Floor {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(Floor {
+            map: GameMap::deserialize(&json["map"]).map_err(|e| e.context("map"))?,
+            entities: DetachedEntities::deserialize(&json["entities"]).map_err(|e| e.context("entities"))?,
+        })
+    }
+}
+
+/// All visited floors except the current one, keyed by their floor number
+pub struct FloorRegistry {
+    floors: BTreeMap<u8, Floor>,
+}
+
+impl FloorRegistry {
+    pub fn new() -> FloorRegistry {
+        FloorRegistry {
+            floors: BTreeMap::new(),
+        }
+    }
+
+    /// Store a floor the player left
+    pub fn store(&mut self, floor_number: u8, floor: Floor) {
+        self.floors.insert(floor_number, floor);
+    }
+
+    /// Take a floor out of the registry to enter it again. Returns `None` if it wasn't visited yet.
+    pub fn take(&mut self, floor_number: u8) -> Option<Floor> {
+        self.floors.remove(&floor_number)
+    }
+}
+
+impl Serialize for FloorRegistry {
+    fn serialize(&self) -> JsonValue {
+        let mut floors = JsonValue::new_array();
+        self.floors.iter().for_each(|(floor_number, floor)| {
+            let mut json = floor.serialize();
+            json["floor_number"] = (*floor_number).into();
+            floors.push(json);
+        });
+        floors
+    }
+}
+
+impl Deserialize for FloorRegistry {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        let mut registry = FloorRegistry::new();
+
+        for floor_json in json.members() {
+            let floor_number = floor_json["floor_number"].read_u8()?;
+            let floor = Floor::deserialize(floor_json)
+                .map_err(|e| e.context(&format!("floor {}", floor_number)))?;
+
+            registry.store(floor_number, floor);
+        }
+
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ecs::component::StairsDirection;
+    use game::simulation::{Simulation, SEED};
+    use settings::Settings;
+    use game::input::InputAction;
+    use savegame::{Serialize, Deserialize};
+    use super::FloorRegistry;
+
+    #[test]
+    fn stairs_lead_to_next_floor() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        sim.send(InputAction::UseStairs(StairsDirection::Down));
+        assert_eq!(sim.floor_number(), 1);
+        assert!(sim.has_message("No stairs to use here"));
+
+        let stairs = sim.stairs_position(StairsDirection::Down);
+        sim.descend();
+
+        assert_eq!(sim.floor_number(), 2);
+        assert!(sim.has_message("You go down one level deeper..."));
+        assert_ne!(sim.stairs_position(StairsDirection::Down), stairs);
+        assert_eq!(sim.player_position(), sim.stairs_position(StairsDirection::Up));
+    }
+
+    #[test]
+    fn floors_stay_as_they_were_left() {
+        let settings = Settings::new();
+        let mut sim = Simulation::new(&settings, SEED);
+
+        let monsters = sim.monsters();
+        let positions : Vec<Option<(i32, i32)>> = monsters.iter().map(|id| sim.position_of(*id)).collect();
+
+        let stairs = sim.stairs_position(StairsDirection::Down);
+        sim.descend();
+        assert_eq!(sim.floor_number(), 2);
+        assert!(monsters.iter().all(|id| sim.position_of(*id).is_none()));
+
+        let up_stairs = sim.stairs_position(StairsDirection::Up);
+        sim.teleport_player(up_stairs);
+        sim.send(InputAction::UseStairs(StairsDirection::Up));
+
+        assert_eq!(sim.floor_number(), 1);
+        assert!(sim.has_message("You climb back up..."));
+        assert_eq!(sim.player_position(), stairs);
+        assert_eq!(sim.monsters(), monsters);
+        assert_eq!(monsters.iter().map(|id| sim.position_of(*id)).collect::<Vec<_>>(), positions);
+    }
+
+    #[test]
+    fn broken_floor_reports_where_it_is_broken() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+        sim.descend();
+
+        let mut json = sim.game().floors.serialize();
+        json[0]["entities"][0]["id"] = "not an id".into();
+
+        let error = FloorRegistry::deserialize(&json).err().expect("The broken floor was loaded");
+        assert!(error.to_string().ends_with("in entities in floor 1"), "{}", error);
+    }
+}
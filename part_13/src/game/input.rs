@@ -5,6 +5,7 @@ use tcod::input::Mouse;
 use tcod::input::Event;
 use tcod::input::EventFlags;
 use game::state::GameState;
+use ecs::component::StairsDirection;
 
 /// Action are triggered by the input (mouse & keys)
 pub enum InputAction {
@@ -13,13 +14,16 @@ pub enum InputAction {
     SelectEntity(isize, isize),
     SelectOption(char),
     PickUp,
-    UseStairs,
+    CloseDoor,
+    UseStairs(StairsDirection),
     ShowInventory,
     ShowInventoryDrop,
     ShowEquip,
     ShowCharacterScreen,
     StartNewGame,
     LoadGame,
+    Confirm,
+    DeleteCharacter,
     Fullscreen,
     Exit,
 }
@@ -33,7 +37,9 @@ pub fn handle_input(state: &GameState, event: Option<(EventFlags, Event)>) -> Op
                     GameState::PlayersTurn => handle_keys_player_turn(key),
                     GameState::ShowInventoryUse | GameState::ShowInventoryDrop | GameState::ShowInventoryEquip
                     | GameState::MainMenu | GameState::ShowQuitGameMenu
+                    | GameState::ShowSaveSlots | GameState::ShowSaveSlotOptions(_)
                     | GameState::ShowLeveUpMenu => handle_keys_selection_menu(key),
+                    GameState::EnterSeed(_) => handle_keys_text_input(key),
                     _ => handle_keys_default(key),
                 }
             }
@@ -75,8 +81,10 @@ fn handle_keys_player_turn(key: Key) -> Option<InputAction> {
         Key { printable: 'd', .. } => Some(InputAction::ShowInventoryDrop),
         Key { printable: 'c', .. } => Some(InputAction::ShowCharacterScreen),
         Key { printable: 'g', .. } => Some(InputAction::PickUp),
+        Key { printable: 'x', .. } => Some(InputAction::CloseDoor),
         Key { printable: 'e', .. } => Some(InputAction::ShowEquip),
-        Key { printable: '>', .. } => Some(InputAction::UseStairs),
+        Key { printable: '>', .. } => Some(InputAction::UseStairs(StairsDirection::Down)),
+        Key { printable: '<', .. } => Some(InputAction::UseStairs(StairsDirection::Up)),
         Key { code: KeyCode::Escape, .. } => Some(InputAction::Exit),
         Key { code: KeyCode::Enter, alt: true, .. } => Some(InputAction::Fullscreen),
         _ => None
@@ -90,6 +98,15 @@ fn handle_keys_selection_menu(key: Key) -> Option<InputAction> {
     }
 }
 
+fn handle_keys_text_input(key: Key) -> Option<InputAction> {
+    match key {
+        Key { code: KeyCode::Escape, .. } => Some(InputAction::Exit),
+        Key { code: KeyCode::Enter, .. } | Key { code: KeyCode::NumPadEnter, .. } => Some(InputAction::Confirm),
+        Key { code: KeyCode::Backspace, .. } => Some(InputAction::DeleteCharacter),
+        Key { printable, .. } => Some(InputAction::SelectOption(printable)),
+    }
+}
+
 fn handle_keys_default(key: Key) -> Option<InputAction> {
     match key {
         Key { code: KeyCode::Escape, .. } => Some(InputAction::Exit),
@@ -1,4 +1,5 @@
 use std::rc::Rc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use std::ops::DerefMut;
 use std::ops::Deref;
 
@@ -7,55 +8,99 @@ use tcod::Map;
 use json::JsonValue;
 
 use ecs::Ecs;
+use ecs::id::EntityId;
 
 use map_objects::map::GameMap;
 use message::MessageLog;
+use events::{EventBus, GameEvent};
 use settings::Settings;
 use render::MessagePanel;
 use map_objects::fov;
-use savegame::{Serialize, Deserialize};
+use map_objects::vault::Vault;
+use savegame::{Serialize, Deserialize, LoadError, ReadJson, SaveMetadata};
 use std::cell::RefCell;
 use ecs::component::Position;
 use ecs::component::MonsterAi;
+use ecs::component::Level;
+use ecs::component::Stairs;
+use game::floor::{Floor, FloorRegistry};
+use game::system::Scheduler;
+use random_utils::GameRng;
 
 pub mod state;
+pub mod floor;
 pub mod input;
+pub mod system;
+#[cfg(test)]
+pub mod simulation;
 
 pub struct Game<'game> {
     pub ecs: RefCell<Ecs>,
     pub map: RefCell<GameMap>,
     pub log: Rc<MessageLog>,
+    pub events: EventBus,
+    pub scheduler: RefCell<Scheduler>,
+    pub rng: RefCell<GameRng>,
 
     pub settings: &'game Settings,
+    /// The vaults which can be placed on new floors, read once when the game starts
+    vaults: Vec<Vault>,
 
     pub fov_map: RefCell<Map>,
     pub log_panel: MessagePanel,
-    pub floor_number: u8
+    pub floor_number: u8,
+    /// All visited floors except the current one
+    pub floors: FloorRegistry,
+
+    /// The save slot the game is stored in
+    pub slot: usize,
+    /// The time spent playing in earlier sessions, in seconds
+    previous_play_time: u64,
+    /// The time the current session started
+    session_start: Instant,
 }
 
 impl<'game> Game<'game> {
-    pub fn new(settings: &'game Settings) -> Game {
+    pub fn new(settings: &'game Settings, vaults: Vec<Vault>) -> Game {
 
         let ecs = Ecs::initialize();
         let map = GameMap::new(1, 1);
         let log = Rc::new(MessageLog::new());
         let fov_map = Map::new(1,1);
         let log_panel = MessagePanel::new((0,0),(0,0),Rc::clone(&log));
+        let events = Self::create_event_bus(&log);
 
         Game {
             ecs: RefCell::new(ecs),
             map: RefCell::new(map),
             log,
+            events,
+            scheduler: RefCell::new(Scheduler::with_default_systems()),
+            rng: RefCell::new(GameRng::from_random_seed()),
             settings,
+            vaults,
             fov_map: RefCell::new(fov_map),
             log_panel,
-            floor_number: 1
+            floor_number: 1,
+            floors: FloorRegistry::new(),
+            slot: 0,
+            previous_play_time: 0,
+            session_start: Instant::now(),
         }
     }
 
-    pub fn start_new(&mut self) {
+    /// Start a new game. Without a seed, a random one is chosen.
+    pub fn start_new(&mut self, seed: Option<u64>) {
 
         let mut map_generated = false;
+        let mut rng = match seed {
+            Some(seed) => GameRng::from_seed(seed),
+            None => GameRng::from_random_seed()
+        };
+        self.floor_number = 1;
+        self.floors = FloorRegistry::new();
+        self.previous_play_time = 0;
+        self.session_start = Instant::now();
 
         let mut ecs = Ecs::initialize();
         let mut map = GameMap::new(self.settings.map_width(), self.settings.map_height());
@@ -63,7 +108,7 @@ impl<'game> Game<'game> {
         while !map_generated {
             ecs = Ecs::initialize();
             map = GameMap::new(self.settings.map_width(), self.settings.map_height());
-            map_generated = map.make_map(&mut ecs, self.settings, self.floor_number);
+            map_generated = map.make_map(&mut ecs, self.settings, &self.vaults, self.floor_number, &mut rng);
         }
 
         let log = MessageLog::new();
@@ -71,7 +116,9 @@ impl<'game> Game<'game> {
 
         self.ecs = RefCell::new(ecs);
         self.map = RefCell::new(map);
+        self.rng = RefCell::new(rng);
         self.log = Rc::new(log);
+        self.events = Self::create_event_bus(&self.log);
         self.fov_map = RefCell::new(fov_map);
         self.log_panel = MessagePanel::new(self.settings.message_pos(),
                                            self.settings.message_dimensions(),
@@ -80,46 +127,110 @@ impl<'game> Game<'game> {
         self.init_entities(self.ecs.borrow_mut().deref_mut());
     }
 
-    pub fn load(&mut self, json: JsonValue) {
+    /// Restore a game from a save file. Nothing is changed if the save file is invalid.
+    pub fn load(&mut self, json: JsonValue) -> Result<(), LoadError> {
 
-        let ecs = Ecs::deserialize(&json["ecs"]);
-        let map = GameMap::deserialize(&json["map"]);
-        let log = MessageLog::deserialize(&json["log"]);
+        let ecs = Ecs::deserialize(&json["ecs"]).map_err(|e| e.context("ecs"))?;
+        let map = GameMap::deserialize(&json["map"]).map_err(|e| e.context("map"))?;
+        let log = MessageLog::deserialize(&json["log"]).map_err(|e| e.context("log"))?;
+        let rng = GameRng::deserialize(&json["rng"]).map_err(|e| e.context("rng"))?;
+        let floor_number = json["floor_number"].read_u8().map_err(|e| e.context("floor_number"))?;
+        let floors = FloorRegistry::deserialize(&json["floors"]).map_err(|e| e.context("floors"))?;
+        let metadata = SaveMetadata::deserialize(&json["metadata"]).map_err(|e| e.context("metadata"))?;
 
         let fov_map = fov::initialize_fov(&map);
 
         self.ecs = RefCell::new(ecs);
         self.map = RefCell::new(map);
-        self.floor_number = json["floor_number"].as_u8().unwrap();
+        self.floor_number = floor_number;
+        self.floors = floors;
+        self.previous_play_time = metadata.play_time;
+        self.session_start = Instant::now();
+        self.rng = RefCell::new(rng);
         self.log = Rc::new(log);
+        self.events = Self::create_event_bus(&self.log);
         self.fov_map = RefCell::new(fov_map);
         self.log_panel = MessagePanel::new(self.settings.message_pos(),
                                            self.settings.message_dimensions(),
                                            self.log.clone());
 
         self.init_entities(self.ecs.borrow_mut().deref_mut());
+
+        Ok(())
     }
 
-    pub fn next_floor(&mut self) {
+    /// Leave the current floor and enter another one.
+    ///
+    /// The current floor is stored with everything on it. A floor which was visited before is
+    /// restored exactly as the player left it, and the player arrives on the stairs which lead
+    /// back. Otherwise a new floor is generated.
+    pub fn change_floor(&mut self, destination: u8) {
 
-        self.floor_number+=1;
+        let previous = self.floor_number;
+        self.floor_number = destination;
         let mut ecs = self.ecs.borrow_mut();
+        let player_id = ecs.player_entity_id;
 
+        // Everything with a position lies on the floor, the items in the inventory stay with the player
+        let ids: Vec<EntityId> = ecs.get_all_ids::<Position>().into_iter().filter(|id| *id != player_id).collect();
+        let entities = ecs.detach_entities(&ids);
+        let map = self.map.replace(GameMap::new(self.settings.map_width(), self.settings.map_height()));
+        self.floors.store(previous, Floor { map, entities });
 
-        let mut map_generated = false;
+        if let Some(floor) = self.floors.take(destination) {
+            self.map.replace(floor.map);
+            ecs.attach_entities(floor.entities);
 
-        while !map_generated {
-            ecs.get_all_ids::<Position>().iter().for_each(|id| {
-                if *id != ecs.player_entity_id {
-                    ecs.destroy_entity(id);
-                }
-            });
+            let arrival = ecs.query::<(&Stairs, &Position)>()
+                .find(|(_, (stairs, _))| stairs.destination == previous)
+                .map(|(_, (_, p))| p.position);
 
-            map_generated = self.map.borrow_mut().make_map(ecs.deref_mut(), self.settings, self.floor_number);
+            if let Some(position) = arrival {
+                ecs.get_component_mut::<Position>(player_id).unwrap().move_absolute(position);
+            }
+        } else {
+            let mut map_generated = false;
+
+            while !map_generated {
+                ecs.get_all_ids::<Position>().iter().for_each(|id| {
+                    if *id != ecs.player_entity_id {
+                        ecs.destroy_entity(id);
+                    }
+                });
+
+                map_generated = self.map.borrow_mut().make_map(ecs.deref_mut(), self.settings, &self.vaults,
+                                                               self.floor_number, self.rng.borrow_mut().deref_mut());
+            }
         }
         self.fov_map = RefCell::new(fov::initialize_fov(&self.map.borrow()));
 
         self.init_entities(ecs.deref_mut());
+
+        self.events.publish(GameEvent::FloorEntered(previous, self.floor_number));
+    }
+
+    /// The time spent playing this game, including earlier sessions, in seconds
+    pub fn play_time(&self) -> u64 {
+        self.previous_play_time + self.session_start.elapsed().as_secs()
+    }
+
+    /// The information shown for the game in the list of save slots
+    pub fn metadata(&self) -> SaveMetadata {
+        let ecs = self.ecs.borrow();
+
+        SaveMetadata {
+            level: ecs.get_component::<Level>(ecs.player_entity_id).map(|l| l.level).unwrap_or(1),
+            floor_number: self.floor_number,
+            play_time: self.play_time(),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        }
+    }
+
+    /// Create a new `EventBus`, with the message log as first listener
+    fn create_event_bus(log: &Rc<MessageLog>) -> EventBus {
+        let events = EventBus::new();
+        events.subscribe(Box::new(Rc::clone(log)));
+        events
     }
 
     /// run initialization on the entities
@@ -139,7 +250,53 @@ impl<'game> Serialize for Game<'game> {
             "ecs" => self.ecs.borrow().serialize(),
             "log" => self.log.serialize(),
             "map" => self.map.borrow().serialize(),
-            "floor_number" => self.floor_number
+            "floor_number" => self.floor_number,
+            "floors" => self.floors.serialize(),
+            "rng" => self.rng.borrow().serialize()
         )
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use game::simulation::{Simulation, SEED};
+    use settings::Settings;
+    use game::input::InputAction;
+    use ecs::component::StairsDirection;
+
+    #[test]
+    fn same_seed_creates_same_game() {
+        let settings = Settings::new();
+        let first = Simulation::new(&settings, SEED);
+        let second = Simulation::new(&settings, SEED);
+
+        assert_eq!(first.player_position(), second.player_position());
+        assert_eq!(first.stairs_position(StairsDirection::Down), second.stairs_position(StairsDirection::Down));
+        assert_eq!(first.monsters(), second.monsters());
+
+        let positions = |sim: &Simulation| -> Vec<Option<(i32, i32)>> {
+            sim.monsters().iter().map(|id| sim.position_of(*id)).collect()
+        };
+        assert_eq!(positions(&first), positions(&second));
+    }
+
+    #[test]
+    fn same_inputs_create_same_outcome() {
+        let script = || vec![
+            InputAction::MovePlayer(1, 0),
+            InputAction::MovePlayer(0, 1),
+            InputAction::MovePlayer(-1, 0),
+            InputAction::MovePlayer(0, -1),
+        ];
+
+        let settings = Settings::new();
+        let mut first = Simulation::new(&settings, SEED);
+        let mut second = Simulation::new(&settings, SEED);
+        first.send_all(script());
+        second.send_all(script());
+
+        assert_eq!(first.player_position(), second.player_position());
+        assert_eq!(first.player_hp(), second.player_hp());
+        assert_eq!(first.messages(), second.messages());
+    }
+}
@@ -0,0 +1,323 @@
+use std::cell::Ref;
+
+use ecs::id::EntityId;
+use ecs::component::{Actor, AiMemory, Corpse, Inventory, MonsterAi, Name, Position, Stairs, StairsDirection};
+use ecs::creature::CreatureTemplate;
+use ecs::item::ItemTemplate;
+use ecs::status::{StatusEffect, StatusKind, StatusEffects};
+use ecs::action::EntityAction;
+use engine::Engine;
+use game::Game;
+use game::input::InputAction;
+use game::state::GameState;
+use map_objects::vault::Vault;
+use map_objects::fov;
+use map_objects::tile::TileKind;
+use savegame::TempDirectory;
+use settings::Settings;
+use camera::Camera;
+use terminal::HeadlessTerminal;
+
+/// The seed of all simulations which don't need a specific dungeon
+pub const SEED: u64 = 1234;
+
+/// Runs the `Engine` on a `HeadlessTerminal`, driven by a script of `InputAction`s.
+///
+/// Every action goes through `Engine::update` exactly like the input of a real player, and the
+/// enemy turns which follow it are run right away. The game is saved into a temporary directory,
+/// which is removed again afterwards. Tests can then check the state of the `Ecs`, the floor and
+/// the message log.
+pub struct Simulation<'s> {
+    pub engine: Engine<'s>,
+    /// Declared after the engine, so it is only removed once the engine is gone
+    save_directory: TempDirectory,
+}
+
+impl<'s> Simulation<'s> {
+    /// Start a new game with a fixed seed, so every run creates the same dungeon. The game is
+    /// started from the main menu, like a player would do it.
+    pub fn new(settings: &'s Settings, seed: u64) -> Simulation<'s> {
+        let save_directory = TempDirectory::new();
+        let terminal = HeadlessTerminal::new(settings.screen_width(), settings.screen_height());
+        let engine = Engine::initialize(settings, Box::new(terminal), Vault::load_all().unwrap(),
+                                        save_directory.saves());
+
+        let mut sim = Simulation { engine, save_directory };
+        sim.send(InputAction::SelectOption('b'));
+        sim.send_all(seed.to_string().chars().map(InputAction::SelectOption).collect());
+        sim.send(InputAction::Confirm);
+        sim
+    }
+
+    /// Start a new game with `SEED`, and remove all monsters, so a test can place exactly the
+    /// monsters it needs
+    pub fn without_monsters(settings: &'s Settings) -> Simulation<'s> {
+        let sim = Simulation::new(settings, SEED);
+        sim.remove_monsters();
+        sim
+    }
+
+    /// Send a single action, then run all following enemy turns
+    pub fn send(&mut self, action: InputAction) {
+        self.engine.update(Some(action));
+
+        while self.engine.state == GameState::EnemyTurn {
+            self.engine.update(None);
+        }
+    }
+
+    /// Send all actions in order
+    pub fn send_all(&mut self, actions: Vec<InputAction>) {
+        for action in actions {
+            self.send(action);
+        }
+    }
+
+    pub fn state(&self) -> GameState {
+        self.engine.state
+    }
+
+    pub fn game(&self) -> Ref<Game<'s>> {
+        self.engine.game.borrow()
+    }
+
+    pub fn settings(&self) -> &'s Settings {
+        self.engine.settings
+    }
+
+    /// The directory the game is saved in
+    pub fn save_directory(&self) -> &TempDirectory {
+        &self.save_directory
+    }
+
+    pub fn player_id(&self) -> EntityId {
+        self.game().ecs.borrow().player_entity_id
+    }
+
+    pub fn hp_of(&self, id: EntityId) -> u32 {
+        self.game().ecs.borrow().get_component::<Actor>(id).unwrap().hp
+    }
+
+    pub fn set_hp(&self, id: EntityId, hp: u32) {
+        self.game().ecs.borrow_mut().get_component_mut::<Actor>(id).unwrap().hp = hp;
+    }
+
+    pub fn player_hp(&self) -> u32 {
+        self.hp_of(self.player_id())
+    }
+
+    pub fn set_player_hp(&self, hp: u32) {
+        self.set_hp(self.player_id(), hp);
+    }
+
+    pub fn player_position(&self) -> (i32, i32) {
+        self.position_of(self.player_id()).unwrap()
+    }
+
+    /// The distance between the player and an `Entity`
+    pub fn distance_to_player(&self, id: EntityId) -> f64 {
+        let game = self.game();
+        let ecs = game.ecs.borrow();
+        let player = ecs.get_component::<Position>(ecs.player_entity_id).unwrap();
+        player.distance_to(ecs.get_component::<Position>(id).unwrap().position)
+    }
+
+    /// The position on the screen a map position is drawn at, to click on it
+    pub fn screen_position(&self, position: (i32, i32)) -> (isize, isize) {
+        let game = self.game();
+        let camera = Camera::following_player(&game.ecs.borrow(), &game.map.borrow(), game.settings);
+        let (x, y) = camera.to_screen(position).expect("The position is outside of the viewport");
+        (x as isize, y as isize)
+    }
+
+    pub fn position_of(&self, id: EntityId) -> Option<(i32, i32)> {
+        self.game().ecs.borrow().get_component::<Position>(id).map(|p| p.position)
+    }
+
+    pub fn is_dead(&self, id: EntityId) -> bool {
+        self.game().ecs.borrow().has_component::<Corpse>(id)
+    }
+
+    /// The names of all items in the inventory of the player
+    pub fn inventory_names(&self) -> Vec<String> {
+        let game = self.game();
+        let ecs = game.ecs.borrow();
+        match ecs.get_component::<Inventory>(ecs.player_entity_id) {
+            Some(inventory) => inventory.items.iter().filter_map(|id| {
+                ecs.get_component::<Name>(*id).map(|n| n.name.clone())
+            }).collect(),
+            None => vec![]
+        }
+    }
+
+    pub fn floor_number(&self) -> u8 {
+        self.game().floor_number
+    }
+
+    /// The texts of all messages in the log, oldest first
+    pub fn messages(&self) -> Vec<String> {
+        self.game().log.messages().iter().map(|m| m.text.clone()).collect()
+    }
+
+    pub fn has_message(&self, text: &str) -> bool {
+        self.messages().iter().any(|m| m == text)
+    }
+
+    /// The number of messages which start with `prefix`, e.g. to count the attacks of a monster
+    pub fn count_messages(&self, prefix: &str) -> usize {
+        self.messages().iter().filter(|m| m.starts_with(prefix)).count()
+    }
+
+    /// Move the player to a position without using a turn
+    pub fn teleport_player(&self, position: (i32, i32)) {
+        let game = self.game();
+        let mut ecs = game.ecs.borrow_mut();
+        let id = ecs.player_entity_id;
+        ecs.get_component_mut::<Position>(id).unwrap().move_absolute(position);
+    }
+
+    /// Remove all monsters from the floor
+    pub fn remove_monsters(&self) {
+        let game = self.game();
+        let mut ecs = game.ecs.borrow_mut();
+        ecs.get_all_ids::<MonsterAi>().iter().for_each(|id| ecs.destroy_entity(id));
+    }
+
+    /// All living monsters, ordered by id
+    pub fn monsters(&self) -> Vec<EntityId> {
+        self.game().ecs.borrow().get_all_ids::<MonsterAi>()
+    }
+
+    pub fn stairs_position(&self, direction: StairsDirection) -> (i32, i32) {
+        self.game().ecs.borrow().query::<(&Stairs, &Position)>()
+            .find(|(_, (stairs, _))| stairs.direction == direction)
+            .map(|(_, (_, p))| p.position).unwrap()
+    }
+
+    /// Go down the stairs of the current floor
+    pub fn descend(&mut self) {
+        let stairs = self.stairs_position(StairsDirection::Down);
+        self.teleport_player(stairs);
+        self.send(InputAction::UseStairs(StairsDirection::Down));
+    }
+
+    pub fn tile_kind(&self, position: (i32, i32)) -> TileKind {
+        self.game().map.borrow().tile_kind(position.0, position.1)
+    }
+
+    /// Change a tile of the map, e.g. to put a door next to the player, and update what the player
+    /// and the monsters can see through it
+    pub fn set_tile_kind(&self, position: (i32, i32), kind: TileKind) {
+        let game = self.game();
+        let mut map = game.map.borrow_mut();
+        map.set_tile_kind(position, kind);
+        fov::update_tile(&mut game.fov_map.borrow_mut(), &map, position);
+
+        let mut ecs = game.ecs.borrow_mut();
+        ecs.get_all_ids::<MonsterAi>().iter().for_each(|id| {
+            if let Some(ai) = ecs.get_component_mut::<MonsterAi>(*id) {
+                ai.update_fov_tile(&map, position);
+            }
+        });
+    }
+
+    /// Find a floor position next to `position` which no `Entity` blocks.
+    /// Returns the position and the direction from it towards `position`.
+    pub fn free_neighbour(&self, position: (i32, i32)) -> Option<((i32, i32), (i32, i32))> {
+        let game = self.game();
+        let map = game.map.borrow();
+        let ecs = game.ecs.borrow();
+
+        for dx in -1..2 {
+            for dy in -1..2 {
+                let neighbour = (position.0 + dx, position.1 + dy);
+                if (dx, dy) != (0, 0)
+                    && map.tile_kind(neighbour.0, neighbour.1) == TileKind::Floor
+                    && Position::is_blocked_by(&ecs, neighbour).is_empty() {
+                    return Some((neighbour, (-dx, -dy)));
+                }
+            }
+        }
+        None
+    }
+
+    /// Find a free tile next to the player, and the direction from the player towards it
+    pub fn free_tile_next_to_player(&self) -> ((i32, i32), (i32, i32)) {
+        let (position, direction) = self.free_neighbour(self.player_position()).unwrap();
+        (position, (-direction.0, -direction.1))
+    }
+
+    /// Spawn a creature on a free tile next to the player. Returns its id, and the direction from
+    /// the player towards it.
+    pub fn spawn_next_to_player(&self, template: CreatureTemplate) -> (EntityId, (i32, i32)) {
+        let (position, direction) = self.free_tile_next_to_player();
+        (self.spawn_creature(template, position), direction)
+    }
+
+    /// Turn everything around the player into floor, and return the position `distance` tiles
+    /// to the side of the player, which can be seen from there
+    pub fn clear_area_around_player(&self, distance: i32) -> (i32, i32) {
+        let (x, y) = self.player_position();
+        let (width, height) = self.game().map.borrow().dimensions;
+
+        for dx in -distance..distance + 1 {
+            for dy in -distance..distance + 1 {
+                let position = (x + dx, y + dy);
+                if position.0 > 0 && position.1 > 0 && position.0 < width - 1 && position.1 < height - 1 {
+                    self.set_tile_kind(position, TileKind::Floor);
+                }
+            }
+        }
+
+        if x + distance < width - 1 { (x + distance, y) } else { (x - distance, y) }
+    }
+
+    /// Build a wall from north to south through the area around the player, `dx` tiles to the side
+    pub fn build_wall(&self, dx: i32, distance: i32) {
+        let (x, y) = self.player_position();
+        for dy in -distance..distance + 1 {
+            self.set_tile_kind((x + dx, y + dy), TileKind::Wall);
+        }
+    }
+
+    /// Let `turns` turns pass, by walking into a wall next to the player
+    pub fn wait(&mut self, turns: u32) {
+        let (wall, direction) = self.free_tile_next_to_player();
+        self.set_tile_kind(wall, TileKind::Wall);
+
+        for _ in 0..turns {
+            self.send(InputAction::MovePlayer(direction.0, direction.1));
+        }
+    }
+
+    /// Put a status effect on an `Entity`, as if a spell or an attack caused it
+    pub fn add_status_effect(&self, id: EntityId, effect: StatusEffect) {
+        let game = self.game();
+        EntityAction::AddStatusEffect(id, effect)
+            .execute(&mut game.ecs.borrow_mut(), &game.fov_map.borrow(), &game.events, game.settings);
+    }
+
+    pub fn has_status(&self, id: EntityId, kind: StatusKind) -> bool {
+        self.game().ecs.borrow().get_component::<StatusEffects>(id).map_or(false, |s| s.has(kind))
+    }
+
+    pub fn ai_memory(&self, id: EntityId) -> Option<AiMemory> {
+        self.game().ecs.borrow().get_component::<MonsterAi>(id).map(|ai| ai.memory())
+    }
+
+    pub fn spawn_item(&self, template: ItemTemplate, position: (i32, i32)) -> EntityId {
+        template.create_on_position(&mut self.game().ecs.borrow_mut(), position).unwrap()
+    }
+
+    pub fn spawn_creature(&self, template: CreatureTemplate, position: (i32, i32)) -> EntityId {
+        let game = self.game();
+        let map = game.map.borrow();
+        let mut ecs = game.ecs.borrow_mut();
+
+        let id = template.create_on_position(&mut ecs, &map, position).unwrap();
+        if let Some(ai) = ecs.get_component_mut::<MonsterAi>(id) {
+            ai.initialize_fov(&map);
+        }
+        id
+    }
+}
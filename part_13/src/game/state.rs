@@ -1,29 +1,31 @@
-use std::rc::Rc;
-
 use tcod::colors;
-use tcod::input::{check_for_event, EventFlags};
 
 use ecs::Ecs;
 use ecs::action::EntityAction;
-use ecs::component::MonsterAi;
 use ecs::component::Corpse;
 use ecs::component::Position;
 use ecs::component::Item;
 use ecs::component::Actor;
-use message::MessageLog;
+use ecs::component::MonsterAi;
+use events::{EventBus, GameEvent};
 use tcod::Map;
 use map_objects::map::GameMap;
 use message::Message;
 use ecs::spell::Spell;
 use ecs::id::EntityId;
 use game::input::*;
-use map_objects::fov::recompute_fov;
+use map_objects::fov::{recompute_fov, update_tile};
+use map_objects::tile::TileKind;
 use settings::Settings;
 use game::Game;
-use engine::Engine;
-use std::cell::RefMut;
+use game::system::{Scheduler, TurnContext, ActionCost, spend_energy};
 use engine::EngineAction;
 use ecs::component::Stairs;
+use savegame::SAVE_SLOT_COUNT;
+use camera::Camera;
+use terminal::Terminal;
+use ecs::status::{StatusKind, has_status, random_direction};
+use random_utils::GameRng;
 
 
 pub struct GameStateResult {
@@ -44,35 +46,56 @@ pub enum GameState {
     ShowCharacterScreen,
     Targeting(Spell, EntityId),
     MainMenu,
+    /// The player enters the seed for a new game, holding the digits entered so far
+    EnterSeed(u64),
+    /// The list of all save slots
+    ShowSaveSlots,
+    /// The options to load or delete the game in a save slot
+    ShowSaveSlotOptions(usize),
 }
 
 impl GameState {
-    pub fn run(&self, engine: &Engine, game: &RefMut<Game>) -> GameStateResult {
-        let input_action = handle_input(self, check_for_event(EventFlags::all()));
-        let log = game.log.clone();
+    /// Translate the next event of the terminal into an input. The enemy turn doesn't wait for
+    /// the player, so it leaves all events in the terminal for the next state which does.
+    pub fn read_input(&self, terminal: &mut Terminal) -> Option<InputAction> {
+        match *self {
+            GameState::EnemyTurn => None,
+            _ => handle_input(self, terminal.poll_event())
+        }
+    }
+
+    /// Update the game with an already translated input
+    pub fn update(&self, input_action: Option<InputAction>, game: &Game) -> GameStateResult {
+        let events = &game.events;
 
         let mut ecs = game.ecs.borrow_mut();
         let mut fov_map = game.fov_map.borrow_mut();
-        let map = game.map.borrow();
+        let mut map = game.map.borrow_mut();
 
         match *self {
-            GameState::PlayersTurn => self.player_turn(&mut ecs, &mut fov_map, input_action, log, &map, game.settings),
-            GameState::EnemyTurn => self.enemy_turn(&mut ecs, &fov_map, log, &map, game.settings),
+            GameState::PlayersTurn => self.player_turn(&mut ecs, &mut fov_map, input_action, events, &mut map, game.settings, &mut game.rng.borrow_mut()),
+            GameState::EnemyTurn => self.enemy_turn(&mut ecs, &fov_map, events, &map, game.settings, &mut game.scheduler.borrow_mut(), &mut game.rng.borrow_mut()),
             GameState::PlayerDead => self.player_dead(input_action),
             GameState::MainMenu => self.main_menu(input_action),
+            GameState::EnterSeed(seed) => self.enter_seed(seed, input_action),
+            GameState::ShowSaveSlots => self.save_slots(input_action),
+            GameState::ShowSaveSlotOptions(slot) => self.save_slot_options(slot, input_action),
             GameState::ShowQuitGameMenu => self.quit_game_menu(input_action),
             GameState::ShowLeveUpMenu => self.level_up_menu(&mut ecs, input_action),
             GameState::ShowCharacterScreen => self.show_character_screen(input_action),
-            GameState::ShowInventoryUse | GameState::ShowInventoryDrop | GameState::ShowInventoryEquip => self.show_inventory(&mut ecs, &fov_map, game.settings, input_action, log),
-            GameState::Targeting(spell, caster_id) => self.targeting(&mut ecs, &fov_map, game.settings, input_action, log, spell, caster_id),
+            GameState::ShowInventoryUse | GameState::ShowInventoryDrop | GameState::ShowInventoryEquip => self.show_inventory(&mut ecs, &fov_map, &map, game.settings, input_action, events),
+            GameState::Targeting(spell, caster_id) => {
+                let camera = Camera::following_player(&ecs, &map, game.settings);
+                self.targeting(&mut ecs, &fov_map, &map, game.settings, &camera, input_action, events, spell, caster_id)
+            }
         }
     }
 
-    fn targeting(&self, ecs: &mut Ecs, fov_map: &Map, settings: &Settings, action: Option<InputAction>,
-                 log: Rc<MessageLog>, spell: Spell, caster_id: EntityId) -> GameStateResult {
+    fn targeting(&self, ecs: &mut Ecs, fov_map: &Map, map: &GameMap, settings: &Settings, camera: &Camera, action: Option<InputAction>,
+                 events: &EventBus, spell: Spell, caster_id: EntityId) -> GameStateResult {
         match action {
             Some(InputAction::Exit) => {
-                log.add(Message::new("Target selection was canceled".to_string(), colors::WHITE));
+                events.publish(GameEvent::Message(Message::new("Target selection was canceled".to_string(), colors::WHITE)));
                 GameStateResult {
                     next_state: GameState::PlayersTurn,
                     engine_action: None,
@@ -85,27 +108,30 @@ impl GameState {
                 }
             }
             Some(InputAction::SelectEntity(x, y)) => {
-                let targets: Vec<EntityId> = ecs.get_all::<Position>().iter().filter(|(id, p)| {
-                    ecs.has_component::<Actor>(**id) && p.position.0 == x as i32 && p.position.1 == y as i32
-                }).map(|(id, _)|{*id}).collect();
+                // The click is in screen coordinates, a click outside of the map selects nothing
+                let position = camera.to_world((x as i32, y as i32));
+                let targets: Vec<EntityId> = ecs.query::<(&Position, &Actor)>().filter(|(_, (p, _))| {
+                    Some(p.position) == position
+                }).map(|(id, _)|{id}).collect();
 
                 if let Some(target) = targets.first() {
                     let spell_result = spell.cast_on_target(ecs, *target, caster_id);
 
                     if let Some(message) = spell_result.message {
-                        log.add(message)
+                        events.publish(GameEvent::Message(message))
                     }
 
                     for action in spell_result.reactions {
-                        action.execute(ecs, fov_map, Rc::clone(&log), settings);
+                        action.execute(ecs, fov_map, events, settings);
                     }
+                    Self::spend_player_energy(ecs, map, ActionCost::UseItem);
 
                     GameStateResult {
                         next_state: GameState::EnemyTurn,
                         engine_action: None,
                     }
                 } else {
-                    log.add(Message::new("No valid target at the selected position".to_string(), colors::YELLOW));
+                    events.publish(GameEvent::Message(Message::new("No valid target at the selected position".to_string(), colors::YELLOW)));
 
                     GameStateResult {
                         next_state: *self,
@@ -122,7 +148,7 @@ impl GameState {
         }
     }
 
-    fn show_inventory(&self, ecs: &mut Ecs, fov_map: &Map, settings: &Settings, action: Option<InputAction>, log: Rc<MessageLog>) -> GameStateResult {
+    fn show_inventory(&self, ecs: &mut Ecs, fov_map: &Map, map: &GameMap, settings: &Settings, action: Option<InputAction>, events: &EventBus) -> GameStateResult {
         match action {
             Some(InputAction::Exit) => {
                 GameStateResult {
@@ -139,9 +165,10 @@ impl GameState {
                         GameState::ShowInventoryUse => EntityAction::UseItem(ecs.player_entity_id, item_number as u8),
                         GameState::ShowInventoryEquip => EntityAction::ToggleEquipment(ecs.player_entity_id, item_number as u8),
                         _ => EntityAction::Idle
-                    }.execute(ecs, fov_map, log, settings) {
+                    }.execute(ecs, fov_map, events, settings) {
                         state
                     } else {
+                        Self::spend_player_energy(ecs, map, ActionCost::UseItem);
                         GameState::EnemyTurn
                     };
 
@@ -201,7 +228,7 @@ impl GameState {
 
     fn main_menu(&self, action: Option<InputAction>) -> GameStateResult {
         match action {
-            Some(InputAction::Exit) | Some(InputAction::SelectOption('c')) => {
+            Some(InputAction::Exit) | Some(InputAction::SelectOption('e')) => {
                 GameStateResult {
                     next_state: GameState::MainMenu,
                     engine_action: Some(EngineAction::Exit),
@@ -214,11 +241,23 @@ impl GameState {
                 }
             }
             Some(InputAction::SelectOption('b')) => {
+                GameStateResult {
+                    next_state: GameState::EnterSeed(0),
+                    engine_action: None,
+                }
+            }
+            Some(InputAction::SelectOption('c')) => {
                 GameStateResult {
                     next_state: GameState::PlayersTurn,
                     engine_action: Some(EngineAction::StartGame(true)),
                 }
             }
+            Some(InputAction::SelectOption('d')) => {
+                GameStateResult {
+                    next_state: GameState::ShowSaveSlots,
+                    engine_action: Some(EngineAction::ListSaves),
+                }
+            }
             _ => {
                 GameStateResult {
                     next_state: GameState::MainMenu,
@@ -227,6 +266,103 @@ impl GameState {
             }
         }
     }
+
+    fn save_slots(&self, action: Option<InputAction>) -> GameStateResult {
+        match action {
+            Some(InputAction::Exit) => {
+                GameStateResult {
+                    next_state: GameState::MainMenu,
+                    engine_action: None,
+                }
+            }
+            Some(InputAction::SelectOption(key)) => {
+                let slot = (key as u32).checked_sub('a' as u32)
+                    .map(|slot| slot as usize)
+                    .filter(|slot| *slot < SAVE_SLOT_COUNT);
+                GameStateResult {
+                    next_state: slot.map_or(GameState::ShowSaveSlots, GameState::ShowSaveSlotOptions),
+                    engine_action: None,
+                }
+            }
+            _ => {
+                GameStateResult {
+                    next_state: GameState::ShowSaveSlots,
+                    engine_action: None,
+                }
+            }
+        }
+    }
+
+    fn save_slot_options(&self, slot: usize, action: Option<InputAction>) -> GameStateResult {
+        match action {
+            Some(InputAction::Exit) => {
+                GameStateResult {
+                    next_state: GameState::ShowSaveSlots,
+                    engine_action: None,
+                }
+            }
+            Some(InputAction::SelectOption('a')) => {
+                GameStateResult {
+                    next_state: GameState::PlayersTurn,
+                    engine_action: Some(EngineAction::LoadGame(slot)),
+                }
+            }
+            Some(InputAction::SelectOption('b')) => {
+                GameStateResult {
+                    next_state: GameState::ShowSaveSlots,
+                    engine_action: Some(EngineAction::DeleteSave(slot)),
+                }
+            }
+            _ => {
+                GameStateResult {
+                    next_state: *self,
+                    engine_action: None,
+                }
+            }
+        }
+    }
+
+    /// Read the seed for a new game digit by digit
+    fn enter_seed(&self, seed: u64, action: Option<InputAction>) -> GameStateResult {
+        match action {
+            Some(InputAction::Exit) => {
+                GameStateResult {
+                    next_state: GameState::MainMenu,
+                    engine_action: None,
+                }
+            }
+            Some(InputAction::Confirm) => {
+                GameStateResult {
+                    next_state: GameState::PlayersTurn,
+                    engine_action: Some(EngineAction::StartSeededGame(seed)),
+                }
+            }
+            Some(InputAction::DeleteCharacter) => {
+                GameStateResult {
+                    next_state: GameState::EnterSeed(seed / 10),
+                    engine_action: None,
+                }
+            }
+            Some(InputAction::SelectOption(c)) => {
+                // Ignore everything which isn't a digit or would overflow the seed
+                let next_seed = c.to_digit(10).and_then(|digit| {
+                    seed.checked_mul(10).and_then(|s| s.checked_add(digit as u64))
+                }).unwrap_or(seed);
+
+                GameStateResult {
+                    next_state: GameState::EnterSeed(next_seed),
+                    engine_action: None,
+                }
+            }
+            _ => {
+                GameStateResult {
+                    next_state: GameState::EnterSeed(seed),
+                    engine_action: None,
+                }
+            }
+        }
+    }
+
     fn quit_game_menu(&self, action: Option<InputAction>) -> GameStateResult {
         match action {
             Some(InputAction::Exit) | Some(InputAction::SelectOption('b')) => {
@@ -310,7 +446,20 @@ impl GameState {
         }
     }
 
-    fn player_turn(&self, ecs: &mut Ecs, fov_map: &mut Map, action: Option<InputAction>, log: Rc<MessageLog>, map: &GameMap, settings: &Settings) -> GameStateResult {
+    /// Change a tile of the map, and update what the player and the monsters can see through it
+    fn change_tile(ecs: &mut Ecs, fov_map: &mut Map, map: &mut GameMap, position: (i32, i32), kind: TileKind) {
+        map.set_tile_kind(position, kind);
+        update_tile(fov_map, map, position);
+
+        ecs.get_all_ids::<MonsterAi>().iter().for_each(|id| {
+            if let Some(ai) = ecs.get_component_mut::<MonsterAi>(*id) {
+                ai.update_fov_tile(map, position);
+            }
+        });
+    }
+
+    fn player_turn(&self, ecs: &mut Ecs, fov_map: &mut Map, action: Option<InputAction>, events: &EventBus, map: &mut GameMap, settings: &Settings,
+                   rng: &mut GameRng) -> GameStateResult {
 
         recompute_fov(ecs, fov_map, settings);
 
@@ -365,23 +514,20 @@ impl GameState {
                 };
                 let mut actions: Vec<EntityAction> = vec![];
 
-                ecs.get_all_ids::<Item>().iter().filter(|item_id| {
-                    if let Some(item_pos) = ecs.get_component::<Position>(**item_id) {
-                        p.0 == item_pos.position.0 && p.1 == item_pos.position.1
-                    } else {
-                        false
-                    }
-                }).for_each(|item_id| {
-                    actions.push(EntityAction::PickUpItem(id, *item_id))
+                ecs.query::<(&Item, &Position)>().filter(|(_, (_, item_pos))| {
+                    p.0 == item_pos.position.0 && p.1 == item_pos.position.1
+                }).for_each(|(item_id, _)| {
+                    actions.push(EntityAction::PickUpItem(id, item_id))
                 });
 
                 let next_state = if actions.is_empty() {
-                    log.add(Message::new("Nothing to pick up here".to_string(), colors::YELLOW));
+                    events.publish(GameEvent::Message(Message::new("Nothing to pick up here".to_string(), colors::YELLOW)));
                     GameState::PlayersTurn
                 } else {
                     actions.iter().for_each(|a| {
-                        a.execute(ecs, fov_map, Rc::clone(&log), settings);
+                        a.execute(ecs, fov_map, events, settings);
                     });
+                    Self::spend_player_energy(ecs, map, ActionCost::UseItem);
                     GameState::EnemyTurn
                 };
 
@@ -390,29 +536,54 @@ impl GameState {
                     engine_action: None,
                 }
             }
-            Some(InputAction::UseStairs) => {
+            Some(InputAction::CloseDoor) => {
+                let p = ecs.get_component::<Position>(ecs.player_entity_id).unwrap().position;
+
+                // Doors can't be closed while something lies in the way
+                let mut doors = vec![];
+                for dx in -1..2 {
+                    for dy in -1..2 {
+                        let door = (p.0 + dx, p.1 + dy);
+                        if map.tile_kind(door.0, door.1) == TileKind::OpenDoor
+                            && !ecs.query::<&Position>().any(|(_, other)| other.position == door) {
+                            doors.push(door);
+                        }
+                    }
+                }
+
+                let next_state = if doors.is_empty() {
+                    events.publish(GameEvent::Message(Message::new("There is no open door to close".to_string(), colors::YELLOW)));
+                    GameState::PlayersTurn
+                } else {
+                    doors.into_iter().for_each(|door| Self::change_tile(ecs, fov_map, map, door, TileKind::ClosedDoor));
+                    events.publish(GameEvent::Message(Message::new("You close the door.".to_string(), colors::WHITE)));
+                    Self::spend_player_energy(ecs, map, ActionCost::Move);
+                    GameState::EnemyTurn
+                };
+
+                GameStateResult {
+                    next_state,
+                    engine_action: None,
+                }
+            }
+            Some(InputAction::UseStairs(direction)) => {
                 let id = ecs.player_entity_id;
                 let p = {
                     let pos = ecs.get_component::<Position>(id).unwrap();
                     (pos.position.0, pos.position.1)
                 };
 
-                let used_stairs = ecs.get_all_ids::<Stairs>().iter().any(|stair_id| {
-                    if let Some(stair_pos) = ecs.get_component::<Position>(*stair_id) {
-                        p.0 == stair_pos.position.0 && p.1 == stair_pos.position.1
-                    } else {
-                        false
-                    }
-                });
+                let destination = ecs.query::<(&Stairs, &Position)>().find(|(_, (stairs, stair_pos))| {
+                    stairs.direction == direction && p.0 == stair_pos.position.0 && p.1 == stair_pos.position.1
+                }).map(|(_, (stairs, _))| stairs.destination);
 
-                if used_stairs {
-                    log.add(Message::new("You go down one level deeper...".to_string(), colors::GREEN));
+                if let Some(destination) = destination {
                     GameStateResult {
                         next_state: GameState::PlayersTurn,
-                        engine_action: Some(EngineAction::CreateNextFloor),
+                        engine_action: Some(EngineAction::ChangeFloor(destination)),
                     }
                 } else {
-                    log.add(Message::new("No stairs to use here".to_string(), colors::YELLOW));
+                    events.publish(GameEvent::Message(Message::new("No stairs to use here".to_string(), colors::YELLOW)));
                     GameStateResult {
                         next_state: GameState::PlayersTurn,
                         engine_action: None,
@@ -422,11 +593,34 @@ impl GameState {
             Some(InputAction::MovePlayer(vel_x, vel_y)) => {
                 let id = ecs.player_entity_id;
 
+                let (vel_x, vel_y) = if has_status(ecs, id, StatusKind::Confusion) {
+                    random_direction(rng)
+                } else {
+                    (vel_x, vel_y)
+                };
+
                 let destination = {
                     let p = ecs.get_component::<Position>(id).unwrap();
                     (p.position.0 + vel_x, p.position.1 + vel_y)
                 };
 
+                let (changed_kind, text) = match map.tile_kind(destination.0, destination.1) {
+                    TileKind::ClosedDoor => (Some(TileKind::OpenDoor), "You open the door."),
+                    TileKind::CrackedWall => (Some(TileKind::Floor), "You break through the cracked wall."),
+                    _ => (None, "")
+                };
+
+                if let Some(kind) = changed_kind {
+                    Self::change_tile(ecs, fov_map, map, destination, kind);
+                    events.publish(GameEvent::Message(Message::new(text.to_string(), colors::WHITE)));
+                    Self::spend_player_energy(ecs, map, ActionCost::Move);
+
+                    return GameStateResult {
+                        next_state: GameState::EnemyTurn,
+                        engine_action: None,
+                    };
+                }
+
                 let action = if !map.is_move_blocked(destination.0, destination.1) {
                     let targets = Position::is_blocked_by(&ecs, destination);
 
@@ -443,11 +637,22 @@ impl GameState {
                     EntityAction::Idle
                 };
 
-                let next_state = if let Some(state) = action.execute(ecs, fov_map, Rc::clone(&log), settings) {
+                let next_state = if let Some(state) = action.execute(ecs, fov_map, events, settings) {
                     state
                 } else {
                     GameState::EnemyTurn
                 };
+                Self::spend_player_energy(ecs, map, ActionCost::of(&action));
+
+                let position = ecs.get_component::<Position>(id).unwrap().position;
+                if position == destination && map.tile_kind(destination.0, destination.1) == TileKind::Chasm {
+                    events.publish(GameEvent::Message(Message::new("You fall into the chasm!".to_string(), colors::ORANGE)));
+
+                    return GameStateResult {
+                        next_state: GameState::PlayersTurn,
+                        engine_action: Some(EngineAction::FallIntoChasm),
+                    };
+                }
 
                 GameStateResult {
                     next_state,
@@ -463,64 +668,37 @@ impl GameState {
         }
     }
 
-    /// Enemy AI updates before the actual actions are taken.
-    ///
-    /// These are:
-    ///  - Set the player as target if no other target is set
-    ///  - Recompute the FOV
-    ///  - Look if the target is inside the FOV
-    ///
-    fn update_enemy_ai(&self, ecs: &mut Ecs, fov_map: &Map, settings: &Settings, log: Rc<MessageLog>) {
-        let player_id = ecs.player_entity_id;
-
-        let mut actions : Vec<EntityAction> = vec![];
-        actions.extend(self.create_set_ai_target_actions(ecs, player_id));
-        actions.extend(self.create_update_fov_actions(ecs));
-        actions.extend(self.create_look_for_target_actions(ecs));
-
-        actions.iter().for_each(|action| {
-            action.execute(ecs, fov_map, Rc::clone(&log), settings);
-        });
-    }
-
-    /// Set the player as target for each entity which has no target
-    fn create_set_ai_target_actions(&self, ecs: &Ecs, player_id: EntityId) -> Vec<EntityAction> {
-        ecs.get_all::<MonsterAi>().iter().filter(|(_, ai)|{
-            ai.has_no_target()
-        }).map(|(id, _)| {
-            EntityAction::SetAiTarget(*id, player_id)
-        }).collect()
-    }
-
-    fn create_update_fov_actions(&self, ecs: &Ecs) -> Vec<EntityAction> {
-        ecs.get_all::<MonsterAi>().iter().filter(|(_, ai)|{
-            !ai.is_chasing_target()
-        }).map(|(id, _)|{
-            EntityAction::UpdateFov(*id)
-        }).collect()
-    }
-
-    fn create_look_for_target_actions(&self, ecs: &Ecs) -> Vec<EntityAction> {
-        ecs.get_all::<MonsterAi>().iter().filter(|(_, ai)|{
-            !ai.is_chasing_target()
-        }).map(|(id, _)|{
-            EntityAction::LookForTarget(*id)
-        }).collect()
+    /// Let the player pay for an action which ends their turn
+    fn spend_player_energy(ecs: &mut Ecs, map: &GameMap, cost: ActionCost) {
+        let id = ecs.player_entity_id;
+        spend_energy(ecs, map, id, cost);
     }
 
+    /// Run all per-turn systems until the player has enough energy to act again, then check if
+    /// the player survived. A paralyzed player loses every turn until the paralysis wears off.
+    fn enemy_turn(&self, ecs: &mut Ecs, fov_map: &Map, events: &EventBus, map: &GameMap, settings: &Settings,
+                  scheduler: &mut Scheduler, rng: &mut GameRng) -> GameStateResult {
+        let player_id = ecs.player_entity_id;
 
-    fn enemy_turn(&self, ecs: &mut Ecs, fov_map: &Map, log: Rc<MessageLog>, map: &GameMap, settings: &Settings) -> GameStateResult {
-        self.update_enemy_ai(ecs, fov_map, settings, Rc::clone(&log));
-
-        let entity_ids = ecs.get_all_ids::<MonsterAi>();
+        while !ecs.has_component::<Corpse>(player_id) {
+            if ecs.get_component::<Actor>(player_id).map_or(true, |a| a.can_act()) {
+                // Without an `Actor` there is no energy to lose, so the paralysis could never end
+                if !has_status(ecs, player_id, StatusKind::Paralysis) || !ecs.has_component::<Actor>(player_id) {
+                    break;
+                }
+                Self::spend_player_energy(ecs, map, ActionCost::Move);
+                continue;
+            }
 
-        entity_ids.iter().for_each(|entity_id| {
-            let action = match ecs.get_component::<MonsterAi>(*entity_id) {
-                Some(ai) => ai.calculate_turn(ecs, map, settings),
-                _ => EntityAction::Idle
-            };
-            action.execute(ecs, fov_map, Rc::clone(&log), settings);
-        });
+            scheduler.run(&mut TurnContext {
+                ecs,
+                map,
+                fov_map,
+                events,
+                settings,
+                rng,
+            });
+        }
 
         if ecs.has_component::<Corpse>(ecs.player_entity_id) {
             GameStateResult {
@@ -536,3 +714,114 @@ impl GameState {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use tcod::input::{Event, EventFlags, Key, KeyCode, KEY_PRESS};
+
+    use super::*;
+    use terminal::HeadlessTerminal;
+    use game::simulation::Simulation;
+    use ecs::status::{StatusEffect, StatusEffects};
+
+    fn key_press(printable: char) -> (EventFlags, Event) {
+        (KEY_PRESS, Event::Key(Key { code: KeyCode::Char, printable, pressed: true, ..Default::default() }))
+    }
+
+    #[test]
+    fn enemy_turn_leaves_input_for_the_player() {
+        let mut terminal = HeadlessTerminal::new(10, 10);
+        terminal.push_event(key_press('l'));
+
+        assert!(GameState::EnemyTurn.read_input(&mut terminal).is_none());
+        assert!(!terminal.is_closed());
+
+        match GameState::PlayersTurn.read_input(&mut terminal) {
+            Some(InputAction::MovePlayer(1, 0)) => (),
+            _ => panic!("The key press was lost")
+        }
+        assert!(terminal.is_closed());
+    }
+
+    #[test]
+    fn only_existing_save_slots_can_be_selected() {
+        let select = |key| GameState::ShowSaveSlots.save_slots(Some(InputAction::SelectOption(key))).next_state;
+
+        assert_eq!(select('a'), GameState::ShowSaveSlotOptions(0));
+        assert_eq!(select((b'a' + SAVE_SLOT_COUNT as u8 - 1) as char), GameState::ShowSaveSlotOptions(SAVE_SLOT_COUNT - 1));
+        assert_eq!(select((b'a' + SAVE_SLOT_COUNT as u8) as char), GameState::ShowSaveSlots);
+        assert_eq!(select('A'), GameState::ShowSaveSlots);
+        // Would wrap around to 'a' if it was truncated to a byte
+        assert_eq!(select('\u{161}'), GameState::ShowSaveSlots);
+    }
+
+    #[test]
+    fn paralyzed_player_without_actor_doesnt_hang_the_game() {
+        let settings = Settings::new();
+        let mut ecs = Ecs::initialize();
+        let map = GameMap::new(10, 10);
+        let fov_map = Map::new(10, 10);
+        let events = EventBus::new();
+
+        let player_id = ecs.create_entity();
+        ecs.player_entity_id = player_id;
+        let mut effects = StatusEffects::new();
+        effects.add(StatusEffect::new(StatusKind::Paralysis, 5, 0));
+        ecs.register_component(player_id, effects);
+
+        let result = GameState::EnemyTurn.enemy_turn(&mut ecs, &fov_map, &events, &map, &settings,
+                                                      &mut Scheduler::with_default_systems(), &mut GameRng::from_seed(0));
+        assert_eq!(result.next_state, GameState::PlayersTurn);
+    }
+
+    #[test]
+    fn doors_open_and_close() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let start = sim.player_position();
+        let (door, direction) = sim.free_tile_next_to_player();
+        sim.set_tile_kind(door, TileKind::ClosedDoor);
+
+        sim.send(InputAction::MovePlayer(direction.0, direction.1));
+        assert_eq!(sim.tile_kind(door), TileKind::OpenDoor);
+        assert_eq!(sim.player_position(), start);
+        assert!(sim.has_message("You open the door."));
+
+        sim.send(InputAction::MovePlayer(direction.0, direction.1));
+        assert_eq!(sim.player_position(), door);
+        sim.send(InputAction::CloseDoor);
+        assert_eq!(sim.tile_kind(door), TileKind::OpenDoor);
+
+        sim.send(InputAction::MovePlayer(-direction.0, -direction.1));
+        sim.send(InputAction::CloseDoor);
+        assert_eq!(sim.tile_kind(door), TileKind::ClosedDoor);
+        assert!(sim.has_message("You close the door."));
+    }
+
+    #[test]
+    fn cracked_walls_can_be_broken() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let (wall, direction) = sim.free_tile_next_to_player();
+        sim.set_tile_kind(wall, TileKind::CrackedWall);
+
+        sim.send(InputAction::MovePlayer(direction.0, direction.1));
+        assert_eq!(sim.tile_kind(wall), TileKind::Floor);
+        assert!(sim.has_message("You break through the cracked wall."));
+    }
+
+    #[test]
+    fn chasms_drop_to_the_next_floor() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let (chasm, direction) = sim.free_tile_next_to_player();
+        sim.set_tile_kind(chasm, TileKind::Chasm);
+
+        sim.send(InputAction::MovePlayer(direction.0, direction.1));
+        assert_eq!(sim.floor_number(), 2);
+        assert!(sim.has_message("You fall into the chasm!"));
+    }
+}
@@ -0,0 +1,550 @@
+use tcod::Map;
+use tcod::colors;
+
+use ecs::Ecs;
+use ecs::action::EntityAction;
+use ecs::component::{Actor, MonsterAi, Morale, Name, Position};
+use ecs::status::{StatusEffects, StatusKind, has_status, random_direction};
+use ecs::id::EntityId;
+use events::{EventBus, GameEvent};
+use map_objects::dijkstra::DijkstraMap;
+use map_objects::map::GameMap;
+use map_objects::tile::{TileKind, LAVA_DAMAGE};
+use message::Message;
+use random_utils::GameRng;
+use settings::Settings;
+
+/// The energy an `Actor` needs to act. Every action uses up some of it, and every turn the
+/// `Actor` regains as much as its speed.
+pub const ACTION_ENERGY: i32 = 100;
+
+/// The kinds of actions, which use up different amounts of energy
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum ActionCost {
+    /// Moving, waiting, opening doors and everything else which only takes a moment
+    Move,
+    Attack,
+    /// Using, picking up, dropping or equipping an item
+    UseItem,
+}
+
+impl ActionCost {
+    pub fn energy(&self) -> i32 {
+        match *self {
+            ActionCost::Move => ACTION_ENERGY,
+            ActionCost::Attack => ACTION_ENERGY,
+            ActionCost::UseItem => ACTION_ENERGY * 3 / 2,
+        }
+    }
+
+    /// The cost of an action a monster took
+    pub fn of(action: &EntityAction) -> ActionCost {
+        match *action {
+            EntityAction::MeleeAttack(..) | EntityAction::RangedAttack(..) => ActionCost::Attack,
+            EntityAction::UseItem(..) | EntityAction::PickUpItem(..) | EntityAction::DropItem(..)
+            | EntityAction::ToggleEquipment(..) => ActionCost::UseItem,
+            _ => ActionCost::Move
+        }
+    }
+}
+
+/// Use up the energy of an action. Acting while standing on a slow tile takes longer, so the
+/// cost is multiplied by the movement cost of the tile the `Actor` stands on afterwards.
+pub fn spend_energy(ecs: &mut Ecs, map: &GameMap, entity_id: EntityId, cost: ActionCost) {
+    let movement_cost = ecs.get_component::<Position>(entity_id)
+        .map(|p| map.tile_kind(p.x(), p.y()).movement_cost())
+        .unwrap_or(1)
+        .max(1);
+
+    if let Some(actor) = ecs.get_component_mut::<Actor>(entity_id) {
+        actor.energy -= cost.energy() * movement_cost as i32;
+    }
+}
+
+/// The phases of a turn. Systems of an earlier phase always run before systems of a later one.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
+pub enum SystemPhase {
+    /// Prepare the turn, e.g. update what the entities know about their surroundings
+    Prepare,
+    /// Let the entities act
+    Act,
+    /// Apply everything which happens at the end of a turn, e.g. regeneration or hunger
+    Resolve,
+}
+
+/// Everything a `System` can access while running
+pub struct TurnContext<'a> {
+    pub ecs: &'a mut Ecs,
+    pub map: &'a GameMap,
+    pub fov_map: &'a Map,
+    pub events: &'a EventBus,
+    pub settings: &'a Settings,
+    pub rng: &'a mut GameRng,
+}
+
+impl<'a> TurnContext<'a> {
+    /// Check if the player notices what happens to an entity, because it's the player itself or
+    /// because it's in view
+    pub fn is_noticed(&self, entity_id: EntityId) -> bool {
+        entity_id == self.ecs.player_entity_id
+            || self.ecs.get_component::<Position>(entity_id).map_or(false, |p| self.fov_map.is_in_fov(p.x(), p.y()))
+    }
+}
+
+/// A piece of game logic which runs once per turn, after the player has acted.
+pub trait System {
+    /// The phase in which the system runs
+    fn phase(&self) -> SystemPhase;
+
+    fn run(&mut self, context: &mut TurnContext);
+}
+
+/// Runs all registered `System`s in order
+///
+/// Systems run ordered by their `SystemPhase`. Systems of the same phase run in the order in
+/// which they were added.
+pub struct Scheduler {
+    systems: Vec<Box<System>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler {
+            systems: vec![],
+        }
+    }
+
+    /// Create a scheduler with all systems the game needs
+    pub fn with_default_systems() -> Scheduler {
+        let mut scheduler = Scheduler::new();
+        scheduler.add(Box::new(MonsterAiUpdateSystem {}));
+        scheduler.add(Box::new(MonsterAiTurnSystem {}));
+        scheduler.add(Box::new(TileEffectSystem {}));
+        scheduler.add(Box::new(StatusEffectSystem {}));
+        scheduler.add(Box::new(EnergySystem {}));
+        scheduler
+    }
+
+    /// Add a system behind all systems of the same or an earlier phase
+    pub fn add(&mut self, system: Box<System>) {
+        let phase = system.phase();
+        let index = self.systems.iter()
+            .position(|s| s.phase() > phase)
+            .unwrap_or(self.systems.len());
+
+        self.systems.insert(index, system);
+    }
+
+    /// Run one turn
+    pub fn run(&mut self, context: &mut TurnContext) {
+        for system in self.systems.iter_mut() {
+            system.run(context);
+        }
+    }
+}
+
+/// Enemy AI updates before the actual actions are taken.
+///
+/// These are:
+///  - Set the player as target if no other target is set
+///  - Recompute the FOV of every monster near the player
+///  - Look if the target is inside the FOV, and remember where it was seen
+///
+pub struct MonsterAiUpdateSystem {}
+
+impl MonsterAiUpdateSystem {
+    /// Set the player as target for each entity which has no target
+    fn create_set_ai_target_actions(&self, ecs: &Ecs, player_id: EntityId) -> Vec<EntityAction> {
+        ecs.query::<&MonsterAi>().filter(|(_, ai)|{
+            ai.has_no_target()
+        }).map(|(id, _)| {
+            EntityAction::SetAiTarget(id, player_id)
+        }).collect()
+    }
+
+    /// Monsters far away from the player don't act, so their FOV isn't needed
+    fn create_update_fov_actions(&self, ecs: &Ecs, settings: &Settings) -> Vec<EntityAction> {
+        ecs.query::<&MonsterAi>().filter(|(_, ai)|{
+            ai.is_within_ai_distance(ecs, settings)
+        }).map(|(id, _)|{
+            EntityAction::UpdateFov(id)
+        }).collect()
+    }
+
+    /// Every monster looks for its target, so it notices when the target gets out of sight
+    fn create_look_for_target_actions(&self, ecs: &Ecs) -> Vec<EntityAction> {
+        ecs.get_all_ids::<MonsterAi>().into_iter().map(|id|{
+            EntityAction::LookForTarget(id)
+        }).collect()
+    }
+}
+
+impl System for MonsterAiUpdateSystem {
+    fn phase(&self) -> SystemPhase {
+        SystemPhase::Prepare
+    }
+
+    fn run(&mut self, context: &mut TurnContext) {
+        let player_id = context.ecs.player_entity_id;
+
+        let mut actions : Vec<EntityAction> = vec![];
+        actions.extend(self.create_set_ai_target_actions(context.ecs, player_id));
+        actions.extend(self.create_update_fov_actions(context.ecs, context.settings));
+        actions.extend(self.create_look_for_target_actions(context.ecs));
+
+        actions.iter().for_each(|action| {
+            action.execute(context.ecs, context.fov_map, context.events, context.settings);
+        });
+    }
+}
+
+/// Calculate and execute the actions of every monster.
+///
+/// A monster keeps acting as long as it has enough energy left, so fast monsters act several
+/// times per turn, and slow ones skip turns until they gathered enough energy. Paralyzed
+/// monsters waste their turns, and confused ones stumble into a random direction.
+///
+/// Badly wounded monsters flee from the player along a flee `DijkstraMap`, until they are
+/// healed or cornered.
+pub struct MonsterAiTurnSystem {}
+
+impl MonsterAiTurnSystem {
+    /// Update the morale of a monster, and tell the player if it changed in view
+    fn update_morale(context: &mut TurnContext, entity_id: EntityId) {
+        let (hp, max_hp) = match context.ecs.get_component::<Actor>(entity_id) {
+            Some(actor) => (actor.hp, actor.max_hp(context.ecs)),
+            None => return
+        };
+
+        let changed = match context.ecs.get_component_mut::<MonsterAi>(entity_id) {
+            Some(ai) => ai.update_morale(hp, max_hp, context.settings),
+            None => return
+        };
+        if !context.is_noticed(entity_id) {
+            return;
+        }
+
+        let name = context.ecs.get_component::<Name>(entity_id).map(|n| n.name.to_uppercase()).unwrap_or_default();
+        match changed {
+            Some(Morale::Fleeing) => context.events.publish(GameEvent::Message(Message::new(format!("The {} flees!", name), colors::LIGHT_ORANGE))),
+            Some(Morale::Steady) => context.events.publish(GameEvent::Message(Message::new(format!("The {} regains its courage.", name), colors::WHITE))),
+            _ => ()
+        }
+    }
+
+    /// Step away from the player, or `None` if the monster is cornered
+    fn flee_action(ecs: &Ecs, map: &GameMap, flee_map: &DijkstraMap, entity_id: EntityId) -> Option<EntityAction> {
+        let position = ecs.get_component::<Position>(entity_id)?.position;
+
+        flee_map.downhill(position, &|p| !map.is_move_blocked(p.0, p.1) && Position::is_blocked_by(ecs, p).is_empty())
+            .map(|p| EntityAction::MoveTo(entity_id, p))
+    }
+
+    /// Stumble into a random direction, attacking whoever stands there
+    fn confused_action(ecs: &Ecs, map: &GameMap, entity_id: EntityId, rng: &mut GameRng) -> EntityAction {
+        let (dx, dy) = random_direction(rng);
+        let destination = match ecs.get_component::<Position>(entity_id) {
+            Some(p) => (p.x() + dx, p.y() + dy),
+            None => return EntityAction::Idle
+        };
+
+        if map.is_move_blocked(destination.0, destination.1) {
+            return EntityAction::Idle;
+        }
+
+        match Position::is_blocked_by(ecs, destination).first() {
+            Some(target_id) if ecs.has_component::<Actor>(*target_id) => EntityAction::MeleeAttack(entity_id, *target_id),
+            Some(_) => EntityAction::Idle,
+            None => EntityAction::MoveRelative(entity_id, (dx, dy))
+        }
+    }
+}
+
+impl System for MonsterAiTurnSystem {
+    fn phase(&self) -> SystemPhase {
+        SystemPhase::Act
+    }
+
+    fn run(&mut self, context: &mut TurnContext) {
+        let entity_ids = context.ecs.get_all_ids::<MonsterAi>();
+        // Only calculated once a monster actually flees
+        let mut flee_map: Option<DijkstraMap> = None;
+
+        entity_ids.iter().for_each(|entity_id| {
+            while context.ecs.get_component::<Actor>(*entity_id).map_or(false, |a| a.can_act() && !a.is_dead()) {
+                Self::update_morale(context, *entity_id);
+
+                let is_fleeing = context.ecs.get_component::<MonsterAi>(*entity_id)
+                    .map_or(false, |ai| ai.is_fleeing() && ai.is_within_ai_distance(context.ecs, context.settings));
+
+                let flee_action = if is_fleeing {
+                    let player_position = context.ecs.get_component::<Position>(context.ecs.player_entity_id).map(|p| p.position);
+                    let flee_map = flee_map.get_or_insert_with(|| {
+                        DijkstraMap::flee(context.map, &player_position.into_iter().collect::<Vec<_>>())
+                    });
+                    let action = Self::flee_action(context.ecs, context.map, flee_map, *entity_id);
+
+                    if action.is_none() {
+                        if let Some(ai) = context.ecs.get_component_mut::<MonsterAi>(*entity_id) {
+                            ai.set_cornered();
+                        }
+                        if context.is_noticed(*entity_id) {
+                            let name = context.ecs.get_component::<Name>(*entity_id).map(|n| n.name.to_uppercase()).unwrap_or_default();
+                            context.events.publish(GameEvent::Message(Message::new(format!("The {} is cornered and fights back!", name), colors::LIGHT_ORANGE)));
+                        }
+                    }
+                    action
+                } else {
+                    None
+                };
+
+                let action = if has_status(context.ecs, *entity_id, StatusKind::Paralysis) {
+                    EntityAction::Idle
+                } else if has_status(context.ecs, *entity_id, StatusKind::Confusion) {
+                    Self::confused_action(context.ecs, context.map, *entity_id, context.rng)
+                } else if let Some(action) = flee_action {
+                    action
+                } else {
+                    match context.ecs.get_component::<MonsterAi>(*entity_id) {
+                        Some(ai) => ai.calculate_turn(context.ecs, context.map, context.settings, context.rng),
+                        _ => EntityAction::Idle
+                    }
+                };
+                action.execute(context.ecs, context.fov_map, context.events, context.settings);
+                spend_energy(context.ecs, context.map, *entity_id, ActionCost::of(&action));
+            }
+        });
+    }
+}
+
+/// Apply the effects of the tiles the `Actor`s stand on, e.g. burn everyone standing in lava
+pub struct TileEffectSystem {}
+
+impl System for TileEffectSystem {
+    fn phase(&self) -> SystemPhase {
+        SystemPhase::Resolve
+    }
+
+    fn run(&mut self, context: &mut TurnContext) {
+        let burning: Vec<EntityId> = context.ecs.query::<(&Actor, &Position)>()
+            .filter(|(_, (actor, p))| !actor.is_dead() && context.map.tile_kind(p.x(), p.y()) == TileKind::Lava)
+            .map(|(id, _)| id)
+            .collect();
+
+        burning.into_iter().for_each(|id| {
+            if context.is_noticed(id) {
+                let name = context.ecs.get_component::<Name>(id).map(|n| n.name.to_uppercase()).unwrap_or_default();
+                context.events.publish(GameEvent::Message(Message::new(format!("The {} is burned by the lava.", name), colors::ORANGE)));
+            }
+
+            EntityAction::TakeDamage(id, LAVA_DAMAGE, EntityId::none())
+                .execute(context.ecs, context.fov_map, context.events, context.settings);
+        });
+    }
+}
+
+/// Apply the status effects of every living `Actor`, and count down how long they last
+pub struct StatusEffectSystem {}
+
+impl System for StatusEffectSystem {
+    fn phase(&self) -> SystemPhase {
+        SystemPhase::Resolve
+    }
+
+    fn run(&mut self, context: &mut TurnContext) {
+        let affected: Vec<EntityId> = context.ecs.query::<(&StatusEffects, &Actor)>()
+            .filter(|(_, (_, actor))| !actor.is_dead())
+            .map(|(id, _)| id)
+            .collect();
+
+        affected.into_iter().for_each(|id| {
+            let name = context.ecs.get_component::<Name>(id).map(|n| n.name.to_uppercase()).unwrap_or_default();
+            let noticed = context.is_noticed(id);
+            let effects = context.ecs.get_component::<StatusEffects>(id).map(|s| s.effects().to_vec()).unwrap_or_default();
+
+            for effect in effects {
+                match effect.kind {
+                    StatusKind::Poison => {
+                        if noticed {
+                            context.events.publish(GameEvent::Message(Message::new(format!("The {} suffers from the poison.", name), effect.kind.color())));
+                        }
+                        EntityAction::TakeDamage(id, effect.strength, EntityId::none())
+                            .execute(context.ecs, context.fov_map, context.events, context.settings);
+                    }
+                    StatusKind::Regeneration => {
+                        let max_hp = context.ecs.get_component::<Actor>(id).map_or(0, |a| a.max_hp(context.ecs));
+                        if let Some(actor) = context.ecs.get_component_mut::<Actor>(id) {
+                            actor.hp = (actor.hp + effect.strength).min(max_hp);
+                        }
+                    }
+                    StatusKind::Confusion | StatusKind::Paralysis => ()
+                }
+            }
+
+            // The poison might have killed it, which already removed the effects
+            let expired = match context.ecs.get_component_mut::<StatusEffects>(id) {
+                Some(effects) => effects.tick(),
+                None => return
+            };
+            if noticed {
+                for effect in expired {
+                    context.events.publish(GameEvent::Message(Message::new(format!("The {} is no longer {}.", name, effect.kind.name()), colors::WHITE)));
+                }
+            }
+
+            if context.ecs.get_component::<StatusEffects>(id).map_or(false, |s| s.is_empty()) {
+                context.ecs.remove_component::<StatusEffects>(id);
+            }
+        });
+    }
+}
+
+/// Let every `Actor` regain energy at the end of a turn
+pub struct EnergySystem {}
+
+impl System for EnergySystem {
+    fn phase(&self) -> SystemPhase {
+        SystemPhase::Resolve
+    }
+
+    fn run(&mut self, context: &mut TurnContext) {
+        let ids = context.ecs.get_all_ids::<Actor>();
+
+        ids.into_iter().for_each(|id| {
+            // Everyone regains at least a little energy, so nobody gets stuck forever
+            if let Some(actor) = context.ecs.get_component_mut::<Actor>(id) {
+                actor.energy += actor.speed.max(1);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use ecs::creature::CreatureTemplate;
+    use ecs::status::StatusEffect;
+    use game::simulation::Simulation;
+    use game::input::InputAction;
+    use message::MessageLog;
+
+    #[test]
+    fn fast_monsters_act_twice_per_turn() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let (_, direction) = sim.spawn_next_to_player(CreatureTemplate::Bat);
+
+        sim.send(InputAction::MovePlayer(direction.0, direction.1));
+        assert_eq!(sim.count_messages("The BAT attacks"), 2);
+    }
+
+    #[test]
+    fn slow_monsters_act_every_other_turn() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let (_, direction) = sim.spawn_next_to_player(CreatureTemplate::Zombie);
+
+        sim.send(InputAction::MovePlayer(direction.0, direction.1));
+        assert_eq!(sim.count_messages("The ZOMBIE attacks"), 0);
+
+        sim.send(InputAction::MovePlayer(direction.0, direction.1));
+        assert_eq!(sim.count_messages("The ZOMBIE attacks"), 1);
+    }
+
+    #[test]
+    fn shallow_water_slows_the_player() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let (water, direction) = sim.free_tile_next_to_player();
+        sim.set_tile_kind(water, TileKind::ShallowWater);
+        let orc_position = sim.free_neighbour(water).unwrap().0;
+        sim.spawn_creature(CreatureTemplate::Orc, orc_position);
+
+        // Acting while standing in the water takes two turns, so the orc attacks twice
+        sim.send(InputAction::MovePlayer(direction.0, direction.1));
+        assert_eq!(sim.count_messages("The ORC attacks"), 2);
+    }
+
+    #[test]
+    fn lava_burns_actors() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+
+        let (lava, direction) = sim.free_tile_next_to_player();
+        sim.set_tile_kind(lava, TileKind::Lava);
+        let hp = sim.player_hp();
+
+        sim.send(InputAction::MovePlayer(direction.0, direction.1));
+        assert_eq!(sim.player_position(), lava);
+        assert_eq!(sim.player_hp(), hp - LAVA_DAMAGE);
+        assert!(sim.has_message("The PLAYER is burned by the lava."));
+    }
+
+    /// Run a system once, while the player sees nothing. Returns the messages it published.
+    fn run_out_of_view(system: &mut System, ecs: &mut Ecs, map: &GameMap) -> Vec<String> {
+        let settings = Settings::new();
+        let fov_map = Map::new(map.dimensions.0, map.dimensions.1);
+        let log = Rc::new(MessageLog::new());
+        let events = EventBus::new();
+        events.subscribe(Box::new(Rc::clone(&log)));
+        let mut rng = GameRng::from_seed(0);
+
+        system.run(&mut TurnContext {
+            ecs,
+            map,
+            fov_map: &fov_map,
+            events: &events,
+            settings: &settings,
+            rng: &mut rng,
+        });
+        events.dispatch(ecs);
+
+        let messages = log.messages().iter().map(|m| m.text.clone()).collect();
+        messages
+    }
+
+    #[test]
+    fn only_burns_in_view_are_reported() {
+        let mut ecs = Ecs::initialize();
+        let mut map = GameMap::new(10, 1);
+        (0..10).for_each(|x| map.set_tile_kind((x, 0), TileKind::Lava));
+
+        let player = CreatureTemplate::Player.create_on_position(&mut ecs, &map, (0, 0)).unwrap();
+        let orc = CreatureTemplate::Orc.create_on_position(&mut ecs, &map, (9, 0)).unwrap();
+        let hp = |ecs: &Ecs, id: EntityId| ecs.get_component::<Actor>(id).unwrap().hp;
+        let (player_hp, orc_hp) = (hp(&ecs, player), hp(&ecs, orc));
+
+        // The player always notices being burned
+        let messages = run_out_of_view(&mut TileEffectSystem {}, &mut ecs, &map);
+
+        assert_eq!(hp(&ecs, player), player_hp - LAVA_DAMAGE);
+        assert_eq!(hp(&ecs, orc), orc_hp - LAVA_DAMAGE);
+        assert!(messages.contains(&"The PLAYER is burned by the lava.".to_string()));
+        assert!(!messages.contains(&"The ORC is burned by the lava.".to_string()));
+    }
+
+    #[test]
+    fn only_status_effects_in_view_are_reported() {
+        let mut ecs = Ecs::initialize();
+        let map = GameMap::new(10, 1);
+
+        let player = CreatureTemplate::Player.create_on_position(&mut ecs, &map, (0, 0)).unwrap();
+        let orc = CreatureTemplate::Orc.create_on_position(&mut ecs, &map, (9, 0)).unwrap();
+        for id in &[player, orc] {
+            let mut effects = StatusEffects::new();
+            effects.add(StatusEffect::new(StatusKind::Poison, 1, 1));
+            ecs.register_component(*id, effects);
+        }
+
+        let messages = run_out_of_view(&mut StatusEffectSystem {}, &mut ecs, &map);
+
+        assert!(messages.contains(&"The PLAYER suffers from the poison.".to_string()));
+        assert!(messages.contains(&"The PLAYER is no longer poisoned.".to_string()));
+        assert!(!messages.iter().any(|m| m.starts_with("The ORC suffers") || m.starts_with("The ORC is no longer")));
+    }
+}
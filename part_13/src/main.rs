@@ -1,6 +1,7 @@
 extern crate tcod;
 extern crate rand;
 extern crate textwrap;
+extern crate flate2;
 
 #[macro_use]
 extern crate json;
@@ -8,18 +9,65 @@ extern crate core;
 
 mod ecs;
 mod render;
+mod camera;
 mod map_objects;
 mod game;
 mod message;
+mod events;
 mod settings;
 mod savegame;
 mod engine;
 mod random_utils;
+mod terminal;
+
+use std::env;
+use std::path::Path;
 
 use engine::Engine;
+use savegame::{SaveDirectory, SaveFormat};
 use settings::Settings;
+use terminal::AnsiTerminal;
 
+/// Start the game in a window, or inside the current text terminal if `--ansi` is passed.
+///
+/// `--save-format <json|binary|compressed>` selects the format new save files are written in.
+///
+/// `--convert-save <input> <output>` converts a save file between JSON and the binary format
+/// instead of starting the game.
 fn main() {
-    Engine::run(&Settings::new());
+    let mut settings = Settings::new();
+    let args : Vec<String> = env::args().collect();
+
+    if let Some(index) = args.iter().position(|arg| arg == "--save-format") {
+        match args.get(index + 1).and_then(|name| SaveFormat::from_name(name)) {
+            Some(format) => settings.set_save_format(format),
+            None => {
+                eprintln!("Usage: --save-format <json|binary|compressed>");
+                return;
+            }
+        }
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--convert-save") {
+        match (args.get(index + 1), args.get(index + 2)) {
+            (Some(input), Some(output)) => {
+                match savegame::convert(Path::new(input), Path::new(output)) {
+                    Ok(format) => println!("Converted {} to {} ({:?})", input, output, format),
+                    Err(e) => eprintln!("{}", e)
+                }
+            }
+            _ => eprintln!("Usage: --convert-save <input> <output>")
+        }
+        return;
+    }
+
+    let saves = SaveDirectory::in_user_data();
+    saves.import_legacy_save();
+
+    if args.iter().any(|arg| arg == "--ansi") {
+        Engine::run_on(&settings, Box::new(AnsiTerminal::new(&settings)), saves);
+    } else {
+        Engine::run(&settings, saves);
+    }
 }
 
@@ -5,6 +5,11 @@ pub enum Color {
     DarkFloor,
     LightWall,
     LightFloor,
+    DarkWater,
+    LightWater,
+    DarkLava,
+    LightLava,
+    Chasm,
 }
 
 impl Color {
@@ -15,6 +20,11 @@ impl Color {
             Color::DarkWall => colors::Color { r: 0, g: 0, b: 100 },
             Color::LightFloor => colors::Color { r: 200, g: 180, b: 150 },
             Color::LightWall => colors::Color { r: 130, g: 110, b: 50 },
+            Color::DarkWater => colors::Color { r: 20, g: 40, b: 130 },
+            Color::LightWater => colors::Color { r: 60, g: 110, b: 200 },
+            Color::DarkLava => colors::Color { r: 90, g: 20, b: 20 },
+            Color::LightLava => colors::Color { r: 220, g: 70, b: 0 },
+            Color::Chasm => colors::Color { r: 0, g: 0, b: 0 },
         }
     }
-}
\ No newline at end of file
+}
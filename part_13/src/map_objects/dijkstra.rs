@@ -0,0 +1,220 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use map_objects::map::GameMap;
+
+/// Scales the distances of a flee map. Values below `-1` make fleeing entities prefer running
+/// past their threat into open space over getting stuck in the nearest dead end.
+const FLEE_FACTOR: f32 = -1.2;
+
+/// The walking distance from every tile of a map to the nearest of a set of goals.
+///
+/// Stepping downhill, i.e. onto the neighbour with the lowest value, leads to the nearest goal
+/// on the shortest way. Every step costs the movement cost of the tile it leads to. Walls and
+/// hazards can't be entered and have no value.
+pub struct DijkstraMap {
+    dimensions: (i32, i32),
+    values: Vec<Option<i32>>,
+}
+
+impl DijkstraMap {
+    /// A map leading towards the goals
+    pub fn new(map: &GameMap, goals: &[(i32, i32)]) -> DijkstraMap {
+        let (width, height) = map.dimensions;
+        let mut dijkstra = DijkstraMap {
+            dimensions: map.dimensions,
+            values: vec![None; (width * height) as usize],
+        };
+
+        for goal in goals {
+            if let Some(index) = dijkstra.index(*goal) {
+                dijkstra.values[index] = Some(0);
+            }
+        }
+
+        dijkstra.relax(map);
+        dijkstra
+    }
+
+    /// A map leading away from the threats.
+    ///
+    /// The distances to the threats are negated and scaled, then the map is calculated again, so
+    /// stepping downhill still finds the best way away instead of running into a corner.
+    pub fn flee(map: &GameMap, threats: &[(i32, i32)]) -> DijkstraMap {
+        let mut dijkstra = DijkstraMap::new(map, threats);
+
+        dijkstra.values.iter_mut().for_each(|value| {
+            *value = value.map(|distance| (distance as f32 * FLEE_FACTOR).round() as i32);
+        });
+
+        dijkstra.relax(map);
+        dijkstra
+    }
+
+    /// The value of a position, `None` if it can't be reached at all
+    pub fn value(&self, position: (i32, i32)) -> Option<i32> {
+        self.index(position).and_then(|index| self.values[index])
+    }
+
+    /// The neighbour of `from` with the lowest value, if it is lower than the value of `from`.
+    /// Neighbours for which `is_free` returns `false` are skipped, e.g. those blocked by monsters.
+    pub fn downhill(&self, from: (i32, i32), is_free: &Fn((i32, i32)) -> bool) -> Option<(i32, i32)> {
+        let mut best = None;
+        let mut best_value = self.value(from)?;
+
+        for neighbour in self.neighbours(from) {
+            if let Some(value) = self.value(neighbour) {
+                if value < best_value && is_free(neighbour) {
+                    best = Some(neighbour);
+                    best_value = value;
+                }
+            }
+        }
+
+        best
+    }
+
+    fn index(&self, position: (i32, i32)) -> Option<usize> {
+        let (width, height) = self.dimensions;
+        if position.0 < 0 || position.1 < 0 || position.0 >= width || position.1 >= height {
+            None
+        } else {
+            Some((position.1 * width + position.0) as usize)
+        }
+    }
+
+    fn neighbours(&self, position: (i32, i32)) -> Vec<(i32, i32)> {
+        let mut neighbours = vec![];
+        for dx in -1..2 {
+            for dy in -1..2 {
+                let neighbour = (position.0 + dx, position.1 + dy);
+                if (dx, dy) != (0, 0) && self.index(neighbour).is_some() {
+                    neighbours.push(neighbour);
+                }
+            }
+        }
+        neighbours
+    }
+
+    /// Lower the value of every tile to the cheapest way from one of its neighbours
+    fn relax(&mut self, map: &GameMap) {
+        let width = self.dimensions.0;
+        let mut queue = BinaryHeap::new();
+
+        for (index, value) in self.values.iter().enumerate() {
+            if let Some(value) = value {
+                queue.push(Reverse((*value, index)));
+            }
+        }
+
+        while let Some(Reverse((value, index))) = queue.pop() {
+            // The tile was lowered again after this entry was queued
+            if self.values[index] != Some(value) {
+                continue;
+            }
+
+            let position = (index as i32 % width, index as i32 / width);
+            for neighbour in self.neighbours(position) {
+                let kind = map.tile_kind(neighbour.0, neighbour.1);
+                if kind.movement_cost() == 0 || kind.is_hazard() {
+                    continue;
+                }
+
+                let neighbour_value = value + kind.movement_cost() as i32;
+                let neighbour_index = self.index(neighbour).unwrap();
+                if self.values[neighbour_index].map_or(true, |old| neighbour_value < old) {
+                    self.values[neighbour_index] = Some(neighbour_value);
+                    queue.push(Reverse((neighbour_value, neighbour_index)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use map_objects::tile::TileKind;
+
+    /// A map drawn with the codes of its tiles, one string per row
+    fn map_from(rows: &[&str]) -> GameMap {
+        let mut map = GameMap::new(rows[0].len() as i32, rows.len() as i32);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, code) in row.chars().enumerate() {
+                map.set_tile_kind((x as i32, y as i32), TileKind::from_code(code).unwrap());
+            }
+        }
+        map
+    }
+
+    #[test]
+    fn distances_add_up_the_movement_costs() {
+        let map = map_from(&[
+            "######",
+            "#....#",
+            "#.~..#",
+            "######",
+        ]);
+        let dijkstra = DijkstraMap::new(&map, &[(1, 1)]);
+
+        assert_eq!(dijkstra.value((1, 1)), Some(0));
+        assert_eq!(dijkstra.value((2, 1)), Some(1));
+        assert_eq!(dijkstra.value((4, 1)), Some(3));
+        // Diagonal steps cost as much as straight ones
+        assert_eq!(dijkstra.value((4, 2)), Some(3));
+        assert_eq!(dijkstra.value((2, 2)), Some(2));
+        assert_eq!(dijkstra.value((3, 2)), Some(2));
+    }
+
+    #[test]
+    fn distances_lead_around_walls() {
+        let map = map_from(&[
+            "#####",
+            "#.#.#",
+            "#.#.#",
+            "#...#",
+            "#####",
+        ]);
+        let dijkstra = DijkstraMap::new(&map, &[(1, 1)]);
+
+        assert_eq!(dijkstra.value((3, 1)), Some(4));
+        assert_eq!(dijkstra.value((2, 1)), None);
+        assert_eq!(dijkstra.downhill((3, 1), &|_| true), Some((3, 2)));
+    }
+
+    #[test]
+    fn blocked_tiles_and_everything_behind_them_are_unreachable() {
+        let map = map_from(&[
+            "#######",
+            "#..+..#",
+            "#..^..#",
+            "#######",
+        ]);
+        let dijkstra = DijkstraMap::new(&map, &[(1, 1)]);
+
+        assert_eq!(dijkstra.value((2, 2)), Some(1));
+        assert_eq!(dijkstra.value((3, 1)), None);
+        assert_eq!(dijkstra.value((3, 2)), None);
+        assert_eq!(dijkstra.value((4, 1)), None);
+        assert_eq!(dijkstra.value((5, 2)), None);
+        assert_eq!(dijkstra.value((-1, 0)), None);
+        assert_eq!(dijkstra.downhill((5, 2), &|_| true), None);
+    }
+
+    #[test]
+    fn fleeing_leads_away_from_the_threat() {
+        let map = map_from(&[
+            "########",
+            "#......#",
+            "########",
+        ]);
+        let dijkstra = DijkstraMap::flee(&map, &[(1, 1)]);
+
+        let values: Vec<i32> = (1..7).map(|x| dijkstra.value((x, 1)).unwrap()).collect();
+        // The scaled distances are relaxed again into one slope down to the far end of the corridor
+        assert_eq!(values, vec![-1, -2, -3, -4, -5, -6]);
+        assert_eq!(dijkstra.downhill((2, 1), &|_| true), Some((3, 1)));
+        assert_eq!(dijkstra.downhill((2, 1), &|position| position != (3, 1)), None);
+        assert_eq!(dijkstra.downhill((6, 1), &|_| true), None);
+    }
+}
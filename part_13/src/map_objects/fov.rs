@@ -10,12 +10,18 @@ pub fn initialize_fov(game_map: &GameMap) -> Map {
     for x in 0..game_map.dimensions.0 {
         for y in 0..game_map.dimensions.1 {
             let tile = game_map.get_tile(x as usize, y as usize);
-            fov_map.set(x, y, !tile.block_sight, !tile.block_move);
+            fov_map.set(x, y, !tile.block_sight(), !tile.block_move());
         }
     }
     fov_map
 }
 
+/// Update a single tile of a FOV map after it changed on the `GameMap`, e.g. a door was opened
+pub fn update_tile(fov_map: &mut Map, game_map: &GameMap, position: (i32, i32)) {
+    let tile = game_map.get_tile(position.0 as usize, position.1 as usize);
+    fov_map.set(position.0, position.1, !tile.block_sight(), !tile.block_move());
+}
+
 pub fn recompute_fov(ecs: &Ecs, fov_map: &mut Map, settings: &Settings) {
     let p = ecs.get_component::<Position>(ecs.player_entity_id).unwrap();
     fov_map.compute_fov(p.position.0, p.position.1,
@@ -0,0 +1,85 @@
+use std::cmp;
+
+use rand::prelude::*;
+
+use map_objects::generator::{Layout, MapGenerator, carve_room, carve_tunnel, room_area};
+use map_objects::map::GameMap;
+use map_objects::rectangle::Rect;
+use random_utils::GameRng;
+use settings::Settings;
+
+/// Rooms spread evenly over the whole map by a binary space partition.
+///
+/// The map is split in two again and again, until the parts become too small. Every part gets a
+/// room, and the rooms of both halves of each split are connected by a tunnel, so all rooms can
+/// be reached.
+pub struct BspGenerator {}
+
+impl BspGenerator {
+    /// Fill `space` with rooms and return them, ordered from the top left to the bottom right
+    /// part of the partition
+    fn partition(map: &mut GameMap, space: Rect, settings: &Settings, rng: &mut GameRng) -> Vec<Rect> {
+        let (width, height) = (space.lr.0 - space.tl.0, space.lr.1 - space.tl.1);
+        let min_size = settings.bsp_min_leaf_size();
+
+        let can_split_h = width >= 2 * min_size;
+        let can_split_v = height >= 2 * min_size;
+
+        if !can_split_h && !can_split_v {
+            return vec![Self::create_room(map, &space, settings, rng)];
+        }
+
+        // Prefer splitting the longer side, so the parts don't become long and narrow
+        let split_h = if can_split_h && can_split_v { width > height || (width == height && rng.gen()) } else { can_split_h };
+
+        let (first, second) = if split_h {
+            let split = rng.gen_range(min_size, width - min_size + 1);
+            (Rect::new(space.tl.0, space.tl.1, split, height),
+             Rect::new(space.tl.0 + split, space.tl.1, width - split, height))
+        } else {
+            let split = rng.gen_range(min_size, height - min_size + 1);
+            (Rect::new(space.tl.0, space.tl.1, width, split),
+             Rect::new(space.tl.0, space.tl.1 + split, width, height - split))
+        };
+
+        let mut rooms = Self::partition(map, first, settings, rng);
+        let second_rooms = Self::partition(map, second, settings, rng);
+
+        carve_tunnel(map, rooms[rooms.len() - 1].center(), second_rooms[0].center(), rng.gen());
+
+        rooms.extend(second_rooms);
+        rooms
+    }
+
+    /// Carve a room of random size somewhere inside of `space`
+    fn create_room(map: &mut GameMap, space: &Rect, settings: &Settings, rng: &mut GameRng) -> Rect {
+        let (width, height) = (space.lr.0 - space.tl.0, space.lr.1 - space.tl.1);
+
+        let w = rng.gen_range(cmp::min(settings.room_min_size(), width), cmp::min(settings.room_max_size(), width) + 1);
+        let h = rng.gen_range(cmp::min(settings.room_min_size(), height), cmp::min(settings.room_max_size(), height) + 1);
+
+        let x = rng.gen_range(space.tl.0, space.lr.0 - w + 1);
+        let y = rng.gen_range(space.tl.1, space.lr.1 - h + 1);
+
+        let room = Rect::new(x, y, w, h);
+        carve_room(map, &room);
+        room
+    }
+}
+
+impl MapGenerator for BspGenerator {
+    fn generate(&self, map: &mut GameMap, settings: &Settings, rng: &mut GameRng) -> Option<Layout> {
+        let space = Rect::new(0, 0, map.dimensions.0 - 1, map.dimensions.1 - 1);
+        let rooms = Self::partition(map, space, settings, rng);
+
+        if (rooms.len() as i32) < settings.min_rooms() {
+            return None;
+        }
+
+        Some(Layout {
+            entrance: rooms[0].center(),
+            exit: rooms[rooms.len() - 1].center(),
+            areas: rooms.iter().map(room_area).collect(),
+        })
+    }
+}
@@ -0,0 +1,90 @@
+use rand::prelude::*;
+
+use map_objects::generator::{Layout, MapGenerator, set_floor, regions, square_areas, farthest_position};
+use map_objects::map::GameMap;
+use random_utils::GameRng;
+use settings::Settings;
+
+/// Caves fill most of a square, so a square with less floor than this is only the edge of a cave
+/// and too small to count as a room
+const MIN_AREA_FLOOR: usize = 25;
+
+/// Natural looking caves, grown by a cellular automaton.
+///
+/// The map starts as random noise of walls and floor, which is then smoothed several times: A
+/// tile becomes a wall if most of its neighbours are walls, and floor otherwise. Only the largest
+/// cave is kept, all others are filled up, so every part of the floor can be reached.
+pub struct CaveGenerator {}
+
+impl CaveGenerator {
+    /// The number of walls around a position, counting everything outside of the map as wall
+    fn count_walls(map: &GameMap, x: i32, y: i32) -> u32 {
+        let mut walls = 0;
+        for dx in -1..2 {
+            for dy in -1..2 {
+                let (nx, ny) = (x + dx, y + dy);
+                if (dx, dy) == (0, 0) {
+                    continue;
+                }
+                if nx < 0 || ny < 0 || nx >= map.dimensions.0 || ny >= map.dimensions.1
+                    || map.is_move_blocked(nx, ny) {
+                    walls += 1;
+                }
+            }
+        }
+        walls
+    }
+
+    fn smooth(map: &mut GameMap) {
+        let (width, height) = map.dimensions;
+        let mut floor = vec![];
+
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                floor.push(((x, y), Self::count_walls(map, x, y) < 5));
+            }
+        }
+
+        floor.into_iter().for_each(|(position, is_floor)| set_floor(map, position, is_floor));
+    }
+}
+
+impl MapGenerator for CaveGenerator {
+    fn generate(&self, map: &mut GameMap, settings: &Settings, rng: &mut GameRng) -> Option<Layout> {
+        let (width, height) = map.dimensions;
+
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                if rng.gen_range(0, 100) >= settings.cave_wall_chance() {
+                    set_floor(map, (x, y), true);
+                }
+            }
+        }
+
+        for _ in 0..settings.cave_smoothing_steps() {
+            Self::smooth(map);
+        }
+
+        let mut regions = regions(map);
+        regions.sort_by_key(|region| region.len());
+        let cave = regions.pop()?;
+
+        if (cave.len() as i32) < width * height / 4 {
+            return None;
+        }
+
+        regions.iter().flat_map(|region| region.iter()).for_each(|position| {
+            set_floor(map, *position, false);
+        });
+
+        // The stairs down are as far away from the entrance as possible
+        let entrance = cave[rng.gen_range(0, cave.len())];
+        let exit = farthest_position(map, entrance)?;
+
+        Some(Layout {
+            entrance,
+            exit,
+            areas: square_areas(&cave, MIN_AREA_FLOOR),
+        })
+    }
+}
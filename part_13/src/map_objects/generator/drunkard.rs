@@ -0,0 +1,68 @@
+use rand::prelude::*;
+
+use map_objects::generator::{Layout, MapGenerator, set_floor, square_areas, farthest_position};
+use map_objects::map::GameMap;
+use random_utils::GameRng;
+use settings::Settings;
+
+/// The tunnels are narrow and cover less of a square than a cave, so less floor is enough to count
+/// as a room
+const MIN_AREA_FLOOR: usize = 15;
+
+/// Winding tunnels, dug by walkers stumbling around at random.
+///
+/// Each walker starts on a tile which was already dug out and turns every tile it steps on into
+/// floor, so all tunnels are connected. Walkers are sent out until enough of the map is floor.
+pub struct DrunkardsWalkGenerator {}
+
+impl DrunkardsWalkGenerator {
+    /// Let a walker stumble around from `start`, and return all positions it dug out
+    fn walk(map: &mut GameMap, start: (i32, i32), steps: u32, rng: &mut GameRng) -> Vec<(i32, i32)> {
+        let (width, height) = map.dimensions;
+        let mut position = start;
+        let mut dug = vec![];
+
+        for _ in 0..steps {
+            let (dx, dy) = [(0, -1), (1, 0), (0, 1), (-1, 0)][rng.gen_range(0, 4)];
+            let next = (position.0 + dx, position.1 + dy);
+
+            // The outer border of the map always stays a wall
+            if next.0 < 1 || next.1 < 1 || next.0 >= width - 1 || next.1 >= height - 1 {
+                continue;
+            }
+
+            position = next;
+            if map.is_move_blocked(position.0, position.1) {
+                set_floor(map, position, true);
+                dug.push(position);
+            }
+        }
+
+        dug
+    }
+}
+
+impl MapGenerator for DrunkardsWalkGenerator {
+    fn generate(&self, map: &mut GameMap, settings: &Settings, rng: &mut GameRng) -> Option<Layout> {
+        let (width, height) = map.dimensions;
+        let target = (width * height) as usize * settings.drunkard_floor_percent() as usize / 100;
+
+        let entrance = (width / 2, height / 2);
+        set_floor(map, entrance, true);
+        let mut floor = vec![entrance];
+
+        while floor.len() < target {
+            let start = floor[rng.gen_range(0, floor.len())];
+            let dug = Self::walk(map, start, settings.drunkard_walk_length(), rng);
+            floor.extend(dug);
+        }
+
+        let exit = farthest_position(map, entrance)?;
+
+        Some(Layout {
+            entrance,
+            exit,
+            areas: square_areas(&floor, MIN_AREA_FLOOR),
+        })
+    }
+}
@@ -0,0 +1,146 @@
+use rand::prelude::*;
+
+use map_objects::generator::{Layout, is_connected, is_walkable};
+use map_objects::map::GameMap;
+use map_objects::tile::TileKind;
+use random_utils::{by_dungeon_level, GameRng};
+use settings::Settings;
+
+/// Add doors, pools and cracked walls to a freshly generated floor.
+///
+/// Pools never cover the entrance or the exit, and a pool of lava or a chasm which would cut off
+/// a part of the floor is removed again.
+pub fn add_features(map: &mut GameMap, layout: &Layout, settings: &Settings, floor_number: u8, rng: &mut GameRng) {
+    add_doors(map, settings.door_chance(), rng);
+
+    let pools = vec![
+        (TileKind::ShallowWater, by_dungeon_level(settings.water_pools(), floor_number)),
+        (TileKind::Lava, by_dungeon_level(settings.lava_pools(), floor_number)),
+        (TileKind::Chasm, by_dungeon_level(settings.chasms(), floor_number)),
+    ];
+
+    for (kind, count) in pools {
+        for _ in 0..count {
+            let pool = add_pool(map, layout, kind, rng);
+
+            if kind.is_hazard() && !is_connected(map, layout) {
+                pool.into_iter().for_each(|position| map.set_tile_kind(position, TileKind::Floor));
+            }
+        }
+    }
+
+    add_cracked_walls(map, by_dungeon_level(settings.cracked_walls(), floor_number), rng);
+}
+
+/// Check if a wall or door position lies between two walkable tiles, either horizontally or
+/// vertically, with walls on the other two sides
+fn is_passage(map: &GameMap, x: i32, y: i32) -> bool {
+    let (width, height) = map.dimensions;
+    if x < 1 || y < 1 || x >= width - 1 || y >= height - 1 {
+        return false;
+    }
+
+    let walkable = |dx: i32, dy: i32| is_walkable(map, x + dx, y + dy);
+    let wall = |dx: i32, dy: i32| map.tile_kind(x + dx, y + dy) == TileKind::Wall;
+
+    (walkable(-1, 0) && walkable(1, 0) && wall(0, -1) && wall(0, 1))
+        || (walkable(0, -1) && walkable(0, 1) && wall(-1, 0) && wall(1, 0))
+}
+
+/// Put doors into the openings of rooms, i.e. where a narrow passage opens up into a wider area
+fn add_doors(map: &mut GameMap, chance: u32, rng: &mut GameRng) {
+    let (width, height) = map.dimensions;
+
+    // The number of walkable tiles around a position, including diagonals
+    let open_neighbours = |map: &GameMap, x: i32, y: i32| {
+        let mut count = 0;
+        for dx in -1..2 {
+            for dy in -1..2 {
+                if (dx, dy) != (0, 0) && is_walkable(map, x + dx, y + dy) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    };
+
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            if map.tile_kind(x, y) != TileKind::Floor || !is_passage(map, x, y) {
+                continue;
+            }
+
+            let opens_into_room = [(-1, 0), (1, 0), (0, -1), (0, 1)].iter()
+                .any(|(dx, dy)| is_walkable(map, x + dx, y + dy) && open_neighbours(map, x + dx, y + dy) >= 5);
+            let next_to_door = [(-1, 0), (1, 0), (0, -1), (0, 1)].iter()
+                .any(|(dx, dy)| map.tile_kind(x + dx, y + dy) == TileKind::ClosedDoor);
+
+            if opens_into_room && !next_to_door && rng.gen_range(0, 100) < chance {
+                map.set_tile_kind((x, y), TileKind::ClosedDoor);
+            }
+        }
+    }
+}
+
+/// Flood a roughly round part of an area with `kind`, and return the flooded positions
+fn add_pool(map: &mut GameMap, layout: &Layout, kind: TileKind, rng: &mut GameRng) -> Vec<(i32, i32)> {
+    if layout.areas.is_empty() {
+        return vec![];
+    }
+
+    let area = &layout.areas[rng.gen_range(0, layout.areas.len())];
+    if area.is_empty() {
+        return vec![];
+    }
+
+    let (width, height) = map.dimensions;
+    let center = area[rng.gen_range(0, area.len())];
+    let radius = rng.gen_range(1, 4);
+    let mut pool = vec![];
+
+    for dx in -radius..radius + 1 {
+        for dy in -radius..radius + 1 {
+            let position = (center.0 + dx, center.1 + dy);
+
+            // The outer ring is left out now and then, so the pool doesn't look like a diamond
+            let distance = dx.abs() + dy.abs();
+            if distance > radius || (distance == radius && rng.gen()) {
+                continue;
+            }
+            if position.0 < 1 || position.1 < 1 || position.0 >= width - 1 || position.1 >= height - 1 {
+                continue;
+            }
+            if position == layout.entrance || position == layout.exit
+                || map.tile_kind(position.0, position.1) != TileKind::Floor {
+                continue;
+            }
+
+            map.set_tile_kind(position, kind);
+            pool.push(position);
+        }
+    }
+
+    pool
+}
+
+/// Turn walls which separate two walkable tiles into cracked walls, which can be broken through
+fn add_cracked_walls(map: &mut GameMap, count: i32, rng: &mut GameRng) {
+    let (width, height) = map.dimensions;
+    let mut candidates = vec![];
+
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            if map.tile_kind(x, y) == TileKind::Wall && is_passage(map, x, y) {
+                candidates.push((x, y));
+            }
+        }
+    }
+
+    for _ in 0..count {
+        if candidates.is_empty() {
+            return;
+        }
+        let position = candidates.remove(rng.gen_range(0, candidates.len()));
+        map.set_tile_kind(position, TileKind::CrackedWall);
+    }
+}
@@ -0,0 +1,287 @@
+use std::cmp;
+use std::collections::{BTreeMap, VecDeque};
+
+use map_objects::map::GameMap;
+use map_objects::rectangle::Rect;
+use map_objects::tile::TileKind;
+use random_utils::GameRng;
+use settings::Settings;
+
+/// Size of the squares which floor without rooms is divided into, like caves or tunnels
+const AREA_SIZE: i32 = 10;
+
+pub mod rooms;
+pub mod caves;
+pub mod bsp;
+pub mod drunkard;
+pub mod features;
+
+pub use self::rooms::RoomsGenerator;
+pub use self::caves::CaveGenerator;
+pub use self::bsp::BspGenerator;
+pub use self::drunkard::DrunkardsWalkGenerator;
+
+/// The result of a `MapGenerator`: where the floor can be entered and left, and where monsters
+/// and items can be placed.
+pub struct Layout {
+    /// The position the player arrives at. The stairs up are placed here.
+    pub entrance: (i32, i32),
+    /// The position of the stairs down
+    pub exit: (i32, i32),
+    /// Groups of walkable positions. Each group is filled with monsters and items like a room.
+    pub areas: Vec<Vec<(i32, i32)>>,
+}
+
+/// An algorithm which carves a floor of the dungeon out of a map full of walls
+pub trait MapGenerator {
+    /// Carve the floor into `map`. Returns `None` if the generation failed and has to be retried.
+    fn generate(&self, map: &mut GameMap, settings: &Settings, rng: &mut GameRng) -> Option<Layout>;
+}
+
+/// All available `MapGenerator`s, used to choose one in the `Settings`
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MapGeneratorKind {
+    Rooms,
+    Caves,
+    BinarySpacePartition,
+    DrunkardsWalk,
+}
+
+impl MapGeneratorKind {
+    pub fn create(&self) -> Box<MapGenerator> {
+        match *self {
+            MapGeneratorKind::Rooms => Box::new(RoomsGenerator {}),
+            MapGeneratorKind::Caves => Box::new(CaveGenerator {}),
+            MapGeneratorKind::BinarySpacePartition => Box::new(BspGenerator {}),
+            MapGeneratorKind::DrunkardsWalk => Box::new(DrunkardsWalkGenerator {}),
+        }
+    }
+}
+
+/// Turn a tile into floor, or back into a wall
+pub fn set_floor(map: &mut GameMap, position: (i32, i32), floor: bool) {
+    map.set_tile_kind(position, if floor { TileKind::Floor } else { TileKind::Wall });
+}
+
+/// Check if the player can walk over a tile without getting hurt. Closed doors count as
+/// walkable, because they can be opened.
+pub fn is_walkable(map: &GameMap, x: i32, y: i32) -> bool {
+    let kind = map.tile_kind(x, y);
+    (!kind.block_move() || kind == TileKind::ClosedDoor) && !kind.is_hazard()
+}
+
+/// Turn the inside of a room into floor, keeping its outline as walls
+pub fn carve_room(map: &mut GameMap, room: &Rect) {
+    for x in room.tl.0 + 1..room.lr.0 {
+        for y in room.tl.1 + 1..room.lr.1 {
+            set_floor(map, (x, y), true);
+        }
+    }
+}
+
+/// Connect two positions by an L-shaped tunnel, either going horizontally or vertically first
+pub fn carve_tunnel(map: &mut GameMap, from: (i32, i32), to: (i32, i32), horizontal_first: bool) {
+    let corner = if horizontal_first { (to.0, from.1) } else { (from.0, to.1) };
+
+    for &(start, end) in [(from, corner), (corner, to)].iter() {
+        for x in cmp::min(start.0, end.0)..cmp::max(start.0, end.0) + 1 {
+            for y in cmp::min(start.1, end.1)..cmp::max(start.1, end.1) + 1 {
+                set_floor(map, (x, y), true);
+            }
+        }
+    }
+}
+
+/// The positions inside of a room where monsters and items can be placed
+pub fn room_area(room: &Rect) -> Vec<(i32, i32)> {
+    let mut area = vec![];
+    for x in room.tl.0 + 1..room.lr.0 - 1 {
+        for y in room.tl.1 + 1..room.lr.1 - 1 {
+            area.push((x, y));
+        }
+    }
+    area
+}
+
+/// Divide floor without any rooms into squares of `AREA_SIZE`, which are filled with monsters and
+/// items like rooms. Squares with less than `min_floor` positions are left empty.
+pub fn square_areas(floor: &[(i32, i32)], min_floor: usize) -> Vec<Vec<(i32, i32)>> {
+    let mut areas: BTreeMap<(i32, i32), Vec<(i32, i32)>> = BTreeMap::new();
+    floor.iter().for_each(|position| {
+        areas.entry((position.0 / AREA_SIZE, position.1 / AREA_SIZE)).or_insert_with(Vec::new).push(*position);
+    });
+
+    areas.into_iter().map(|(_, area)| area).filter(|area| area.len() >= min_floor).collect()
+}
+
+/// The walkable position which is farthest away from `start`
+pub fn farthest_position(map: &GameMap, start: (i32, i32)) -> Option<(i32, i32)> {
+    let width = map.dimensions.0;
+
+    distances(map, start).iter().enumerate()
+        .filter_map(|(index, distance)| distance.map(|d| (index as i32, d)))
+        .max_by_key(|(_, distance)| *distance)
+        .map(|(index, _)| (index % width, index / width))
+}
+
+/// Check if the player can walk from the entrance of a `Layout` to its exit and into every area.
+/// Hazards in the areas are left out, they only must not cut off the rest.
+pub fn is_connected(map: &GameMap, layout: &Layout) -> bool {
+    let width = map.dimensions.0;
+    let inside = |(x, y): (i32, i32)| x >= 0 && y >= 0 && x < map.dimensions.0 && y < map.dimensions.1;
+
+    if !inside(layout.entrance) || !is_walkable(map, layout.entrance.0, layout.entrance.1) {
+        return false;
+    }
+
+    let distances = distances(map, layout.entrance);
+    let reachable = |position: (i32, i32)| {
+        inside(position) && distances[(position.1 * width + position.0) as usize].is_some()
+    };
+
+    reachable(layout.exit) && layout.areas.iter().all(|area| {
+        area.iter().all(|p| map.tile_kind(p.0, p.1).is_hazard() || reachable(*p))
+    })
+}
+
+/// The shortest way from `start` through walls to the nearest walkable tile, without entering the
+/// positions for which `excluded` is true or the outer border of the map. The path doesn't
+/// include `start`, and ends next to the walkable tile.
+pub fn path_to_floor(map: &GameMap, start: (i32, i32), excluded: &Fn((i32, i32)) -> bool) -> Option<Vec<(i32, i32)>> {
+    let (width, height) = map.dimensions;
+    let mut previous: Vec<Option<(i32, i32)>> = vec![None; (width * height) as usize];
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+
+    while let Some(position) = queue.pop_front() {
+        for neighbour in neighbours(map, position) {
+            let (x, y) = neighbour;
+            let index = (y * width + x) as usize;
+            if x < 1 || y < 1 || x >= width - 1 || y >= height - 1 || excluded(neighbour)
+                || neighbour == start || previous[index].is_some() {
+                continue;
+            }
+
+            if is_walkable(map, x, y) {
+                let mut path = vec![];
+                let mut step = position;
+                while step != start {
+                    path.push(step);
+                    step = previous[(step.1 * width + step.0) as usize].unwrap();
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            previous[index] = Some(position);
+            queue.push_back(neighbour);
+        }
+    }
+
+    None
+}
+
+/// The positions next to `position` in all four directions which lie inside of the map
+fn neighbours(map: &GameMap, position: (i32, i32)) -> Vec<(i32, i32)> {
+    [(0, -1), (1, 0), (0, 1), (-1, 0)].iter()
+        .map(|(dx, dy)| (position.0 + dx, position.1 + dy))
+        .filter(|(x, y)| *x >= 0 && *y >= 0 && *x < map.dimensions.0 && *y < map.dimensions.1)
+        .collect()
+}
+
+/// The walking distance from `start` to every tile of the map, or `None` for unreachable tiles.
+/// The result is indexed by `y * width + x`.
+pub fn distances(map: &GameMap, start: (i32, i32)) -> Vec<Option<u32>> {
+    let width = map.dimensions.0;
+    let mut distances = vec![None; (map.dimensions.0 * map.dimensions.1) as usize];
+    let mut queue = VecDeque::new();
+
+    distances[(start.1 * width + start.0) as usize] = Some(0);
+    queue.push_back(start);
+
+    while let Some(position) = queue.pop_front() {
+        let distance = distances[(position.1 * width + position.0) as usize].unwrap();
+
+        for neighbour in neighbours(map, position) {
+            let index = (neighbour.1 * width + neighbour.0) as usize;
+            if distances[index].is_none() && is_walkable(map, neighbour.0, neighbour.1) {
+                distances[index] = Some(distance + 1);
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    distances
+}
+
+/// All groups of walkable tiles which are connected to each other, ordered by their top left tile
+pub fn regions(map: &GameMap) -> Vec<Vec<(i32, i32)>> {
+    let (width, height) = map.dimensions;
+    let mut visited = vec![false; (width * height) as usize];
+    let mut regions = vec![];
+
+    for y in 0..height {
+        for x in 0..width {
+            if visited[(y * width + x) as usize] || !is_walkable(map, x, y) {
+                continue;
+            }
+
+            let region: Vec<(i32, i32)> = distances(map, (x, y)).iter().enumerate()
+                .filter(|(_, distance)| distance.is_some())
+                .map(|(index, _)| (index as i32 % width, index as i32 / width))
+                .collect();
+
+            region.iter().for_each(|(x, y)| visited[(y * width + x) as usize] = true);
+            regions.push(region);
+        }
+    }
+
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecs::component::StairsDirection;
+    use game::simulation::{Simulation, SEED};
+
+    #[test]
+    fn cave_floors_are_connected() {
+        let settings = Settings::new();
+        let mut sim = Simulation::new(&settings, SEED);
+
+        while sim.floor_number() < 3 {
+            sim.descend();
+        }
+
+        let game = sim.game();
+        let map = game.map.borrow();
+        assert_eq!(regions(&map).len(), 1);
+
+        let stairs = sim.stairs_position(StairsDirection::Down);
+        let width = map.dimensions.0;
+        let distances = distances(&map, sim.player_position());
+        assert!(distances[(stairs.1 * width + stairs.0) as usize].is_some());
+    }
+
+    #[test]
+    fn stairs_are_reachable_with_every_generator() {
+        for seed in 0..5 {
+            let settings = Settings::new();
+            let mut sim = Simulation::new(&settings, seed);
+
+            while sim.floor_number() < 5 {
+                {
+                    let game = sim.game();
+                    let map = game.map.borrow();
+                    let stairs = sim.stairs_position(StairsDirection::Down);
+                    let distances = distances(&map, sim.player_position());
+                    assert!(distances[(stairs.1 * map.dimensions.0 + stairs.0) as usize].is_some());
+                }
+
+                sim.remove_monsters();
+                sim.descend();
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use rand::prelude::*;
+
+use map_objects::generator::{Layout, MapGenerator, carve_room, carve_tunnel, room_area};
+use map_objects::map::GameMap;
+use map_objects::rectangle::Rect;
+use random_utils::GameRng;
+use settings::Settings;
+
+/// Rectangular rooms, each connected to the previous one by an L-shaped tunnel
+pub struct RoomsGenerator {}
+
+impl MapGenerator for RoomsGenerator {
+    fn generate(&self, map: &mut GameMap, settings: &Settings, rng: &mut GameRng) -> Option<Layout> {
+        let mut rooms: Vec<Rect> = Vec::new();
+
+        let mut failed_attempts = 0;
+        let mut failed_attempts_room = 0;
+
+        'roomloop: loop {
+
+            if (rooms.len() as i32) == settings.max_rooms() {
+                break 'roomloop
+            }
+
+            if (rooms.len() as i32) >= settings.min_rooms() && failed_attempts_room == settings.max_attempts_room() {
+                break 'roomloop
+            }
+
+            if (rooms.len() as i32) < settings.min_rooms() && failed_attempts == settings.max_attempts_min_rooms() {
+                return None
+            }
+
+
+            let w = rng.gen_range(settings.room_min_size(), settings.room_max_size());
+            let h = rng.gen_range(settings.room_min_size(), settings.room_max_size());
+
+            let x = rng.gen_range(0, map.dimensions.0 - w - 1);
+            let y = rng.gen_range(0, map.dimensions.1 - h - 1);
+
+            let new_room = Rect::new(x, y, w, h);
+
+            for other_room in &rooms {
+                if new_room.intersect(&other_room) {
+                    failed_attempts+=1;
+                    failed_attempts_room+=1;
+                    continue 'roomloop;
+                }
+            }
+
+            failed_attempts_room = 0;
+
+            carve_room(map, &new_room);
+
+            if let Some(previous_room) = rooms.last() {
+                carve_tunnel(map, previous_room.center(), new_room.center(), rng.gen());
+            }
+            rooms.push(new_room);
+        }
+
+        Some(Layout {
+            entrance: rooms[0].center(),
+            exit: rooms[rooms.len() - 1].center(),
+            areas: rooms.iter().map(room_area).collect(),
+        })
+    }
+}
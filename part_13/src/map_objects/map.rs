@@ -1,32 +1,36 @@
-use std::cmp;
-
 use rand::prelude::*;
 
-use tcod::Console;
 use tcod::BackgroundFlag;
 use tcod::Map;
 use tcod::colors;
 
 use json::JsonValue;
 
-use map_objects::tile::Tile;
-use map_objects::rectangle::Rect;
+use map_objects::tile::{Tile, TileKind};
 
-use map_objects::color::Color;
+use map_objects::generator;
+use map_objects::rectangle::Rect;
+use map_objects::vault::{Vault, VaultContent};
 
 use ecs::Ecs;
 use ecs::creature::CreatureTemplate;
 use ecs::component::Position;
 use ecs::item::ItemTemplate;
 use settings::Settings;
-use savegame::{Serialize, Deserialize};
-use ecs::component::Stairs;
+use savegame::{Serialize, Deserialize, LoadError, ReadJson};
+use ecs::component::{Stairs, StairsDirection};
 use ecs::component::Render;
 use render::RenderOrder;
 use ecs::component::Name;
 use random_utils::by_dungeon_level;
+use random_utils::GameRng;
+use terminal::Terminal;
+use camera::Camera;
 use std::borrow::Cow;
 
+/// Number of random positions which are tried for each vault before it is left out
+const VAULT_ATTEMPTS: i32 = 50;
+
 pub struct GameMap {
     pub dimensions: (i32, i32),
     tiles: Vec<Tile>,
@@ -49,95 +53,150 @@ impl GameMap {
     }
 
     fn initialize_tiles(width: usize, height: usize) -> Vec<Tile> {
-        vec![Tile::new(true, true); height * width]
+        vec![Tile::new(TileKind::Wall); height * width]
     }
 
     fn reset_tiles(&mut self) {
         let size = self.tiles.len();
-        self.tiles = vec![Tile::new(true, true); size]
+        self.tiles = vec![Tile::new(TileKind::Wall); size]
     }
 
     pub fn is_move_blocked(&self, x: i32, y: i32) -> bool {
-        if self.get_tile(x as usize, y as usize).block_move {
+        if self.get_tile(x as usize, y as usize).block_move() {
             return true;
         }
         false
     }
 
-    /// Try to create a new dungeon map and place entities.
+    pub fn tile_kind(&self, x: i32, y: i32) -> TileKind {
+        self.get_tile(x as usize, y as usize).kind
+    }
+
+    pub fn set_tile_kind(&mut self, position: (i32, i32), kind: TileKind) {
+        self.get_tile_mut(position.0 as usize, position.1 as usize).kind = kind;
+    }
+
+    /// Try to create a new dungeon map and place entities, using the `MapGenerator` the
+    /// `Settings` choose for the floor.
     /// Returns true if successful, false if failed
     pub fn make_map(&mut self,
-                    ecs: &mut Ecs, settings: &Settings, floor_number: u8) -> bool{
+                    ecs: &mut Ecs, settings: &Settings, vaults: &[Vault], floor_number: u8,
+                    rng: &mut GameRng) -> bool{
 
         self.reset_tiles();
-        let mut rooms: Vec<Rect> = Vec::new();
-        let mut rng = thread_rng();
 
-        let mut failed_attempts = 0;
-        let mut failed_attempts_room = 0;
+        let generator = settings.map_generator(floor_number).create();
+        let layout = match generator.generate(self, settings, rng) {
+            Some(layout) => layout,
+            None => return false
+        };
 
-        'roomloop: loop {
-
-            if (rooms.len() as i32) == settings.max_rooms() {
-                break 'roomloop
-            }
+        if !generator::is_connected(self, &layout) {
+            return false;
+        }
 
-            if (rooms.len() as i32) >= settings.min_rooms() && failed_attempts_room == settings.max_attempts_room() {
-                break 'roomloop
-            }
+        generator::features::add_features(self, &layout, settings, floor_number, rng);
 
-            if (rooms.len() as i32) < settings.min_rooms() && failed_attempts == settings.max_attempts_min_rooms() {
-                return false
-            }
+        self.create_or_update_player(ecs, layout.entrance);
 
+        for area in layout.areas.iter() {
+            self.place_entities(area, ecs,
+                                settings.max_monsters_per_room(),
+                                settings.max_items_per_room(), floor_number, rng);
+        }
 
-            let w = rng.gen_range(settings.room_min_size(), settings.room_max_size());
-            let h = rng.gen_range(settings.room_min_size(), settings.room_max_size());
+        self.add_stair(ecs, layout.exit, StairsDirection::Down, floor_number.saturating_add(1));
+        if floor_number > 1 {
+            // The player arrives at the entrance, so the way back up is right there
+            self.add_stair(ecs, layout.entrance, StairsDirection::Up, floor_number - 1);
+        }
 
-            let x = rng.gen_range(0, self.dimensions.0 - w - 1);
-            let y = rng.gen_range(0, self.dimensions.1 - h - 1);
+        self.place_vaults(ecs, settings, vaults, floor_number, rng);
+        true
+    }
 
-            let new_room = Rect::new(x, y, w, h);
+    /// Stamp randomly chosen vaults into solid rock, and dig a tunnel from their entrance to the
+    /// nearest floor. Returns the number of vaults which were placed.
+    fn place_vaults(&mut self, ecs: &mut Ecs, settings: &Settings, vaults: &[Vault], floor_number: u8,
+                    rng: &mut GameRng) -> usize {
+        let vaults: Vec<&Vault> = vaults.iter()
+            .filter(|vault| vault.fits_depth(floor_number))
+            .collect();
+        let total_rarity: i32 = vaults.iter().map(|vault| vault.rarity).sum();
+        let mut footprints = vec![];
+
+        if vaults.is_empty() {
+            return 0;
+        }
 
-            for other_room in &rooms {
-                if new_room.intersect(&other_room) {
-                    failed_attempts+=1;
-                    failed_attempts_room+=1;
-                    continue 'roomloop;
+        for _ in 0..by_dungeon_level(settings.vaults_per_floor(), floor_number) {
+            let mut roll = rng.gen_range(0, total_rarity);
+            let vault = vaults.iter().find(|vault| {
+                roll -= vault.rarity;
+                roll < 0
+            }).unwrap();
+
+            for _ in 0..VAULT_ATTEMPTS {
+                if let Some(footprint) = self.try_place_vault(vault, &footprints, ecs, rng) {
+                    footprints.push(footprint);
+                    break;
                 }
             }
+        }
+        footprints.len()
+    }
 
-            failed_attempts_room = 0;
-
-            self.create_room(&new_room);
-            let center = new_room.center();
+    /// Place a vault on a random position, if it only covers walls there and a tunnel can be dug
+    /// from it which doesn't break into other vaults or end at a hazard. Returns the area which
+    /// the vault covers.
+    fn try_place_vault(&mut self, vault: &Vault, footprints: &[Rect], ecs: &mut Ecs, rng: &mut GameRng) -> Option<Rect> {
+        let (width, height) = self.dimensions;
+        if vault.width + 2 >= width || vault.height + 2 >= height {
+            return None;
+        }
 
-            if rooms.len() == 0 {
-                self.create_or_update_player(ecs, center);
-            } else {
-                let prev_center = rooms[rooms.len() - 1].center();
+        let x = rng.gen_range(1, width - vault.width);
+        let y = rng.gen_range(1, height - vault.height);
 
-                if rng.gen() {
-                    self.create_h_tunnel(prev_center.0, center.0, prev_center.1);
-                    self.create_v_tunnel(prev_center.1, center.1, center.0);
-                } else {
-                    self.create_v_tunnel(prev_center.1, center.1, prev_center.0);
-                    self.create_h_tunnel(prev_center.0, center.0, center.1);
+        // Keep a wall around the vault, so it isn't opened up by a room next to it
+        for vx in x - 1..x + vault.width + 1 {
+            for vy in y - 1..y + vault.height + 1 {
+                if !self.is_move_blocked(vx, vy) {
+                    return None;
                 }
             }
-            self.place_entities(&new_room, ecs,
-                                settings.max_monsters_per_room(),
-                                settings.max_items_per_room(), floor_number);
-            rooms.push(new_room);
         }
 
-        self.add_stair(ecs, &rooms[rooms.len()-1]);
-        true
+        let footprint = Rect::new(x, y, vault.width, vault.height);
+        let inside = |r: &Rect, (px, py): (i32, i32)| px >= r.tl.0 && py >= r.tl.1 && px < r.lr.0 && py < r.lr.1;
+        let excluded = |position: (i32, i32)| {
+            inside(&footprint, position)
+                || footprints.iter().any(|other| inside(other, position))
+                || self.tile_kind(position.0, position.1).is_hazard()
+        };
+        let entrance = (x + vault.entrance.0, y + vault.entrance.1);
+        let tunnel = generator::path_to_floor(self, entrance, &excluded)?;
+
+        for vx in 0..vault.width {
+            for vy in 0..vault.height {
+                self.set_tile_kind((x + vx, y + vy), vault.tile(vx, vy));
+            }
+        }
+        tunnel.into_iter().for_each(|position| generator::set_floor(self, position, true));
+
+        for ((vx, vy), content) in vault.contents() {
+            let position = (x + vx, y + vy);
+            match content {
+                VaultContent::Creature(template) => template.create_on_position(ecs, &self, position),
+                VaultContent::Item(template) => template.create_on_position(ecs, position),
+            };
+        }
+        Some(footprint)
     }
 
     fn create_or_update_player(&self, ecs: &mut Ecs, position: (i32, i32)) {
         let id = ecs.player_entity_id;
-        if ecs.player_entity_id > 0 {
+        if ecs.is_alive(id) {
             let p = ecs.get_component_mut::<Position>(id).unwrap();
             p.move_absolute(position);
         } else {
@@ -145,91 +204,79 @@ impl GameMap {
         }
     }
 
-    fn add_stair(&mut self, ecs: &mut Ecs, room: &Rect) {
+    fn add_stair(&mut self, ecs: &mut Ecs, position: (i32, i32), direction: StairsDirection, destination: u8) {
+        let (glyph, name) = match direction {
+            StairsDirection::Down => ('>', "Stairs down"),
+            StairsDirection::Up => ('<', "Stairs up"),
+        };
+
         let id = ecs.create_entity();
-        ecs.register_component(id, Stairs {});
+        ecs.register_component(id, Stairs::new(direction, destination));
         ecs.register_component(id, Position {
             entity_id: id,
-            position: room.center(),
+            position,
             is_blocking: false,
         });
-        ecs.register_component(id, Render::new(id, '>',
+        ecs.register_component(id, Render::new(id, glyph,
                                                colors::WHITE, RenderOrder::Stair));
         ecs.register_component(id, Name {
-            name: String::from("Stairs"),
+            name: String::from(name),
         });
     }
 
-    fn create_room(&mut self, room: &Rect) {
-        for x in room.tl.0 + 1..room.lr.0 {
-            for y in room.tl.1 + 1..room.lr.1 {
-                self.get_tile_mut(x as usize, y as usize).block_move = false;
-                self.get_tile_mut(x as usize, y as usize).block_sight = false;
-            }
-        }
-    }
-
-    fn place_entities(&mut self, room: &Rect, ecs: &mut Ecs,
+    /// Place random monsters and items on random positions of an area
+    fn place_entities(&mut self, area: &[(i32, i32)], ecs: &mut Ecs,
                         max_monsters_per_room: Cow<Vec<(i32, i32)>>, max_items_per_room: Cow<Vec<(i32, i32)>>,
-                      floor_number: u8 ) {
-        let mut rng = thread_rng();
+                      floor_number: u8, rng: &mut GameRng) {
+        if area.is_empty() {
+            return;
+        }
 
         let monster_count = rng.gen_range(0, by_dungeon_level(max_monsters_per_room, floor_number));
         let item_count = rng.gen_range(0, by_dungeon_level(max_items_per_room, floor_number));
 
         for _ in 0..monster_count {
-            let x = rng.gen_range(room.tl.0 + 1, room.lr.0 - 1);
-            let y = rng.gen_range(room.tl.1 + 1, room.lr.1 - 1);
+            let (x, y) = area[rng.gen_range(0, area.len())];
 
-            if !ecs.get_all::<Position>().iter().any(|(_, p)| p.position.0 == x && p.position.1 == y) {
-                CreatureTemplate::create_random(ecs, &self, (x, y), floor_number);
+            if self.tile_kind(x, y) == TileKind::Floor
+                && !ecs.query::<&Position>().any(|(_, p)| p.position.0 == x && p.position.1 == y) {
+                CreatureTemplate::create_random(ecs, &self, (x, y), floor_number, rng);
             }
         }
 
         for _ in 0..item_count {
-            let x = rng.gen_range(room.tl.0 + 1, room.lr.0 - 1);
-            let y = rng.gen_range(room.tl.1 + 1, room.lr.1 - 1);
+            let (x, y) = area[rng.gen_range(0, area.len())];
 
-            if !ecs.get_all::<Position>().iter().any(|(_, p)| p.position.0 == x && p.position.1 == y) {
-                ItemTemplate::create_random(ecs, (x,y), floor_number);
+            if self.tile_kind(x, y) == TileKind::Floor
+                && !ecs.query::<&Position>().any(|(_, p)| p.position.0 == x && p.position.1 == y) {
+                ItemTemplate::create_random(ecs, (x,y), floor_number, rng);
             }
         }
     }
 
-    fn create_h_tunnel(&mut self, x_start: i32, x_end: i32, y: i32) {
-        for x in cmp::min(x_start, x_end)..cmp::max(x_start, x_end) + 1 {
-            self.get_tile_mut(x as usize, y as usize).block_move = false;
-            self.get_tile_mut(x as usize, y as usize).block_sight = false;
-        }
-    }
-
-    fn create_v_tunnel(&mut self, y_start: i32, y_end: i32, x: i32) {
-        for y in cmp::min(y_start, y_end)..cmp::max(y_start, y_end) + 1 {
-            self.get_tile_mut(x as usize, y as usize).block_move = false;
-            self.get_tile_mut(x as usize, y as usize).block_sight = false;
-        }
-    }
-
-    pub fn draw(&mut self, console: &mut Console, fov_map: &Map) {
+    /// Draw the part of the map inside the viewport of the camera. Every tile in view is marked
+    /// as explored, even if it lies outside of the viewport.
+    pub fn draw(&mut self, terminal: &mut Terminal, fov_map: &Map, camera: &Camera) {
         for x in 0..self.dimensions.0 {
             for y in 0..self.dimensions.1 {
                 let tile = self.get_tile_mut(x as usize, y as usize);
-
-                let wall = tile.block_move;
                 let visible = fov_map.is_in_fov(x, y);
 
                 if visible {
-                    if wall {
-                        console.set_char_background(x, y, Color::LightWall.value(), BackgroundFlag::Set)
-                    } else {
-                        console.set_char_background(x, y, Color::LightFloor.value(), BackgroundFlag::Set)
-                    }
                     tile.explored = true;
-                } else if tile.explored {
-                    if wall {
-                        console.set_char_background(x, y, Color::DarkWall.value(), BackgroundFlag::Set)
-                    } else {
-                        console.set_char_background(x, y, Color::DarkFloor.value(), BackgroundFlag::Set)
+                }
+
+                let (screen_x, screen_y) = match camera.to_screen((x, y)) {
+                    Some(screen) => screen,
+                    None => continue
+                };
+
+                if tile.explored {
+                    terminal.set_char_background(screen_x, screen_y, tile.kind.background(visible), BackgroundFlag::Set);
+
+                    if let Some((glyph, color)) = tile.kind.glyph() {
+                        terminal.set_default_foreground(color);
+                        terminal.put_char(screen_x, screen_y, glyph, BackgroundFlag::None);
                     }
                 }
             }
@@ -240,32 +287,151 @@ impl GameMap {
 impl Serialize for GameMap {
     fn serialize(&self) -> JsonValue {
 
+        // Each row is stored as a string of tile codes, and the explored flags as a table of bools
         let mut tiles = JsonValue::new_array();
+        let mut explored = JsonValue::new_array();
 
-        for tile in self.tiles.iter() {
-            tiles.push(tile.serialize());
+        for row in self.tiles.chunks(self.dimensions.0 as usize) {
+            tiles.push(row.iter().map(|tile| tile.kind.code()).collect::<String>());
+            explored.push(row.iter().map(|tile| JsonValue::from(tile.explored)).collect::<Vec<JsonValue>>());
         }
 
         object!(
             "width" => self.dimensions.0,
             "height" => self.dimensions.1,
-            "tiles" => tiles
+            "tiles" => tiles,
+            "explored" => explored
         )
     }
 }
 
 impl Deserialize for GameMap {
-    fn deserialize(json: &JsonValue) -> Self {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+
+        let dimensions = (json["width"].read_i32()?, json["height"].read_i32()?);
+        if dimensions.0 <= 0 {
+            return Err(LoadError::invalid("a positive width", &json["width"]));
+        }
+        if dimensions.1 <= 0 {
+            return Err(LoadError::invalid("a positive height", &json["height"]));
+        }
+
+        let (rows, explored) = (&json["tiles"], &json["explored"]);
+        if rows.len() != dimensions.1 as usize {
+            return Err(LoadError::invalid("a row of tiles for every line of the map", rows));
+        }
+        if explored.len() != dimensions.1 as usize {
+            return Err(LoadError::invalid("a row of explored flags for every line of the map", explored));
+        }
 
         let mut tiles = vec!();
 
-        for t in json["tiles"].members() {
-            tiles.push(Tile::deserialize(t));
+        for (row, explored) in rows.members().zip(explored.members()) {
+            let codes = row.read_str()?;
+            if codes.chars().count() != dimensions.0 as usize {
+                return Err(LoadError::invalid("a row of tiles matching the width of the map", row));
+            }
+            if explored.len() != dimensions.0 as usize {
+                return Err(LoadError::invalid("a row of explored flags matching the width of the map", explored));
+            }
+
+            for (x, code) in codes.chars().enumerate() {
+                let kind = TileKind::from_code(code).ok_or_else(|| LoadError::invalid("a tile", row))?;
+                tiles.push(Tile {
+                    kind,
+                    explored: explored[x].read_bool()?,
+                });
+            }
         }
 
-        GameMap {
+        Ok(GameMap {
             tiles,
-            dimensions: (json["width"].as_i32().unwrap(), json["height"].as_i32().unwrap()),
+            dimensions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vaults_are_connected_to_the_floor() {
+        let settings = Settings::new();
+        let vaults = Vault::load_all().unwrap();
+        let floor_number = 2;
+        let mut placed = 0;
+
+        for seed in 0..5 {
+            let mut rng = GameRng::from_seed(seed);
+            let mut ecs = Ecs::initialize();
+            let mut map = GameMap::new(settings.map_width(), settings.map_height());
+            while !map.make_map(&mut ecs, &settings, &[], floor_number, &mut rng) {
+                ecs = Ecs::initialize();
+            }
+
+            placed += map.place_vaults(&mut ecs, &settings, &vaults, floor_number, &mut rng);
+            assert_eq!(generator::regions(&map).len(), 1);
         }
+
+        assert!(placed > 0);
+    }
+
+    #[test]
+    fn vaults_which_can_only_be_reached_through_hazards_are_left_out() {
+        let settings = Settings::new();
+        let vaults = Vault::load_all().unwrap();
+        let (width, height) = (settings.map_width(), settings.map_height());
+
+        // The only floor is behind a wall of lava, too close to the border to fit a vault
+        let mut rng = GameRng::from_seed(0);
+        let mut ecs = Ecs::initialize();
+        let mut map = GameMap::new(width, height);
+        (1..height - 1).for_each(|y| map.set_tile_kind((width - 3, y), TileKind::Lava));
+        map.set_tile_kind((width - 2, height / 2), TileKind::Floor);
+
+        assert_eq!(map.place_vaults(&mut ecs, &settings, &vaults, 2, &mut rng), 0);
+        assert!((1..height - 1).all(|y| map.tile_kind(width - 3, y) == TileKind::Lava));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn nothing_is_placed_in_an_empty_area() {
+        let settings = Settings::new();
+        let mut rng = GameRng::from_seed(0);
+        let mut ecs = Ecs::initialize();
+        let mut map = GameMap::new(settings.map_width(), settings.map_height());
+
+        map.place_entities(&[], &mut ecs, Cow::Owned(vec![(5, 1)]), Cow::Owned(vec![(5, 1)]), 1, &mut rng);
+        assert_eq!(ecs.query::<&Position>().count(), 0);
+    }
+
+    #[test]
+    fn maps_are_saved_and_loaded() {
+        let mut map = GameMap::new(3, 2);
+        map.get_tile_mut(1, 0).explored = true;
+
+        let loaded = GameMap::deserialize(&map.serialize()).unwrap();
+        assert_eq!(loaded.dimensions, (3, 2));
+        assert!(loaded.get_tile(1, 0).explored);
+        assert!(!loaded.get_tile(2, 1).explored);
+    }
+
+    #[test]
+    fn maps_which_dont_match_their_size_are_rejected() {
+        let valid = GameMap::new(3, 2).serialize();
+        let is_invalid = |change: &Fn(&mut JsonValue)| {
+            let mut json = valid.clone();
+            change(&mut json);
+            match GameMap::deserialize(&json) { Err(LoadError::InvalidValue(_)) => true, _ => false }
+        };
+
+        assert!(is_invalid(&|json| json["width"] = 0.into()));
+        assert!(is_invalid(&|json| { json["width"] = (-3).into(); json["height"] = (-2).into() }));
+        assert!(is_invalid(&|json| json["height"] = 1.into()));
+        assert!(is_invalid(&|json| { json["tiles"].pop(); }));
+        assert!(is_invalid(&|json| { json["explored"].pop(); }));
+        assert!(is_invalid(&|json| json["tiles"][0] = "####".into()));
+        assert!(is_invalid(&|json| { json["tiles"][0] = "##".into(); json["tiles"][1] = "####".into() }));
+        assert!(is_invalid(&|json| { json["explored"][1].pop(); }));
+    }
+}
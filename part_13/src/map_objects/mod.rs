@@ -1,5 +1,8 @@
 pub mod map;
 pub mod fov;
-mod tile;
+pub mod generator;
+pub mod vault;
+pub mod tile;
+pub mod dijkstra;
 mod rectangle;
 mod color;
\ No newline at end of file
@@ -1,35 +1,165 @@
-use json::JsonValue;
+use tcod::colors;
 
-use savegame::{Serialize, Deserialize};
+use map_objects::color::Color;
+
+/// Everything a tile of the map can be made of
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TileKind {
+    Wall,
+    Floor,
+    ClosedDoor,
+    OpenDoor,
+    /// Slows everyone wading through it
+    ShallowWater,
+    /// Burns every `Actor` standing in it at the end of a turn
+    Lava,
+    /// Whoever steps into it falls down to the next floor
+    Chasm,
+    /// A wall which breaks when walking into it
+    CrackedWall,
+}
+
+/// Damage lava deals to an `Actor` standing in it, once per turn
+pub const LAVA_DAMAGE: u32 = 10;
+
+impl TileKind {
+    /// All kinds, used to look up a kind by its code
+    const ALL: [TileKind; 8] = [
+        TileKind::Wall, TileKind::Floor, TileKind::ClosedDoor, TileKind::OpenDoor,
+        TileKind::ShallowWater, TileKind::Lava, TileKind::Chasm, TileKind::CrackedWall,
+    ];
+
+    pub fn block_move(&self) -> bool {
+        match *self {
+            TileKind::Wall | TileKind::ClosedDoor | TileKind::CrackedWall => true,
+            _ => false
+        }
+    }
+
+    pub fn block_sight(&self) -> bool {
+        match *self {
+            TileKind::Wall | TileKind::ClosedDoor | TileKind::CrackedWall => true,
+            _ => false
+        }
+    }
+
+    /// Check if stepping onto the tile hurts, so monsters avoid it and nothing is placed on it
+    pub fn is_hazard(&self) -> bool {
+        *self == TileKind::Lava || *self == TileKind::Chasm
+    }
+
+    /// The number of turns it takes to act while standing on the tile, `0` if it can't be entered
+    pub fn movement_cost(&self) -> u32 {
+        match *self {
+            _ if self.block_move() => 0,
+            TileKind::ShallowWater => 2,
+            _ => 1
+        }
+    }
+
+    /// The glyph drawn on the tile, if any, and its color
+    pub fn glyph(&self) -> Option<(char, colors::Color)> {
+        match *self {
+            TileKind::ClosedDoor => Some(('+', colors::DARKER_ORANGE)),
+            TileKind::OpenDoor => Some(('\'', colors::DARKER_ORANGE)),
+            TileKind::ShallowWater => Some(('~', colors::LIGHT_BLUE)),
+            TileKind::Lava => Some(('~', colors::YELLOW)),
+            TileKind::CrackedWall => Some(('%', colors::DARKEST_GREY)),
+            _ => None
+        }
+    }
+
+    /// The background of the tile, depending on whether it is in view or only remembered
+    pub fn background(&self, visible: bool) -> colors::Color {
+        let color = match (*self, visible) {
+            (TileKind::Wall, true) | (TileKind::CrackedWall, true) => Color::LightWall,
+            (TileKind::Wall, false) | (TileKind::CrackedWall, false) => Color::DarkWall,
+            (TileKind::ShallowWater, true) => Color::LightWater,
+            (TileKind::ShallowWater, false) => Color::DarkWater,
+            (TileKind::Lava, true) => Color::LightLava,
+            (TileKind::Lava, false) => Color::DarkLava,
+            (TileKind::Chasm, _) => Color::Chasm,
+            (_, true) => Color::LightFloor,
+            (_, false) => Color::DarkFloor,
+        };
+        color.value()
+    }
+
+    /// The character a tile is stored as in save files and vault layouts
+    pub fn code(&self) -> char {
+        match *self {
+            TileKind::Wall => '#',
+            TileKind::Floor => '.',
+            TileKind::ClosedDoor => '+',
+            TileKind::OpenDoor => '\'',
+            TileKind::ShallowWater => '~',
+            TileKind::Lava => '^',
+            TileKind::Chasm => ':',
+            TileKind::CrackedWall => '%',
+        }
+    }
+
+    pub fn from_code(code: char) -> Option<TileKind> {
+        TileKind::ALL.iter().find(|kind| kind.code() == code).cloned()
+    }
+}
 
 #[derive(Clone)]
 pub struct Tile {
-    pub block_move: bool,
-    pub block_sight: bool,
+    pub kind: TileKind,
     pub explored: bool
 }
 
 impl Tile {
-    pub fn new(block_move: bool, block_sight: bool) -> Self {
-
+    pub fn new(kind: TileKind) -> Self {
         Tile {
-            block_move, block_sight, explored : false
+            kind, explored : false
         }
     }
-}
 
-impl Serialize for Tile {
-    fn serialize(&self) -> JsonValue {
-        array![self.block_move, self.block_sight, self.explored]
+    pub fn block_move(&self) -> bool {
+        self.kind.block_move()
+    }
+
+    pub fn block_sight(&self) -> bool {
+        self.kind.block_sight()
     }
 }
 
-impl Deserialize for Tile {
-    fn deserialize(json: &JsonValue) -> Self {
-        Tile {
-            block_move: json[0].as_bool().unwrap(),
-            block_sight: json[1].as_bool().unwrap(),
-            explored: json[2].as_bool().unwrap(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_unique() {
+        for kind in TileKind::ALL.iter() {
+            assert_eq!(TileKind::from_code(kind.code()), Some(*kind));
+        }
+        assert_eq!(TileKind::from_code('x'), None);
+    }
+
+    #[test]
+    fn blocking_tiles_cant_be_entered() {
+        for kind in TileKind::ALL.iter() {
+            assert_eq!(kind.movement_cost() == 0, kind.block_move(), "{:?}", kind);
         }
+        assert_eq!(TileKind::Floor.movement_cost(), 1);
+        assert_eq!(TileKind::OpenDoor.movement_cost(), 1);
+        assert_eq!(TileKind::ShallowWater.movement_cost(), 2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn closed_doors_and_walls_block_sight() {
+        assert!(TileKind::ClosedDoor.block_sight());
+        assert!(TileKind::CrackedWall.block_sight());
+        assert!(!TileKind::OpenDoor.block_sight());
+        assert!(!TileKind::ShallowWater.block_sight());
+        assert!(!TileKind::Chasm.block_sight());
+    }
+
+    #[test]
+    fn only_lava_and_chasms_are_hazards() {
+        let hazards: Vec<TileKind> = TileKind::ALL.iter().cloned().filter(|kind| kind.is_hazard()).collect();
+        assert_eq!(hazards, vec![TileKind::Lava, TileKind::Chasm]);
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Display, Formatter};
+
+use ecs::creature::CreatureTemplate;
+use ecs::item::ItemTemplate;
+use map_objects::tile::TileKind;
+
+/// The text files of all vaults, which are built into the game
+const VAULT_FILES: [(&str, &str); 3] = [
+    ("guard_post.txt", include_str!("../../vaults/guard_post.txt")),
+    ("armory.txt", include_str!("../../vaults/armory.txt")),
+    ("treasury.txt", include_str!("../../vaults/treasury.txt")),
+];
+
+/// Something which is placed on a floor tile of a vault
+#[derive(Clone)]
+pub enum VaultContent {
+    Creature(CreatureTemplate),
+    Item(ItemTemplate),
+}
+
+/// A hand-designed part of a floor, which is stamped into the generated map.
+///
+/// A vault is described by a text file with a header and an ASCII layout:
+///
+/// ```text
+/// # Comments start with a hash
+/// name: Guard post
+/// depth: 2-5
+/// rarity: 10
+///
+/// o = creature Orc
+/// ! = item Healing Potion
+///
+/// layout:
+/// #####
+/// #o!o#
+/// ##+##
+/// ```
+///
+/// The layout uses the codes of the `TileKind`s, e.g. `#` for walls, `.` for floor and `~` for
+/// water. A single `+`, a closed door, is the entrance, which has to lie on the outline. It is
+/// connected to the rest of the floor by a tunnel. All other characters are floor with the
+/// creature or item of the legend on it. `depth` is the range of floors the vault can
+/// appear on, the upper bound can be left out. Vaults with a higher `rarity` are chosen more often.
+/// The `name` only describes the vault to the readers of the file and can be left out.
+pub struct Vault {
+    pub min_floor: u8,
+    pub max_floor: u8,
+    pub rarity: i32,
+    pub width: i32,
+    pub height: i32,
+    /// The position of the entrance, relative to the top left corner
+    pub entrance: (i32, i32),
+    tiles: Vec<TileKind>,
+    contents: Vec<((i32, i32), VaultContent)>,
+}
+
+/// The reason why a vault file can't be used
+#[derive(Debug)]
+pub struct VaultError {
+    pub file: String,
+    pub line: usize,
+    pub reason: String,
+}
+
+impl Display for VaultError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}, line {}: {}", self.file, self.line, self.reason)
+    }
+}
+
+impl Vault {
+    /// Read all vaults which are built into the game
+    pub fn load_all() -> Result<Vec<Vault>, VaultError> {
+        VAULT_FILES.iter().map(|(file, text)| Vault::parse(file, text)).collect()
+    }
+
+    /// Read a vault from the text of a vault file. `file` is only used for error messages.
+    pub fn parse(file: &str, text: &str) -> Result<Vault, VaultError> {
+        let error = |line: usize, reason: String| VaultError { file: file.to_string(), line, reason };
+
+        let mut depth = None;
+        let mut rarity = None;
+        let mut legend: HashMap<char, VaultContent> = HashMap::new();
+        let mut layout: Vec<(usize, &str)> = vec![];
+        let mut in_layout = false;
+
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+
+            if in_layout {
+                if !line.trim().is_empty() {
+                    layout.push((number, line.trim_end()));
+                }
+                continue;
+            }
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line == "layout:" {
+                in_layout = true;
+                continue;
+            }
+
+            let mut chars = line.chars();
+            if let (Some(symbol), Some(' '), Some('=')) = (chars.next(), chars.next(), chars.next()) {
+                let mut definition = chars.as_str().trim().splitn(2, ' ');
+                let content = match (definition.next(), definition.next()) {
+                    (Some("creature"), Some(template)) => CreatureTemplate::from_name(template)
+                        .map(VaultContent::Creature),
+                    (Some("item"), Some(template)) => ItemTemplate::from_name(template)
+                        .map(VaultContent::Item),
+                    _ => None
+                };
+                let content = content.ok_or_else(|| error(number, format!("unknown content for '{}'", symbol)))?;
+
+                if TileKind::from_code(symbol).is_some() {
+                    return Err(error(number, format!("'{}' can't be redefined", symbol)));
+                }
+                legend.insert(symbol, content);
+                continue;
+            }
+
+            let mut field = line.splitn(2, ':');
+            match (field.next(), field.next().map(|value| value.trim())) {
+                (Some("name"), Some(_)) => {}
+                (Some("depth"), Some(value)) => {
+                    depth = Some(Self::parse_depth(value).ok_or_else(|| error(number, format!("invalid depth '{}'", value)))?);
+                }
+                (Some("rarity"), Some(value)) => {
+                    rarity = Some(value.parse::<i32>().ok().filter(|r| *r > 0)
+                        .ok_or_else(|| error(number, format!("invalid rarity '{}'", value)))?);
+                }
+                _ => return Err(error(number, format!("unexpected line '{}'", line)))
+            }
+        }
+
+        let end = text.lines().count();
+        let (min_floor, max_floor) = depth.ok_or_else(|| error(end, "the depth is missing".to_string()))?;
+        let rarity = rarity.ok_or_else(|| error(end, "the rarity is missing".to_string()))?;
+
+        if layout.is_empty() {
+            return Err(error(end, "the layout is missing".to_string()));
+        }
+
+        let width = layout[0].1.chars().count() as i32;
+        let height = layout.len() as i32;
+        let mut tiles = vec![];
+        let mut contents = vec![];
+        let mut entrance = None;
+
+        for (y, (number, row)) in layout.iter().enumerate() {
+            if row.chars().count() as i32 != width {
+                return Err(error(*number, "all rows of the layout need the same length".to_string()));
+            }
+
+            for (x, symbol) in row.chars().enumerate() {
+                let position = (x as i32, y as i32);
+                tiles.push(TileKind::from_code(symbol).unwrap_or(TileKind::Floor));
+
+                match symbol {
+                    '+' => {
+                        let on_outline = position.0 == 0 || position.1 == 0 || position.0 == width - 1 || position.1 == height - 1;
+                        if entrance.is_some() || !on_outline {
+                            return Err(error(*number, "there has to be a single entrance on the outline".to_string()));
+                        }
+                        entrance = Some(position);
+                    }
+                    _ if TileKind::from_code(symbol).is_some() => {}
+                    _ => {
+                        let content = legend.get(&symbol)
+                            .ok_or_else(|| error(*number, format!("'{}' is missing in the legend", symbol)))?;
+                        contents.push((position, content.clone()));
+                    }
+                }
+            }
+        }
+
+        let entrance = entrance.ok_or_else(|| error(end, "the entrance is missing".to_string()))?;
+
+        let vault = Vault { min_floor, max_floor, rarity, width, height, entrance, tiles, contents };
+
+        if !vault.is_connected() {
+            return Err(error(end, "not all floor can be reached from the entrance".to_string()));
+        }
+
+        Ok(vault)
+    }
+
+    /// Read a range of floors like `2-5`, or `2-` without an upper bound
+    fn parse_depth(value: &str) -> Option<(u8, u8)> {
+        let mut bounds = value.splitn(2, '-');
+        let min = bounds.next()?.trim().parse().ok()?;
+        let max = match bounds.next()?.trim() {
+            "" => u8::max_value(),
+            max => max.parse().ok()?,
+        };
+
+        if min <= max { Some((min, max)) } else { None }
+    }
+
+    /// Check if all floor of the vault can be reached from its entrance
+    fn is_connected(&self) -> bool {
+        let passable = |x, y| self.tile(x, y) == TileKind::ClosedDoor || !self.tile(x, y).block_move();
+        let mut reached = vec![false; self.tiles.len()];
+        let mut queue = VecDeque::new();
+        reached[(self.entrance.1 * self.width + self.entrance.0) as usize] = true;
+        queue.push_back(self.entrance);
+
+        while let Some((x, y)) = queue.pop_front() {
+            for (nx, ny) in vec![(x, y - 1), (x + 1, y), (x, y + 1), (x - 1, y)] {
+                let index = (ny * self.width + nx) as usize;
+                if passable(nx, ny) && !reached[index] {
+                    reached[index] = true;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+
+        (0..self.tiles.len() as i32).all(|index| {
+            let (x, y) = (index % self.width, index / self.width);
+            !passable(x, y) || reached[index as usize]
+        })
+    }
+
+    /// Check if a vault can appear on a floor
+    pub fn fits_depth(&self, floor_number: u8) -> bool {
+        floor_number >= self.min_floor && floor_number <= self.max_floor
+    }
+
+    /// The tile at a position relative to the top left corner. Everything outside of the vault
+    /// counts as wall.
+    pub fn tile(&self, x: i32, y: i32) -> TileKind {
+        if x >= 0 && y >= 0 && x < self.width && y < self.height {
+            self.tiles[(y * self.width + x) as usize]
+        } else {
+            TileKind::Wall
+        }
+    }
+
+    /// The creatures and items of the vault, with their positions relative to the top left corner
+    pub fn contents(&self) -> &[((i32, i32), VaultContent)] {
+        &self.contents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The line and reason of the error a vault file causes
+    fn error_of(text: &str) -> (usize, String) {
+        match Vault::parse("test.txt", text) {
+            Ok(_) => panic!("The vault was accepted"),
+            Err(e) => (e.line, e.reason)
+        }
+    }
+
+    #[test]
+    fn built_in_vaults_are_valid() {
+        assert_eq!(Vault::load_all().unwrap().len(), VAULT_FILES.len());
+    }
+
+    #[test]
+    fn valid_vault_is_read() {
+        let vault = Vault::parse("test.txt", "depth: 2-\nrarity: 3\no = creature Orc\nlayout:\n###\n#o#\n#+#\n").unwrap();
+
+        assert_eq!((vault.min_floor, vault.max_floor, vault.rarity), (2, u8::max_value(), 3));
+        assert_eq!((vault.width, vault.height, vault.entrance), (3, 3, (1, 2)));
+        assert_eq!(vault.tile(1, 1), TileKind::Floor);
+        assert_eq!(vault.tile(1, 2), TileKind::ClosedDoor);
+        assert_eq!(vault.tile(-1, 0), TileKind::Wall);
+        assert_eq!(vault.contents().len(), 1);
+    }
+
+    #[test]
+    fn invalid_header_is_rejected() {
+        assert_eq!(error_of("x = creature Dragon"), (1, "unknown content for 'x'".to_string()));
+        assert_eq!(error_of(". = item Healing Potion"), (1, "'.' can't be redefined".to_string()));
+        assert_eq!(error_of("depth: 5-2"), (1, "invalid depth '5-2'".to_string()));
+        assert_eq!(error_of("depth: 2"), (1, "invalid depth '2'".to_string()));
+        assert_eq!(error_of("rarity: 0"), (1, "invalid rarity '0'".to_string()));
+        assert_eq!(error_of("\nsize: 3"), (2, "unexpected line 'size: 3'".to_string()));
+    }
+
+    #[test]
+    fn missing_parts_are_rejected() {
+        assert_eq!(error_of("rarity: 1\nlayout:\n#+#"), (3, "the depth is missing".to_string()));
+        assert_eq!(error_of("depth: 1-\nlayout:\n#+#"), (3, "the rarity is missing".to_string()));
+        assert_eq!(error_of("depth: 1-\nrarity: 1\nlayout:\n"), (3, "the layout is missing".to_string()));
+        assert_eq!(error_of("depth: 1-\nrarity: 1\nlayout:\n###\n#.#\n###"),
+                   (6, "the entrance is missing".to_string()));
+    }
+
+    #[test]
+    fn invalid_layout_is_rejected() {
+        let header = "depth: 1-\nrarity: 1\no = creature Orc\nlayout:\n";
+        let error_of_layout = |layout: &str| error_of(&format!("{}{}", header, layout));
+
+        assert_eq!(error_of_layout("###\n#.\n#+#"), (6, "all rows of the layout need the same length".to_string()));
+        assert_eq!(error_of_layout("###\n#+#\n###"), (6, "there has to be a single entrance on the outline".to_string()));
+        assert_eq!(error_of_layout("#+#\n#.#\n#+#"), (7, "there has to be a single entrance on the outline".to_string()));
+        assert_eq!(error_of_layout("###\n#x#\n#+#"), (6, "'x' is missing in the legend".to_string()));
+        assert_eq!(error_of_layout("#####\n#.#o#\n#+###"),
+                   (7, "not all floor can be reached from the entrance".to_string()));
+    }
+}
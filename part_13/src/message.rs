@@ -1,12 +1,14 @@
 use std::cell::RefCell;
 use std::cell::Ref;
+use std::collections::VecDeque;
 
 use json::JsonValue;
 
 use tcod::Color;
 
-use savegame::{Serialize, Deserialize};
+use savegame::{Serialize, Deserialize, LoadError, ReadJson};
 
+#[derive(Clone)]
 pub struct Message {
     pub text: String,
     pub color: Color,
@@ -38,35 +40,50 @@ impl Serialize for Message {
 }
 
 impl Deserialize for Message {
-    fn deserialize(json: &JsonValue) -> Self {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
 
-        Message {
-            text: json["text"].as_str().unwrap().to_string(),
-            color: Color {
-                r : json["color"][0].as_u8().unwrap(),
-                g : json["color"][1].as_u8().unwrap(),
-                b : json["color"][2].as_u8().unwrap(),
-            }
-        }
+        Ok(Message {
+            text: json["text"].read_str()?.to_string(),
+            color: Color::deserialize(&json["color"])?,
+        })
+    }
+}
+
+impl Deserialize for Color {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(Color {
+            r : json[0].read_u8()?,
+            g : json[1].read_u8()?,
+            b : json[2].read_u8()?,
+        })
     }
 }
 
+/// The number of messages a `MessageLog` keeps. Older messages are dropped, so the log doesn't
+/// grow for as long as a game goes on, and neither do the saves.
+const MAX_MESSAGES: usize = 100;
+
 pub struct MessageLog {
-    messages: RefCell<Vec<Message>>
+    messages: RefCell<VecDeque<Message>>
 }
 
 impl MessageLog {
     pub fn new() -> MessageLog {
         MessageLog {
-            messages: RefCell::new(vec![])
+            messages: RefCell::new(VecDeque::new())
         }
     }
 
+    /// Add a message, and drop the oldest one if the log is full
     pub fn add(&self, message: Message) {
-        self.messages.borrow_mut().push(message);
+        let mut messages = self.messages.borrow_mut();
+        if messages.len() >= MAX_MESSAGES {
+            messages.pop_front();
+        }
+        messages.push_back(message);
     }
 
-    pub fn messages(&self) -> Ref<Vec<Message>> {
+    pub fn messages(&self) -> Ref<VecDeque<Message>> {
         self.messages.borrow()
     }
 }
@@ -82,12 +99,43 @@ impl Serialize for MessageLog {
 }
 
 impl Deserialize for MessageLog {
-    fn deserialize(json: &JsonValue) -> Self {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
         let log = Self::new();
 
         for m in json.members() {
-            log.add(Message::deserialize(m))
+            log.add(Message::deserialize(m)?)
+        }
+        Ok(log)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tcod::colors;
+
+    fn log_with(count: usize) -> MessageLog {
+        let log = MessageLog::new();
+        for i in 0..count {
+            log.add(Message::new(format!("Message {:05}", i), colors::WHITE));
         }
         log
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn only_the_newest_messages_are_kept() {
+        let log = log_with(MAX_MESSAGES + 5);
+
+        assert_eq!(log.messages().len(), MAX_MESSAGES);
+        assert_eq!(log.messages().front().unwrap().text, "Message 00005");
+        assert_eq!(log.messages().back().unwrap().text, format!("Message {:05}", MAX_MESSAGES + 4));
+    }
+
+    #[test]
+    fn saved_log_of_a_long_game_stays_small() {
+        let full = log_with(MAX_MESSAGES).serialize().dump();
+        let old = log_with(MAX_MESSAGES * 50).serialize().dump();
+
+        assert_eq!(old.len(), full.len());
+        assert_eq!(MessageLog::deserialize(&json::parse(&old).unwrap()).unwrap().messages().len(), MAX_MESSAGES);
+    }
+}
@@ -1,10 +1,97 @@
 use rand::prelude::*;
+use rand::Error;
 use std::collections::HashMap;
 use std::borrow::Cow;
 
+use json::JsonValue;
+
+use savegame::{Serialize, Deserialize, LoadError, ReadJson};
+
+/// The random number generator of a game.
+///
+/// Every random decision of the game is taken from this generator, so a game started with the
+/// same seed always creates the same dungeon. The state is stored in the save file, so a loaded
+/// game continues with the same numbers it would have gotten without saving.
+///
+/// This uses the SplitMix64 algorithm, which is fast and whose whole state is a single number.
+pub struct GameRng {
+    seed: u64,
+    state: u64,
+}
+
+impl GameRng {
+    /// Create a generator with a fixed seed
+    pub fn from_seed(seed: u64) -> GameRng {
+        GameRng {
+            seed,
+            state: seed,
+        }
+    }
+
+    /// Create a generator with a randomly chosen seed
+    pub fn from_random_seed() -> GameRng {
+        GameRng::from_seed(thread_rng().gen())
+    }
+
+    /// The seed the generator was created with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let value = self.next_u64();
+            for (i, byte) in chunk.iter_mut().enumerate() {
+                *byte = (value >> (i * 8)) as u8;
+            }
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl Serialize for GameRng {
+    fn serialize(&self) -> JsonValue {
+        // JSON numbers can't hold every u64, so both values are stored as strings
+        object!(
+            "seed" => self.seed.to_string(),
+            "state" => self.state.to_string()
+        )
+    }
+}
+
+impl Deserialize for GameRng {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        let read_number = |json: &JsonValue| -> Result<u64, LoadError> {
+            json.read_str()?.parse().map_err(|_| LoadError::invalid("a number", json))
+        };
+
+        Ok(GameRng {
+            seed: read_number(&json["seed"])?,
+            state: read_number(&json["state"])?,
+        })
+    }
+}
+
 /// Returns a randomly selected index from a weighted list
-pub fn random_choice_index(chances: Vec<i32>) -> usize {
-    let mut rng = thread_rng();
+pub fn random_choice_index(chances: Vec<i32>, rng: &mut GameRng) -> usize {
     let random_chance = rng.gen_range(1, chances.iter().sum());
 
     let mut running_sum = 0;
@@ -1,9 +1,8 @@
-use std::fmt::{Display, Formatter, Result, Debug};
+use std::fmt;
+use std::fmt::{Display, Formatter, Debug};
 use std::ops::DerefMut;
 use std::cell::RefMut;
 
-use tcod::console::{Console, Root, blit, Offscreen};
-use tcod::image;
 
 use tcod::Map;
 use ecs::Ecs;
@@ -15,6 +14,8 @@ use tcod::colors;
 use tcod::BackgroundFlag;
 use tcod::TextAlignment;
 use ecs::component::Actor;
+use ecs::component::MonsterAi;
+use ecs::status::StatusEffects;
 use message::MessageLog;
 use std::rc::Rc;
 use textwrap::wrap;
@@ -22,17 +23,18 @@ use ecs::component::Name;
 use ecs::component::Inventory;
 use game::state::GameState;
 use json::JsonValue;
-use savegame::Deserialize;
+use savegame::{Deserialize, LoadError};
 
 use game::Game;
 use engine::Engine;
-use tcod::image::Image;
 use ecs::component::Stairs;
 use ecs::component::Corpse;
 use ecs::component::Level;
 use ecs::component::Equipment;
 use ecs::component::Equippable;
 use ecs::component::EquipmentSlot;
+use terminal::Terminal;
+use camera::Camera;
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub enum RenderOrder {
@@ -43,20 +45,20 @@ pub enum RenderOrder {
 }
 
 impl Display for RenderOrder {
-    fn fmt(&self, f: &mut Formatter) -> Result {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         Debug::fmt(self, f)
     }
 }
 
 impl Deserialize for RenderOrder {
-    fn deserialize(json: &JsonValue) -> Self {
-        match json.as_str() {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(match json.as_str() {
             Some("Corpse") => RenderOrder::Corpse,
             Some("Item") => RenderOrder::Item,
             Some("Actor") => RenderOrder::Actor,
             Some("Stair") => RenderOrder::Stair,
             _ => RenderOrder::Corpse
-        }
+        })
     }
 }
 
@@ -64,7 +66,8 @@ impl Deserialize for RenderOrder {
 /// Render all `Entity`s which got both the `Render` and the `Position` component assigned onto the console
 pub fn render_all(engine: &Engine, game: &RefMut<Game>) {
     match engine.state {
-        GameState::MainMenu => render_main_menu(&engine),
+        GameState::MainMenu | GameState::EnterSeed(_)
+        | GameState::ShowSaveSlots | GameState::ShowSaveSlotOptions(_) => render_main_menu(&engine),
         _ => render_game(&engine, &game)
     }
 }
@@ -74,150 +77,201 @@ fn render_game(engine: &Engine, game: &RefMut<Game>) {
     let mut map = game.map.borrow_mut();
     let fov_map = game.fov_map.borrow();
 
-    let mut root_console = engine.root_console.borrow_mut();
+    let mut terminal = engine.terminal.borrow_mut();
+    let terminal = terminal.deref_mut().as_mut();
 
-    let mut console = Offscreen::new(engine.settings.screen_width(), engine.settings.screen_height());
-    let mut panel = Offscreen::new(engine.settings.screen_width(), engine.settings.panel_height());
+    let screen_width = engine.settings.screen_width();
+    let screen_height = engine.settings.screen_height();
+    let (panel_x, panel_y) = engine.settings.panel_pos();
 
-    map.draw(&mut console, &fov_map);
+    let camera = Camera::following_player(&ecs, &map, &engine.settings);
 
-    let component_ids = ecs.get_all_ids::<Render>();
-    let mut ids_filtered: Vec<&EntityId> = component_ids.iter().filter(|id| {
-        if let Some(p) = ecs.get_component::<Position>(**id) {
-            fov_map.is_in_fov(p.position.0, p.position.1)
-                || (map.get_tile(p.position.0 as usize, p.position.1 as usize).explored &&
-                ecs.has_component::<Stairs>(**id))
-        } else {
-            false
-        }
-    }).collect();
-    ids_filtered.sort_by(|id_a, id_b| {
-        let comp_a = ecs.get_component::<Render>(**id_a).unwrap();
-        let comp_b = ecs.get_component::<Render>(**id_b).unwrap();
+    terminal.set_default_background(colors::BLACK);
+    terminal.clear();
 
+    map.draw(terminal, &fov_map, &camera);
+
+    let mut renderables: Vec<&Render> = ecs.query::<(&Render, &Position, Option<&Stairs>)>().filter(|(_, (_, p, stairs))| {
+        fov_map.is_in_fov(p.position.0, p.position.1)
+            || (map.get_tile(p.position.0 as usize, p.position.1 as usize).explored && stairs.is_some())
+    }).map(|(_, (r, _, _))| r).collect();
+    renderables.sort_by(|comp_a, comp_b| {
         comp_a.order.cmp(&comp_b.order)
     });
-    ids_filtered.iter().for_each(|id| {
-        let c = ecs.get_component::<Render>(**id).unwrap();
-        c.draw(&ecs, &mut console)
+    renderables.iter().for_each(|c| {
+        c.draw(&ecs, terminal, &camera)
     });
 
 
-    blit(&console, (0, 0),
-         (console.width(), console.height()),
-         root_console.deref_mut(), (0, 0),
-         1.0, 1.0);
-
-
-    panel.set_default_foreground(colors::LIGHT_GREY);
-    panel.set_default_background(colors::BLACK);
-    panel.clear();
+    terminal.set_default_foreground(colors::LIGHT_GREY);
+    terminal.set_default_background(colors::BLACK);
+    terminal.rect(panel_x, panel_y, screen_width, engine.settings.panel_height(), true, BackgroundFlag::Set);
 
-    panel.print_ex(1, 0, BackgroundFlag::None, TextAlignment::Left,
-                   get_names_under_mouse(&ecs, &fov_map, engine.mouse_pos));
+    terminal.print(panel_x + 1, panel_y, TextAlignment::Left,
+                   &get_names_under_mouse(&ecs, &fov_map, &camera, engine.mouse_pos));
 
     if let Some(p) = ecs.get_component::<Actor>(ecs.player_entity_id) {
-        panel.set_default_background(colors::BLACK);
-        render_bar(&mut panel, (1, 1), engine.settings.bar_width(),
+        terminal.set_default_background(colors::BLACK);
+        render_bar(terminal, (panel_x + 1, panel_y + 1), engine.settings.bar_width(),
                    "HP", p.hp, p.max_hp(&ecs),
                    colors::RED, colors::DARK_RED);
     }
 
-    panel.print_ex(1, 3, BackgroundFlag::None, TextAlignment::Left, format!("Dungeon level: {}", game.floor_number));
+    if let Some(effects) = ecs.get_component::<StatusEffects>(ecs.player_entity_id) {
+        let mut x = panel_x + 1;
+        for effect in effects.effects() {
+            terminal.set_default_foreground(effect.kind.color());
+            terminal.print(x, panel_y + 2, TextAlignment::Left, effect.kind.label());
+            x += effect.kind.label().len() as i32 + 1;
+        }
+    }
 
-    game.log_panel.render(&mut panel);
+    terminal.set_default_foreground(colors::LIGHT_GREY);
+    terminal.print(panel_x + 1, panel_y + 3, TextAlignment::Left, &format!("Dungeon level: {}", game.floor_number));
+    terminal.print(panel_x + 1, panel_y + 4, TextAlignment::Left, &format!("Seed: {}", game.rng.borrow().seed()));
+
+    if is_faster_enemy_in_view(&ecs, &fov_map) {
+        terminal.set_default_foreground(colors::LIGHT_RED);
+        terminal.print(panel_x + 1, panel_y + 5, TextAlignment::Left, "An enemy is faster!");
+    }
 
-    blit(&panel, (0, 0),
-         (panel.width(), panel.height()),
-         root_console.deref_mut(), engine.settings.panel_pos(),
-         1.0, 1.0);
+    game.log_panel.render(terminal, (panel_x, panel_y));
 
 
     match engine.state {
-        GameState::ShowInventoryUse => inventory_menu(root_console.deref_mut(), &ecs, "Press the key next to an item to use it, or Esc to cancel.",
-                                                      50, console.width(), console.height()),
-        GameState::ShowInventoryDrop => inventory_menu(root_console.deref_mut(), &ecs, "Press the key next to an item to drop it, or Esc to cancel.",
-                                                       50, console.width(), console.height()),
-        GameState::ShowInventoryEquip => equipment_menu(root_console.deref_mut(), &ecs, "Press the key next to an item to equip or unequip it, or Esc to cancel.",
-                                                       50, console.width(), console.height()),
-        GameState::ShowLeveUpMenu => level_up_menu(root_console.deref_mut(), &ecs,console.width(), console.height()),
-        GameState::ShowQuitGameMenu => selection_menu(root_console.deref_mut(), "",
+        GameState::ShowInventoryUse => inventory_menu(terminal, &ecs, "Press the key next to an item to use it, or Esc to cancel.",
+                                                      50, screen_width, screen_height),
+        GameState::ShowInventoryDrop => inventory_menu(terminal, &ecs, "Press the key next to an item to drop it, or Esc to cancel.",
+                                                       50, screen_width, screen_height),
+        GameState::ShowInventoryEquip => equipment_menu(terminal, &ecs, "Press the key next to an item to equip or unequip it, or Esc to cancel.",
+                                                       50, screen_width, screen_height),
+        GameState::ShowLeveUpMenu => level_up_menu(terminal, &ecs, screen_width, screen_height),
+        GameState::ShowQuitGameMenu => selection_menu(terminal, "",
                                                       vec![String::from("Save & Quit"), String::from("Cancel")],
-                                                      24, console.width(), console.height()),
-        GameState::ShowCharacterScreen => character_screen(root_console.deref_mut(), &ecs,30, 10,
-                                                           console.width(), console.height()),
-        GameState::PlayerDead => message_box(root_console.deref_mut(), "YOU ARE DEAD. Press Escape to return to the main menu",
-                                             console.width(), console.height()),
+                                                      24, screen_width, screen_height),
+        GameState::ShowCharacterScreen => character_screen(terminal, &ecs,30, 10,
+                                                           screen_width, screen_height),
+        GameState::PlayerDead => message_box(terminal, "YOU ARE DEAD. Press Escape to return to the main menu",
+                                             screen_width, screen_height),
         _ => ()
     }
-    root_console.flush()
+    terminal.flush()
 }
 
 fn render_main_menu(engine: &Engine) {
-    let mut root_console = engine.root_console.borrow_mut();
+    let mut terminal = engine.terminal.borrow_mut();
+    let terminal = terminal.deref_mut().as_mut();
 
-    let background = Image::from_file("menu_background1.png").unwrap();
+    // Terminals which can't show the image would show the game behind the menu otherwise
+    terminal.clear();
+    terminal.draw_image("menu_background1.png");
 
-    image::blit_2x(&background, (0, 0),
-                   (-1, -1),
-                   root_console.deref_mut(), (0, 0));
+    terminal.set_default_foreground(colors::LIGHT_YELLOW);
 
-    root_console.set_default_foreground(colors::LIGHT_YELLOW);
+    terminal.print(engine.settings.screen_width() / 2, engine.settings.screen_height() / 2 - 4,
+                   TextAlignment::Center,
+                   "/r/roguelikedev Tutorial Series 2018");
 
-    root_console.print_ex(engine.settings.screen_width() / 2, engine.settings.screen_height() / 2 - 4,
-                          BackgroundFlag::None, TextAlignment::Center,
-                          "/r/roguelikedev Tutorial Series 2018");
+    terminal.print(engine.settings.screen_width() / 2, engine.settings.screen_height() - 2,
+                   TextAlignment::Center,
+                   "by /u/CrocodileSpacePope");
 
-    root_console.print_ex(engine.settings.screen_width() / 2, engine.settings.screen_height() - 2,
-                          BackgroundFlag::None, TextAlignment::Center,
-                          "by /u/CrocodileSpacePope");
-
-    selection_menu(&mut root_console, "",
-                   vec![String::from("New game"), String::from("Continue last game"), String::from("Quit")],
-                   24, engine.settings.screen_width(), engine.settings.screen_height());
+    match engine.state {
+        GameState::EnterSeed(seed) => {
+            let title = format!("Enter a seed and press Enter, or Esc to cancel: {}_", seed);
+            message_box(terminal, &title, engine.settings.screen_width(), engine.settings.screen_height());
+        }
+        GameState::ShowSaveSlots => {
+            let slots = (0..engine.save_slots.len()).map(|slot| describe_save_slot(engine, slot)).collect();
+            selection_menu(terminal, "Choose a saved game, or press Esc to go back\n", slots,
+                           70, engine.settings.screen_width(), engine.settings.screen_height());
+        }
+        GameState::ShowSaveSlotOptions(slot) => {
+            let title = format!("{}\n", describe_save_slot(engine, slot));
+            selection_menu(terminal, &title, vec![String::from("Load"), String::from("Delete")],
+                           70, engine.settings.screen_width(), engine.settings.screen_height());
+        }
+        _ => {
+            selection_menu(terminal, "",
+                           vec![String::from("New game"), String::from("New game with seed"),
+                                String::from("Continue last game"), String::from("Load game"),
+                                String::from("Quit")],
+                           24, engine.settings.screen_width(), engine.settings.screen_height());
+        }
+    }
 
+    if let Some(ref error) = engine.menu_error {
+        let width = engine.settings.screen_width() - 10;
+        terminal.set_default_foreground(colors::LIGHT_RED);
+        terminal.print_rect(engine.settings.screen_width() / 2, engine.settings.screen_height() - 6,
+                            width, 3, TextAlignment::Center, error);
+    }
 
-    root_console.flush();
+    terminal.flush();
 }
 
 
+/// The text shown for a save slot in the list of saved games
+fn describe_save_slot(engine: &Engine, slot: usize) -> String {
+    let description = match engine.save_slots.get(slot) {
+        Some(Ok(metadata)) => metadata.to_string(),
+        Some(Err(LoadError::NoSaveFile)) | None => String::from("Empty"),
+        Some(Err(_)) => String::from("Can't be loaded"),
+    };
+
+    format!("Slot {}: {}", slot + 1, description)
+}
+
 /// Render a bar to graphically represent a value
-pub fn render_bar(panel: &mut Offscreen, pos: (i32, i32), width: i32, name: &str, value: u32, max: u32, bar_color: Color, back_color: Color) {
+pub fn render_bar(terminal: &mut Terminal, pos: (i32, i32), width: i32, name: &str, value: u32, max: u32, bar_color: Color, back_color: Color) {
     let filled_width = (value as f64 / max as f64 * width as f64).round() as i32;
 
-    panel.set_default_background(back_color);
-    panel.rect(pos.0, pos.1, width, 1, false, BackgroundFlag::Screen);
+    terminal.set_default_background(back_color);
+    terminal.rect(pos.0, pos.1, width, 1, false, BackgroundFlag::Screen);
 
     if filled_width > 0 {
-        panel.set_default_background(bar_color);
-        panel.rect(pos.0, pos.1, filled_width, 1, false, BackgroundFlag::Screen)
+        terminal.set_default_background(bar_color);
+        terminal.rect(pos.0, pos.1, filled_width, 1, false, BackgroundFlag::Screen)
     }
 
-    panel.set_default_foreground(colors::WHITE);
-    panel.print_ex(pos.0 + width / 2, pos.1, BackgroundFlag::None,
-                   TextAlignment::Center, format!("{}: {}/{}", name, value, max));
+    terminal.set_default_foreground(colors::WHITE);
+    terminal.print(pos.0 + width / 2, pos.1,
+                   TextAlignment::Center, &format!("{}: {}/{}", name, value, max));
+}
+
+/// Check if the player can see a monster which acts more often than the player
+fn is_faster_enemy_in_view(ecs: &Ecs, fov_map: &Map) -> bool {
+    let player_speed = match ecs.get_component::<Actor>(ecs.player_entity_id) {
+        Some(player) => player.speed,
+        None => return false
+    };
+
+    ecs.query::<(&Actor, &Position, &MonsterAi)>().any(|(_, (actor, p, _))| {
+        actor.speed > player_speed && !actor.is_dead() && fov_map.is_in_fov(p.position.0, p.position.1)
+    })
 }
 
 /// Get a Vec of the names of all Entities which are under the cursor.
-fn get_names_under_mouse(ecs: &Ecs, fov_map: &Map, mouse_pos: (i32, i32)) -> String {
+fn get_names_under_mouse(ecs: &Ecs, fov_map: &Map, camera: &Camera, mouse_pos: (i32, i32)) -> String {
     let mut names = vec![];
 
-    let mut ids_filtered: Vec<EntityId> = ecs.get_all::<Position>().iter().filter(|(id, p)| {
+    let mouse_pos = match camera.to_world(mouse_pos) {
+        Some(position) => position,
+        None => return String::new()
+    };
+
+    let mut entities_filtered: Vec<(EntityId, &Render)> = ecs.query::<(&Position, &Render)>().filter(|(_, (p, _))| {
         p.position.0 == mouse_pos.0 && p.position.1 == mouse_pos.1
             && fov_map.is_in_fov(mouse_pos.0, mouse_pos.1)
-            && ecs.has_component::<Render>(**id)
-    }).map(|(id, _)| {
-        id.clone()
+    }).map(|(id, (_, r))| {
+        (id, r)
     }).collect();
 
-    ids_filtered.sort_by(|id_a, id_b| {
-        let comp_a = ecs.get_component::<Render>(*id_a).unwrap();
-        let comp_b = ecs.get_component::<Render>(*id_b).unwrap();
-
+    entities_filtered.sort_by(|(_, comp_a), (_, comp_b)| {
         comp_b.order.cmp(&comp_a.order)
     });
 
-    ids_filtered.iter().for_each(|id| {
+    entities_filtered.iter().for_each(|(id, _)| {
         names.push(generate_entity_text(ecs, *id));
     });
 
@@ -237,44 +291,60 @@ fn generate_entity_text(ecs: &Ecs, id: EntityId) -> String {
     if let Some(c) = ecs.get_component::<Corpse>(id) {
         name = format!("{},Dead", name);
     }
+    if let Some(effects) = ecs.get_component::<StatusEffects>(id) {
+        for effect in effects.effects() {
+            name = format!("{},{}", name, effect.kind.name());
+        }
+    }
+    if ecs.get_component::<MonsterAi>(id).map_or(false, |ai| ai.is_fleeing()) {
+        name = format!("{},fleeing", name);
+    }
+
+    // The speed is compared to the player's, since that decides how often the creature acts
+    let player_speed = ecs.get_component::<Actor>(ecs.player_entity_id).map(|p| p.speed);
+    match (ecs.get_component::<Actor>(id), player_speed) {
+        (Some(a), Some(speed)) if id != ecs.player_entity_id && !a.is_dead() && a.speed > speed => {
+            name = format!("{},fast", name);
+        }
+        (Some(a), Some(speed)) if id != ecs.player_entity_id && !a.is_dead() && a.speed < speed => {
+            name = format!("{},slow", name);
+        }
+        _ => ()
+    }
 
     name
 }
 
-fn message_box(console: &mut Root, title: &str, screen_width: i32, screen_height: i32) {
-    selection_menu(console, title, vec![], 24, screen_width, screen_height);
+fn message_box(terminal: &mut Terminal, title: &str, screen_width: i32, screen_height: i32) {
+    selection_menu(terminal, title, vec![], 24, screen_width, screen_height);
 }
 
 /// Display a selection menu of various options
-pub fn selection_menu(console: &mut Root, title: &str, options: Vec<String>, width: i32, screen_width: i32, screen_height: i32) {
-    let header_height = console.get_height_rect(0, 0, width, screen_height, title);
+pub fn selection_menu(terminal: &mut Terminal, title: &str, options: Vec<String>, width: i32, screen_width: i32, screen_height: i32) {
+    let header_height = terminal.get_height_rect(width, title);
     let height = header_height + options.len() as i32;
-    let mut menu_panel = Offscreen::new(width, height);
 
-    menu_panel.set_default_foreground(colors::WHITE);
-    menu_panel.print_rect_ex(0, 0, width, height, BackgroundFlag::None, TextAlignment::Left, title);
+    let x = screen_width / 2 - width / 2;
+    let y = screen_height / 2 - height / 2;
+
+    terminal.set_default_foreground(colors::WHITE);
+    terminal.set_default_background(colors::BLACK);
+    terminal.rect(x, y, width, height, true, BackgroundFlag::Set);
+    terminal.print_rect(x, y, width, height, TextAlignment::Left, title);
 
-    let mut y = header_height;
+    let mut option_y = y + header_height;
     let mut letter_index = 'a' as u8;
 
     for option in options {
         let text = format!("({}) {}", letter_index as char, option);
-        menu_panel.print_ex(0, y, BackgroundFlag::None, TextAlignment::Left, text);
-        y += 1;
+        terminal.print(x, option_y, TextAlignment::Left, &text);
+        option_y += 1;
         letter_index += 1;
     }
-
-    let x = screen_width / 2 - width / 2;
-    let y = screen_height / 2 - height / 2;
-
-    blit(&menu_panel, (0, 0),
-         (width, height),
-         console, (x, y),
-         1.0, 1.0);
 }
 
 
-pub fn equipment_menu(console: &mut Root, ecs: &Ecs, title: &str, width: i32, screen_width: i32, screen_height: i32) {
+pub fn equipment_menu(terminal: &mut Terminal, ecs: &Ecs, title: &str, width: i32, screen_width: i32, screen_height: i32) {
     if let Some(inventory) = ecs.get_component::<Inventory>(ecs.player_entity_id) {
         if let Some(equipment) = ecs.get_component::<Equipment>(ecs.player_entity_id) {
 
@@ -308,12 +378,12 @@ pub fn equipment_menu(console: &mut Root, ecs: &Ecs, title: &str, width: i32, sc
                 }).collect()
             };
 
-            selection_menu(console, title, items, width, screen_width, screen_height);
+            selection_menu(terminal, title, items, width, screen_width, screen_height);
         }
     }
 }
 
-pub fn inventory_menu(console: &mut Root, ecs: &Ecs, title: &str, width: i32, screen_width: i32, screen_height: i32) {
+pub fn inventory_menu(terminal: &mut Terminal, ecs: &Ecs, title: &str, width: i32, screen_width: i32, screen_height: i32) {
     if let Some(inventory) = ecs.get_component::<Inventory>(ecs.player_entity_id) {
         let items = if inventory.items.len() == 0 {
             vec!["Inventory is empty".to_string()]
@@ -325,51 +395,69 @@ pub fn inventory_menu(console: &mut Root, ecs: &Ecs, title: &str, width: i32, sc
             }).collect()
         };
 
-        selection_menu(console, title, items, width, screen_width, screen_height);
+        selection_menu(terminal, title, items, width, screen_width, screen_height);
     }
 }
 
-pub fn character_screen(console: &mut Root, ecs: &Ecs, width: i32, height: i32, screen_width: i32, screen_height: i32) {
+pub fn character_screen(terminal: &mut Terminal, ecs: &Ecs, width: i32, height: i32, screen_width: i32, screen_height: i32) {
+    let effects = ecs.get_component::<StatusEffects>(ecs.player_entity_id)
+        .map(|s| s.effects().to_vec())
+        .unwrap_or_default();
+
+    // Every status effect gets a row below the stats
+    let height = if effects.is_empty() { height } else { height + effects.len() as i32 + 1 };
+
+    let x = screen_width / 2 - width / 2;
+    let y = screen_height / 2 - height / 2;
 
-    let mut panel = Offscreen::new(width, height);
-    panel.set_default_foreground(colors::WHITE);
+    terminal.set_default_foreground(colors::WHITE);
+    terminal.set_default_background(colors::BLACK);
+    terminal.rect(x, y, width, height, true, BackgroundFlag::Set);
 
     let mut text_row = 3;
 
-    panel.print_rect_ex(0, 1, width, height, BackgroundFlag::None, TextAlignment::Left,
+    terminal.print_rect(x, y + 1, width, height - 1, TextAlignment::Left,
         "Character Information");
 
     if let Some(l) = ecs.get_component::<Level>(ecs.player_entity_id) {
-        panel.print_rect_ex(0, text_row, width, height, BackgroundFlag::None, TextAlignment::Left,
-                            format!("Level:            {}", l.level));
-        panel.print_rect_ex(0, text_row+1, width, height, BackgroundFlag::None, TextAlignment::Left,
-                            format!("Total XP:         {}", l.xp_total));
-        panel.print_rect_ex(0, text_row+2, width, height, BackgroundFlag::None, TextAlignment::Left,
-                            format!("XP to next Level: {}", l.xp_to_level(l.level as i32 + 1)));
+        terminal.print_rect(x, y + text_row, width, height - text_row, TextAlignment::Left,
+                            &format!("Level:            {}", l.level));
+        terminal.print_rect(x, y + text_row+1, width, height - text_row-1, TextAlignment::Left,
+                            &format!("Total XP:         {}", l.xp_total));
+        terminal.print_rect(x, y + text_row+2, width, height - text_row-2, TextAlignment::Left,
+                            &format!("XP to next Level: {}", l.xp_to_level(l.level as i32 + 1)));
 
         text_row = 7;
     }
 
     if let Some(a) = ecs.get_component::<Actor>(ecs.player_entity_id) {
-        panel.print_rect_ex(0, text_row, width, height, BackgroundFlag::None, TextAlignment::Left,
-                            format!("MaxHP:       {}", a.max_hp(ecs)));
-        panel.print_rect_ex(0, text_row+1, width, height, BackgroundFlag::None, TextAlignment::Left,
-                            format!("PWR:         {}", a.power(ecs)));
-        panel.print_rect_ex(0, text_row+2, width, height, BackgroundFlag::None, TextAlignment::Left,
-                            format!("DEF:         {}", a.defense(ecs)));
+        terminal.print_rect(x, y + text_row, width, height - text_row, TextAlignment::Left,
+                            &format!("MaxHP:       {}", a.max_hp(ecs)));
+        terminal.print_rect(x, y + text_row+1, width, height - text_row-1, TextAlignment::Left,
+                            &format!("PWR:         {}", a.power(ecs)));
+        terminal.print_rect(x, y + text_row+2, width, height - text_row-2, TextAlignment::Left,
+                            &format!("DEF:         {}", a.defense(ecs)));
+
+        text_row += 4;
     }
 
-    let x = screen_width / 2 - width / 2;
-    let y = screen_height / 2 - height / 2;
-
-    blit(&panel, (0, 0),
-         (width, height),
-         console, (x, y),
-         1.0, 1.0);
+    for (row, effect) in effects.iter().enumerate() {
+        let row = text_row + row as i32;
+        terminal.set_default_foreground(effect.kind.color());
+        terminal.print_rect(x, y + row, width, height - row, TextAlignment::Left,
+                            &format!("{:<15}{} turns", format!("{}:", capitalize(effect.kind.name())), effect.turns));
+    }
+}
 
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new()
+    }
 }
 
-pub fn level_up_menu(console: &mut Root, ecs: &Ecs, screen_width: i32, screen_height: i32) {
+pub fn level_up_menu(terminal: &mut Terminal, ecs: &Ecs, screen_width: i32, screen_height: i32) {
     if let Some(actor) = ecs.get_component::<Actor>(ecs.player_entity_id) {
         let items =
             vec![
@@ -378,7 +466,7 @@ pub fn level_up_menu(console: &mut Root, ecs: &Ecs, screen_width: i32, screen_he
                 format!("+1 Defense         (currently: {})", actor.defense(ecs)),
             ];
 
-        selection_menu(console, "Level Up! Select a stat to raise:", items, 40, screen_width, screen_height);
+        selection_menu(terminal, "Level Up! Select a stat to raise:", items, 40, screen_width, screen_height);
     }
 }
 
@@ -397,17 +485,18 @@ impl MessagePanel {
         }
     }
 
-    pub fn render(&self, panel: &mut Offscreen) {
+    /// Render the newest messages. The position of the panel is relative to `origin`.
+    pub fn render(&self, terminal: &mut Terminal, origin: (i32, i32)) {
         let mut total_lines = 0;
 
         'l: for m in self.log.messages().iter().rev() {
             let lines = wrap(&m.text, self.dimensions.0 as usize);
 
-            panel.set_default_foreground(m.color);
+            terminal.set_default_foreground(m.color);
 
             for l in lines {
-                panel.print_ex(self.pos.0, self.pos.1 + total_lines,
-                               BackgroundFlag::None, TextAlignment::Left, l.to_string());
+                terminal.print(origin.0 + self.pos.0, origin.1 + self.pos.1 + total_lines,
+                               TextAlignment::Left, &l);
                 total_lines += 1;
                 if self.pos.1 + total_lines > self.dimensions.1 {
                     break 'l;
@@ -0,0 +1,408 @@
+use std::io::{Read, Write};
+
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+
+use json::JsonValue;
+use json::number::Number;
+
+use savegame::LoadError;
+
+/// The first bytes of every binary save file, used to tell it apart from a JSON save file
+pub const MAGIC: &[u8] = b"RLSV";
+
+/// The version of the binary encoding itself. The content is versioned like a JSON save file.
+const ENCODING_VERSION: u8 = 1;
+
+/// Flag in the header which marks the content as compressed
+const COMPRESSED: u8 = 1;
+
+/// How deeply arrays and objects can be nested. Saves don't come close to it, but a damaged file
+/// could otherwise nest values until the stack overflows.
+const MAX_DEPTH: usize = 64;
+
+const NULL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const POSITIVE_INTEGER: u8 = 3;
+const NEGATIVE_INTEGER: u8 = 4;
+const DECIMAL: u8 = 5;
+const STRING: u8 = 6;
+const ARRAY: u8 = 7;
+const OBJECT: u8 = 8;
+const BOOL_TABLE: u8 = 9;
+
+/// Check if data starts like a binary save file
+pub fn is_binary(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Encode a `JsonValue` in the compact binary format.
+///
+/// The encoding is lossless, so a binary save file can be converted back to the same JSON:
+///
+/// * Numbers are stored as variable length integers, so small numbers like ids take one or two
+///   bytes.
+/// * Arrays of equally long arrays of bools, like the tiles of a `GameMap`, are packed into
+///   single bits.
+/// * The whole content can be compressed on top of that.
+pub fn encode(json: &JsonValue, compress: bool) -> Vec<u8> {
+    let mut content = vec![];
+    write_value(&mut content, json);
+
+    let mut data = MAGIC.to_vec();
+    data.push(ENCODING_VERSION);
+
+    if compress {
+        data.push(COMPRESSED);
+
+        let mut encoder = ZlibEncoder::new(data, Compression::default());
+        // Writing into a `Vec` can't fail
+        encoder.write_all(&content).unwrap();
+        encoder.finish().unwrap()
+    } else {
+        data.push(0);
+        data.extend(content);
+        data
+    }
+}
+
+/// Decode data which was encoded by `encode`
+pub fn decode(data: &[u8]) -> Result<JsonValue, LoadError> {
+    if !is_binary(data) || data.len() < MAGIC.len() + 2 {
+        return Err(corrupted("the header is missing"));
+    }
+
+    let version = data[MAGIC.len()];
+    let flags = data[MAGIC.len() + 1];
+    let content = &data[MAGIC.len() + 2..];
+
+    if version != ENCODING_VERSION {
+        return Err(corrupted(&format!("unknown encoding {}", version)));
+    }
+
+    if flags & COMPRESSED != 0 {
+        let mut decompressed = vec![];
+        ZlibDecoder::new(content).read_to_end(&mut decompressed).map_err(LoadError::Io)?;
+        Reader { data: &decompressed, position: 0 }.read_value(0)
+    } else {
+        Reader { data: content, position: 0 }.read_value(0)
+    }
+}
+
+fn corrupted(reason: &str) -> LoadError {
+    LoadError::InvalidValue(format!("broken binary data, {}", reason))
+}
+
+fn write_varint(data: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        data.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    data.push(value as u8);
+}
+
+fn write_string(data: &mut Vec<u8>, text: &str) {
+    write_varint(data, text.len() as u64);
+    data.extend_from_slice(text.as_bytes());
+}
+
+/// The number of columns if `json` is a non-empty array of equally long arrays of bools
+fn bool_table_columns(json: &JsonValue) -> Option<usize> {
+    let columns = match json.members().next() {
+        Some(row) if row.is_array() && row.len() > 0 => row.len(),
+        _ => return None
+    };
+
+    let is_table = json.members().all(|row| {
+        row.is_array() && row.len() == columns && row.members().all(|v| v.is_boolean())
+    });
+
+    if is_table { Some(columns) } else { None }
+}
+
+fn write_value(data: &mut Vec<u8>, json: &JsonValue) {
+    match *json {
+        JsonValue::Null => data.push(NULL),
+        JsonValue::Boolean(false) => data.push(FALSE),
+        JsonValue::Boolean(true) => data.push(TRUE),
+        JsonValue::Number(number) => {
+            let (positive, mantissa, exponent) = number.as_parts();
+
+            if number.is_nan() {
+                data.push(NULL);
+            } else if exponent == 0 {
+                data.push(if positive { POSITIVE_INTEGER } else { NEGATIVE_INTEGER });
+                write_varint(data, mantissa);
+            } else {
+                data.push(DECIMAL);
+                data.push(positive as u8);
+                write_varint(data, mantissa);
+                // Zigzag encoding, so small negative exponents stay small
+                write_varint(data, ((exponent << 1) ^ (exponent >> 15)) as u16 as u64);
+            }
+        }
+        JsonValue::Short(_) | JsonValue::String(_) => {
+            data.push(STRING);
+            write_string(data, json.as_str().unwrap());
+        }
+        JsonValue::Array(ref values) => {
+            if let Some(columns) = bool_table_columns(json) {
+                data.push(BOOL_TABLE);
+                write_varint(data, values.len() as u64);
+                write_varint(data, columns as u64);
+
+                let bits : Vec<bool> = values.iter()
+                    .flat_map(|row| row.members())
+                    .map(|value| value.as_bool().unwrap())
+                    .collect();
+
+                for byte in bits.chunks(8) {
+                    data.push(byte.iter().enumerate().fold(0, |b, (i, bit)| b | (*bit as u8) << i));
+                }
+            } else {
+                data.push(ARRAY);
+                write_varint(data, values.len() as u64);
+                values.iter().for_each(|value| write_value(data, value));
+            }
+        }
+        JsonValue::Object(ref object) => {
+            data.push(OBJECT);
+            write_varint(data, object.len() as u64);
+            for (key, value) in object.iter() {
+                write_string(data, key);
+                write_value(data, value);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read_byte(&mut self) -> Result<u8, LoadError> {
+        let byte = *self.data.get(self.position).ok_or_else(|| corrupted("it ends too early"))?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], LoadError> {
+        if self.data.len() - self.position < count {
+            return Err(corrupted("it ends too early"));
+        }
+        let bytes = &self.data[self.position..self.position + count];
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn read_varint(&mut self) -> Result<u64, LoadError> {
+        let mut value = 0u64;
+        let mut shift = 0;
+
+        loop {
+            let byte = self.read_byte()?;
+            if shift > 63 {
+                return Err(corrupted("a number is too large"));
+            }
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    fn read_length(&mut self) -> Result<usize, LoadError> {
+        let length = self.read_varint()? as usize;
+        // Every element takes at least one bit, which catches absurd lengths before allocating
+        if length / 8 > self.data.len() - self.position {
+            return Err(corrupted("a length is too large"));
+        }
+        Ok(length)
+    }
+
+    fn read_string(&mut self) -> Result<String, LoadError> {
+        let length = self.read_length()?;
+        let bytes = self.read_bytes(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| corrupted("a text isn't valid UTF-8"))
+    }
+
+    /// Read a single value, which is nested into `depth` arrays and objects
+    fn read_value(&mut self, depth: usize) -> Result<JsonValue, LoadError> {
+        if depth > MAX_DEPTH {
+            return Err(corrupted("it is nested too deeply"));
+        }
+
+        match self.read_byte()? {
+            NULL => Ok(JsonValue::Null),
+            FALSE => Ok(JsonValue::Boolean(false)),
+            TRUE => Ok(JsonValue::Boolean(true)),
+            POSITIVE_INTEGER => Ok(Number::from_parts(true, self.read_varint()?, 0).into()),
+            NEGATIVE_INTEGER => Ok(Number::from_parts(false, self.read_varint()?, 0).into()),
+            DECIMAL => {
+                let positive = self.read_byte()? != 0;
+                let mantissa = self.read_varint()?;
+                let zigzag = self.read_varint()? as u16;
+                let exponent = (zigzag >> 1) as i16 ^ -((zigzag & 1) as i16);
+                Ok(Number::from_parts(positive, mantissa, exponent).into())
+            }
+            STRING => Ok(self.read_string()?.into()),
+            ARRAY => {
+                let length = self.read_length()?;
+                let mut values = Vec::with_capacity(length);
+                for _ in 0..length {
+                    values.push(self.read_value(depth + 1)?);
+                }
+                Ok(JsonValue::Array(values))
+            }
+            OBJECT => {
+                let length = self.read_length()?;
+                let mut object = JsonValue::new_object();
+                for _ in 0..length {
+                    let key = self.read_string()?;
+                    object[key.as_str()] = self.read_value(depth + 1)?;
+                }
+                Ok(object)
+            }
+            BOOL_TABLE => {
+                let rows = self.read_length()?;
+                let columns = self.read_length()?;
+                let count = rows.checked_mul(columns).ok_or_else(|| corrupted("a table is too large"))?;
+                let bits = self.read_bytes((count + 7) / 8)?;
+
+                let table = (0..rows).map(|row| {
+                    JsonValue::Array((0..columns).map(|column| {
+                        let bit = row * columns + column;
+                        JsonValue::Boolean(bits[bit / 8] & (1 << (bit % 8)) != 0)
+                    }).collect())
+                }).collect();
+
+                Ok(JsonValue::Array(table))
+            }
+            tag => Err(corrupted(&format!("unknown value type {}", tag)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use savegame::{convert, SaveFormat, TempDirectory};
+
+    fn assert_round_trip(json: JsonValue) {
+        assert_eq!(decode(&encode(&json, false)).unwrap(), json);
+        assert_eq!(decode(&encode(&json, true)).unwrap(), json);
+    }
+
+    fn is_corrupted(result: Result<JsonValue, LoadError>) -> bool {
+        match result { Err(LoadError::InvalidValue(_)) => true, _ => false }
+    }
+
+    #[test]
+    fn values_round_trip() {
+        assert_round_trip(JsonValue::Null);
+        assert_round_trip(array![true, false, "text", "", 0, 127, 128, u64::max_value()]);
+        assert_round_trip(object!("nested" => object!("list" => array![1, array![], object!()])));
+
+        let mut deep = JsonValue::Null;
+        for _ in 0..MAX_DEPTH {
+            deep = array![deep];
+        }
+        assert_round_trip(deep);
+    }
+
+    #[test]
+    fn negative_numbers_round_trip() {
+        assert_round_trip(array![-1, -128, -300, i32::min_value() + 1, i64::min_value() + 1]);
+    }
+
+    #[test]
+    fn numbers_with_exponent_round_trip() {
+        assert_round_trip(array![1.5, -2.25, 0.001, 1e300, -1e-300, 12.0e10]);
+        assert_round_trip(Number::from_parts(true, 5, i16::min_value()).into());
+        assert_round_trip(Number::from_parts(false, 5, i16::max_value()).into());
+    }
+
+    #[test]
+    fn bool_tables_round_trip() {
+        assert_round_trip(array![array![true, false, true], array![false, true, true], array![true, true, false]]);
+        assert_round_trip(array![JsonValue::from(vec![true; 8]), JsonValue::from(vec![false; 8])]);
+        // Not tables, so they are stored as plain arrays
+        assert_round_trip(array![array![true, false], array![true]]);
+        assert_round_trip(array![array![true, 1]]);
+        assert_round_trip(array![array![]]);
+    }
+
+    #[test]
+    fn truncated_data_is_rejected() {
+        let json = object!("tiles" => array![array![true, false]], "name" => "Orc", "hp" => -5);
+
+        let data = encode(&json, false);
+        for length in 0..data.len() {
+            assert!(is_corrupted(decode(&data[..length])), "{} bytes were accepted", length);
+        }
+
+        let data = encode(&json, true);
+        assert!(decode(&data[..data.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn corrupted_data_is_rejected() {
+        let header = |version: u8| { let mut data = MAGIC.to_vec(); data.push(version); data.push(0); data };
+
+        let mut unknown_encoding = header(ENCODING_VERSION + 1);
+        unknown_encoding.push(NULL);
+        assert!(is_corrupted(decode(&unknown_encoding)));
+
+        let mut unknown_type = header(ENCODING_VERSION);
+        unknown_type.push(42);
+        assert!(is_corrupted(decode(&unknown_type)));
+
+        let mut invalid_text = header(ENCODING_VERSION);
+        invalid_text.extend_from_slice(&[STRING, 2, 0xff, 0xfe]);
+        assert!(is_corrupted(decode(&invalid_text)));
+
+        let mut huge_array = header(ENCODING_VERSION);
+        huge_array.extend_from_slice(&[ARRAY, 0xff, 0xff, 0xff, 0xff, 0x0f]);
+        assert!(is_corrupted(decode(&huge_array)));
+
+        let mut deeply_nested = header(ENCODING_VERSION);
+        for _ in 0..100_000 {
+            deeply_nested.extend_from_slice(&[ARRAY, 1]);
+        }
+        deeply_nested.push(NULL);
+        assert!(is_corrupted(decode(&deeply_nested)));
+
+        let mut not_compressed = header(ENCODING_VERSION);
+        not_compressed[MAGIC.len() + 1] = COMPRESSED;
+        not_compressed.extend_from_slice(b"plain");
+        assert!(decode(&not_compressed).is_err());
+
+        assert!(is_corrupted(decode(b"{\"json\": true}")));
+    }
+
+    #[test]
+    fn json_is_converted_to_binary_and_back() {
+        let directory = TempDirectory::new();
+        fs::create_dir_all(&directory.path).unwrap();
+        let (json_file, binary_file, converted_file) = (directory.path.join("save.json"),
+                                                        directory.path.join("save.dat"),
+                                                        directory.path.join("converted.json"));
+
+        let json = object!("version" => 7, "map" => object!("explored" => array![array![true, false]]), "x" => -1.5);
+        fs::write(&json_file, json.dump()).unwrap();
+
+        assert_eq!(convert(&json_file, &binary_file).unwrap(), SaveFormat::CompressedBinary);
+        assert!(is_binary(&fs::read(&binary_file).unwrap()));
+
+        assert_eq!(convert(&binary_file, &converted_file).unwrap(), SaveFormat::Json);
+        let converted = fs::read_to_string(&converted_file).unwrap();
+        assert_eq!(::json::parse(&converted).unwrap(), json);
+    }
+}
@@ -0,0 +1,1017 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::time::SystemTime;
+
+use json::{JsonValue};
+use json;
+
+use game::Game;
+use ecs::id::{EntityId, IdGenerator};
+use random_utils::GameRng;
+use game::system::ACTION_ENERGY;
+
+pub mod binary;
+
+/// The single save file of older versions, in the working directory
+const LEGACY_SAVE_FILE_NAME: &str = "savegame.dat";
+/// The directory inside the data directory of the user which contains the save slots
+const SAVE_DIRECTORY_NAME: &str = "roguelikedev-tutorial";
+
+/// The number of games which can be saved at the same time
+pub const SAVE_SLOT_COUNT: usize = 5;
+
+/// The encoding of save files. Both formats can always be loaded, the setting only selects the
+/// format new saves are written in.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SaveFormat {
+    /// Plain JSON, easy to read and edit for debugging
+    Json,
+    /// The compact encoding of `binary`
+    Binary,
+    /// The compact encoding of `binary`, compressed
+    CompressedBinary,
+}
+
+impl SaveFormat {
+    /// Find a format by the name it is chosen with on the command line
+    pub fn from_name(name: &str) -> Option<SaveFormat> {
+        match name {
+            "json" => Some(SaveFormat::Json),
+            "binary" => Some(SaveFormat::Binary),
+            "compressed" => Some(SaveFormat::CompressedBinary),
+            _ => None
+        }
+    }
+}
+
+/// The version of the save format written by this build.
+///
+/// Increase it whenever the format changes, and add a migration from the previous version to
+/// `MIGRATIONS`.
+pub const SAVE_FORMAT_VERSION: u32 = 10;
+
+/// Upgrades a save file from one version to the next. The first entry upgrades version 1.
+const MIGRATIONS: [fn(JsonValue) -> Result<JsonValue, LoadError>; 9] = [
+    migrate_1_to_2,
+    migrate_2_to_3,
+    migrate_3_to_4,
+    migrate_4_to_5,
+    migrate_5_to_6,
+    migrate_6_to_7,
+    migrate_7_to_8,
+    migrate_8_to_9,
+    migrate_9_to_10,
+];
+
+/// Everything which can go wrong while loading a save file
+#[derive(Debug)]
+pub enum LoadError {
+    /// There is no save file at all
+    NoSaveFile,
+    /// The save file exists, but can't be read
+    Io(io::Error),
+    /// The save file is no valid JSON
+    Parse(json::Error),
+    /// The save file was written by a newer version of the game
+    UnsupportedVersion(u32),
+    /// A value is missing or has the wrong type
+    InvalidValue(String),
+}
+
+impl LoadError {
+    /// Create an error for a value which doesn't have the expected type
+    pub fn invalid(expected: &str, found: &JsonValue) -> LoadError {
+        LoadError::InvalidValue(format!("expected {}, found {}", expected, found.dump()))
+    }
+
+    /// Add the place where an invalid value was found to the error
+    pub fn context(self, context: &str) -> LoadError {
+        match self {
+            LoadError::InvalidValue(message) => LoadError::InvalidValue(format!("{} in {}", message, context)),
+            e => e
+        }
+    }
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            LoadError::NoSaveFile => write!(f, "There is no saved game"),
+            LoadError::Io(ref e) => write!(f, "The save file can't be read: {}", e),
+            LoadError::Parse(ref e) => write!(f, "The save file is corrupted: {}", e),
+            LoadError::UnsupportedVersion(version) => {
+                write!(f, "The save file was created by a newer version of the game (format {})", version)
+            }
+            LoadError::InvalidValue(ref message) => write!(f, "The save file contains invalid data: {}", message),
+        }
+    }
+}
+
+/// The information shown in the list of save slots, stored next to the game itself
+#[derive(Clone, Debug, PartialEq)]
+pub struct SaveMetadata {
+    /// The character level of the player
+    pub level: u8,
+    pub floor_number: u8,
+    /// The time spent playing, in seconds
+    pub play_time: u64,
+    /// The time of saving, in seconds since the unix epoch. `0` if it isn't known.
+    pub timestamp: u64,
+}
+
+impl Display for SaveMetadata {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Level {}, floor {}, {}:{:02}:{:02} played",
+               self.level, self.floor_number,
+               self.play_time / 3600, self.play_time / 60 % 60, self.play_time % 60)?;
+
+        if self.timestamp > 0 {
+            write!(f, ", saved {}", format_timestamp(self.timestamp))?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for SaveMetadata {
+    fn serialize(&self) -> JsonValue {
+        object!(
+            "level" => self.level,
+            "floor_number" => self.floor_number,
+            "play_time" => self.play_time,
+            "timestamp" => self.timestamp,
+        )
+    }
+}
+
+impl Deserialize for SaveMetadata {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError> {
+        Ok(SaveMetadata {
+            level: json["level"].read_u8()?,
+            floor_number: json["floor_number"].read_u8()?,
+            play_time: json["play_time"].read_u64()?,
+            timestamp: json["timestamp"].read_u64()?,
+        })
+    }
+}
+
+/// Format a unix timestamp as UTC date and time, e.g. `2018-08-14 17:03`
+fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let minutes = timestamp / 60 % 1440;
+
+    // Convert the days since 1970-01-01 into a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{}-{:02}-{:02} {:02}:{:02}", year, month, day, minutes / 60, minutes % 60)
+}
+
+/// A save read from a slot, migrated to the current format
+pub struct LoadedSave {
+    pub json: JsonValue,
+    /// Why the save of the slot couldn't be loaded, if its backup was loaded instead
+    pub broken_save: Option<LoadError>,
+}
+
+/// The save slots in one directory.
+///
+/// The metadata of each slot is kept after it was read, so the list of save slots doesn't decode
+/// every save file again each time it is shown.
+pub struct SaveDirectory {
+    path: PathBuf,
+    /// The metadata of the slots, with the modification time and size of the save file it was
+    /// read from
+    metadata_cache: RefCell<HashMap<usize, ((SystemTime, u64), SaveMetadata)>>,
+}
+
+impl SaveDirectory {
+    pub fn new(path: PathBuf) -> SaveDirectory {
+        SaveDirectory {
+            path,
+            metadata_cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// The save slots in the data directory of the user, or in the working directory if there
+    /// is none
+    pub fn in_user_data() -> SaveDirectory {
+        let data_directory = env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+            .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))
+            .unwrap_or_else(|| PathBuf::from("."));
+
+        SaveDirectory::new(data_directory.join(SAVE_DIRECTORY_NAME))
+    }
+
+    fn slot_path(&self, slot: usize) -> PathBuf {
+        self.path.join(format!("slot_{}.dat", slot + 1))
+    }
+
+    /// The previous save of a slot, used if the current one can't be loaded
+    fn backup_path(&self, slot: usize) -> PathBuf {
+        self.slot_path(slot).with_extension("bak")
+    }
+
+    /// The file a save is written to before it replaces the current save of a slot
+    fn temp_path(&self, slot: usize) -> PathBuf {
+        self.slot_path(slot).with_extension("tmp")
+    }
+
+    /// Save the game into its save slot.
+    ///
+    /// The game is written to a temporary file first, which then replaces the save file in a
+    /// single step. A crash while saving can't destroy the existing save this way. The previous
+    /// save is kept as backup, unless it can't be loaded.
+    pub fn save(&self, game: &Game) -> io::Result<()> {
+        fs::create_dir_all(&self.path)?;
+        self.metadata_cache.borrow_mut().remove(&game.slot);
+
+        let mut json = game.serialize();
+        json["version"] = SAVE_FORMAT_VERSION.into();
+        json["metadata"] = game.metadata().serialize();
+
+        let data = encode(&json, game.settings.save_format());
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .open(self.temp_path(game.slot))?;
+
+        file.write_all(data.as_slice())?;
+        file.sync_all()?;
+
+        let path = self.slot_path(game.slot);
+        if path.exists() {
+            // A damaged save never replaces the backup, which might be the only usable save left.
+            // Decoding it is enough to tell, it doesn't need to be migrated.
+            if read_file(&path).is_ok() {
+                fs::copy(&path, self.backup_path(game.slot))?;
+                OpenOptions::new().write(true).open(self.backup_path(game.slot))?.sync_all()?;
+            }
+        } else if self.backup_path(game.slot).exists() {
+            // The backup belongs to a game which was deleted from this slot
+            fs::remove_file(self.backup_path(game.slot))?;
+        }
+
+        fs::rename(self.temp_path(game.slot), path)?;
+        sync_directory(&self.path)
+    }
+
+    /// Read a save slot and migrate it to the current format. Falls back to the backup of the
+    /// slot if the save can't be loaded.
+    pub fn load(&self, slot: usize) -> Result<LoadedSave, LoadError> {
+
+        match load_file(&self.slot_path(slot)) {
+            Err(LoadError::NoSaveFile) => Err(LoadError::NoSaveFile),
+            Err(e) => match load_file(&self.backup_path(slot)) {
+                Ok(json) => Ok(LoadedSave { json, broken_save: Some(e) }),
+                Err(_) => Err(e)
+            },
+            Ok(json) => Ok(LoadedSave { json, broken_save: None })
+        }
+
+    }
+
+    /// Read the metadata of a save slot. Fails with `LoadError::NoSaveFile` if the slot is empty.
+    pub fn load_metadata(&self, slot: usize) -> Result<SaveMetadata, LoadError> {
+        let version = fs::metadata(self.slot_path(slot))
+            .and_then(|file| file.modified().map(|modified| (modified, file.len())))
+            .ok();
+
+        if let (Some(version), Some((cached, metadata))) = (version, self.metadata_cache.borrow().get(&slot)) {
+            if version == *cached {
+                return Ok(metadata.clone());
+            }
+        }
+
+        let metadata = SaveMetadata::deserialize(&self.load(slot)?.json["metadata"]).map_err(|e| e.context("metadata"))?;
+        if let Some(version) = version {
+            self.metadata_cache.borrow_mut().insert(slot, (version, metadata.clone()));
+        }
+        Ok(metadata)
+    }
+
+    /// Read the metadata of all save slots, in the order of the slots
+    pub fn list_slots(&self) -> Vec<Result<SaveMetadata, LoadError>> {
+        (0..SAVE_SLOT_COUNT).map(|slot| self.load_metadata(slot)).collect()
+    }
+
+    /// The first slot which doesn't contain a saved game
+    pub fn free_slot(&self) -> Option<usize> {
+        (0..SAVE_SLOT_COUNT).find(|slot| !self.slot_path(*slot).exists())
+    }
+
+    /// The slot which was saved last, ignoring all slots which can't be loaded
+    pub fn latest_slot(&self) -> Option<usize> {
+        self.list_slots().iter().enumerate()
+            .filter_map(|(slot, metadata)| metadata.as_ref().ok().map(|m| (slot, m.timestamp)))
+            .max_by_key(|&(_, timestamp)| timestamp)
+            .map(|(slot, _)| slot)
+    }
+
+    /// Move the save file of older versions from the working directory into the first free slot
+    pub fn import_legacy_save(&self) {
+        if !Path::new(LEGACY_SAVE_FILE_NAME).exists() {
+            return;
+        }
+
+        if let Some(slot) = self.free_slot() {
+            let imported = fs::create_dir_all(&self.path)
+                .and_then(|_| fs::copy(LEGACY_SAVE_FILE_NAME, self.slot_path(slot)));
+
+            if imported.is_ok() {
+                fs::remove_file(LEGACY_SAVE_FILE_NAME).ok();
+            }
+        }
+    }
+
+    /// Remove the save of a slot, together with its backup. An empty slot isn't an error.
+    pub fn delete(&self, slot: usize) -> io::Result<()> {
+        self.metadata_cache.borrow_mut().remove(&slot);
+
+        for path in &[self.slot_path(slot), self.backup_path(slot), self.temp_path(slot)] {
+            match fs::remove_file(path) {
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+                result => result?
+            }
+        }
+        Ok(())
+    }
+}
+
+fn load_file(path: &Path) -> Result<JsonValue, LoadError> {
+    migrate(read_file(path)?)
+}
+
+/// Read and decode a save file, without migrating it to the current format
+fn read_file(path: &Path) -> Result<JsonValue, LoadError> {
+
+    match OpenOptions::new().read(true).open(path) {
+        Ok(mut f) => deserialize(&mut f),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Err(LoadError::NoSaveFile),
+        Err(e) => Err(LoadError::Io(e))
+    }
+
+}
+
+/// Write the entries of a directory to the disk, so a file renamed inside of it stays renamed
+/// after a crash. Directories can't be opened like files on Windows, so it's skipped there.
+fn sync_directory(path: &Path) -> io::Result<()> {
+    if cfg!(unix) {
+        File::open(path)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn deserialize(file: &mut File) -> Result<JsonValue, LoadError> {
+    let mut data = vec![];
+    file.read_to_end(&mut data).map_err(LoadError::Io)?;
+
+    decode(&data)
+}
+
+fn encode(json: &JsonValue, format: SaveFormat) -> Vec<u8> {
+    match format {
+        SaveFormat::Json => json.dump().into_bytes(),
+        SaveFormat::Binary => binary::encode(json, false),
+        SaveFormat::CompressedBinary => binary::encode(json, true),
+    }
+}
+
+/// Decode a save file in any `SaveFormat`
+fn decode(data: &[u8]) -> Result<JsonValue, LoadError> {
+    if binary::is_binary(data) {
+        binary::decode(data)
+    } else {
+        let text = String::from_utf8_lossy(data);
+        json::parse(&text).map_err(LoadError::Parse)
+    }
+}
+
+/// Convert a save file from the binary format into indented JSON, or from JSON into the
+/// compressed binary format. The content is converted as it is, without any migration.
+///
+/// Returns the format of the written file.
+pub fn convert(input: &Path, output: &Path) -> Result<SaveFormat, LoadError> {
+    let mut data = vec![];
+    File::open(input).and_then(|mut f| f.read_to_end(&mut data)).map_err(LoadError::Io)?;
+
+    let json = decode(&data)?;
+
+    let (format, converted) = if binary::is_binary(&data) {
+        (SaveFormat::Json, json.pretty(2).into_bytes())
+    } else {
+        (SaveFormat::CompressedBinary, encode(&json, SaveFormat::CompressedBinary))
+    };
+
+    fs::write(output, converted).map_err(LoadError::Io)?;
+    Ok(format)
+}
+
+/// Upgrade a save file of any older version to the current format.
+///
+/// Save files written before the format was versioned count as version 1.
+pub fn migrate(mut json: JsonValue) -> Result<JsonValue, LoadError> {
+    let mut version = if json["version"].is_null() {
+        1
+    } else {
+        json["version"].read_u32()?
+    };
+
+    if version < 1 {
+        return Err(LoadError::invalid("a version of at least 1", &json["version"]).context("version"));
+    }
+    if version > SAVE_FORMAT_VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+
+    while version < SAVE_FORMAT_VERSION {
+        json = MIGRATIONS[version as usize - 1](json)?;
+        version += 1;
+    }
+
+    json["version"] = version.into();
+    Ok(json)
+}
+
+/// Version 2 stores the state of the RNG and of the id generator
+fn migrate_1_to_2(mut json: JsonValue) -> Result<JsonValue, LoadError> {
+    if json["rng"].is_null() {
+        json["rng"] = GameRng::from_random_seed().serialize();
+    }
+
+    if json["ecs"]["id_generator"].is_null() {
+        let mut ids = vec![];
+        for entity_json in json["ecs"]["entities"].members() {
+            ids.push(EntityId::deserialize(&entity_json["id"])?);
+        }
+        json["ecs"]["id_generator"] = IdGenerator::from_ids(&ids).serialize();
+    }
+
+    Ok(json)
+}
+
+/// Version 3 stores the metadata shown in the list of save slots
+fn migrate_2_to_3(mut json: JsonValue) -> Result<JsonValue, LoadError> {
+    if json["metadata"].is_null() {
+        let player_id = EntityId::deserialize(&json["ecs"]["player"])?;
+        let mut level = 1;
+
+        for entity_json in json["ecs"]["entities"].members() {
+            if EntityId::deserialize(&entity_json["id"])? != player_id {
+                continue;
+            }
+            for component_json in entity_json["components"].members() {
+                if component_json["type"] == "Level" {
+                    level = component_json["data"]["level"].read_u8()?;
+                }
+            }
+        }
+
+        json["metadata"] = SaveMetadata {
+            level,
+            floor_number: json["floor_number"].read_u8()?,
+            play_time: 0,
+            timestamp: 0,
+        }.serialize();
+    }
+
+    Ok(json)
+}
+
+/// Version 4 keeps all visited floors, and stores where stairs lead
+fn migrate_3_to_4(mut json: JsonValue) -> Result<JsonValue, LoadError> {
+    if json["floors"].is_null() {
+        json["floors"] = JsonValue::new_array();
+    }
+
+    // Older versions only had stairs leading down to the next floor
+    let destination = json["floor_number"].read_u8()?.saturating_add(1);
+
+    for entity_json in json["ecs"]["entities"].members_mut() {
+        for component_json in entity_json["components"].members_mut() {
+            if (component_json["type"] == "Stairs" || component_json["type"] == "Stair")
+                && component_json["data"]["direction"].is_null() {
+                component_json["data"] = object!(
+                    "direction" => "Down",
+                    "destination" => destination
+                );
+            }
+        }
+    }
+
+    Ok(json)
+}
+
+/// Version 5 stores the kind of each tile. Older maps only had walls and floor, stored as
+/// `[block_move, block_sight, explored]` per tile.
+fn migrate_4_to_5(mut json: JsonValue) -> Result<JsonValue, LoadError> {
+    fn migrate_map(map: &mut JsonValue) -> Result<(), LoadError> {
+        let width = map["width"].read_usize()?;
+        if width == 0 {
+            return Err(LoadError::invalid("a map width", &map["width"]));
+        }
+
+        let mut tiles = JsonValue::new_array();
+        let mut explored = JsonValue::new_array();
+
+        let old_tiles: Vec<&JsonValue> = map["tiles"].members().collect();
+        for row in old_tiles.chunks(width) {
+            let mut codes = String::new();
+            let mut explored_row = JsonValue::new_array();
+
+            for tile in row {
+                codes.push(if tile[0].read_bool()? { '#' } else { '.' });
+                explored_row.push(tile[2].read_bool()?).unwrap();
+            }
+            tiles.push(codes).unwrap();
+            explored.push(explored_row).unwrap();
+        }
+
+        map["tiles"] = tiles;
+        map["explored"] = explored;
+        Ok(())
+    }
+
+    migrate_map(&mut json["map"]).map_err(|e| e.context("map"))?;
+    for floor_json in json["floors"].members_mut() {
+        migrate_map(&mut floor_json["map"]).map_err(|e| e.context("floors"))?;
+    }
+
+    Ok(json)
+}
+
+/// Change the data of all components with a tag, on the current floor and on all other floors
+fn migrate_components(json: &mut JsonValue, tag: &str, migrate: &Fn(&mut JsonValue)) {
+    fn migrate_entities(entities: &mut JsonValue, tag: &str, migrate: &Fn(&mut JsonValue)) {
+        for entity_json in entities.members_mut() {
+            for component_json in entity_json["components"].members_mut() {
+                if component_json["type"] == tag {
+                    migrate(&mut component_json["data"]);
+                }
+            }
+        }
+    }
+
+    migrate_entities(&mut json["ecs"]["entities"], tag, migrate);
+    for floor_json in json["floors"].members_mut() {
+        migrate_entities(&mut floor_json["entities"], tag, migrate);
+    }
+}
+
+/// Version 6 gives every `Actor` a speed and energy. Everyone used to act once per turn.
+fn migrate_5_to_6(mut json: JsonValue) -> Result<JsonValue, LoadError> {
+    migrate_components(&mut json, "Actor", &|actor| {
+        if actor["speed"].is_null() {
+            actor["speed"] = ACTION_ENERGY.into();
+            actor["energy"] = ACTION_ENERGY.into();
+        }
+    });
+
+    Ok(json)
+}
+
+/// Version 7 stores timed status effects. Confusion used to turn a monster against the nearest
+/// other monster for good, so these monsters come to their senses and look for the player again.
+fn migrate_6_to_7(mut json: JsonValue) -> Result<JsonValue, LoadError> {
+    let player = json["ecs"]["player"].clone();
+    migrate_components(&mut json, "MonsterAi", &|ai| {
+        if ai["target"] != player {
+            ai["target"] = JsonValue::Null;
+            ai["chase_target"] = false.into();
+        }
+    });
+
+    Ok(json)
+}
+
+/// Version 8 stores how a monster fights. All monsters used to fight in melee.
+fn migrate_7_to_8(mut json: JsonValue) -> Result<JsonValue, LoadError> {
+    migrate_components(&mut json, "MonsterAi", &|ai| {
+        if ai["behavior"].is_null() {
+            ai["behavior"] = object!("type" => "Melee");
+        }
+    });
+
+    Ok(json)
+}
+
+/// Version 9 stores the morale of monsters. Monsters never used to flee.
+fn migrate_8_to_9(mut json: JsonValue) -> Result<JsonValue, LoadError> {
+    migrate_components(&mut json, "MonsterAi", &|ai| {
+        if ai["morale"].is_null() {
+            ai["morale"] = "Steady".into();
+        }
+    });
+
+    Ok(json)
+}
+
+/// Version 10 stores where monsters last saw their target. Monsters used to forget it at once.
+fn migrate_9_to_10(mut json: JsonValue) -> Result<JsonValue, LoadError> {
+    migrate_components(&mut json, "MonsterAi", &|ai| {
+        if ai["memory"].is_null() {
+            ai["memory"] = object!("type" => "Forgotten");
+        }
+    });
+
+    Ok(json)
+}
+
+
+pub trait Serialize {
+    fn serialize(&self) -> JsonValue;
+}
+
+pub trait Deserialize: Sized {
+    fn deserialize(json: &JsonValue) -> Result<Self, LoadError>;
+}
+
+/// Typed access to the values of a save file, which fails with a `LoadError` instead of
+/// panicking if a value is missing or has the wrong type.
+pub trait ReadJson {
+    fn read_u8(&self) -> Result<u8, LoadError>;
+    fn read_u32(&self) -> Result<u32, LoadError>;
+    fn read_u64(&self) -> Result<u64, LoadError>;
+    fn read_usize(&self) -> Result<usize, LoadError>;
+    fn read_i32(&self) -> Result<i32, LoadError>;
+    fn read_f32(&self) -> Result<f32, LoadError>;
+    fn read_bool(&self) -> Result<bool, LoadError>;
+    fn read_str(&self) -> Result<&str, LoadError>;
+}
+
+macro_rules! read_json {
+    ($name:ident, $convert:ident, $result:ty, $expected:expr) => {
+        fn $name(&self) -> Result<$result, LoadError> {
+            self.$convert().ok_or_else(|| LoadError::invalid($expected, self))
+        }
+    }
+}
+
+impl ReadJson for JsonValue {
+    read_json!(read_u8, as_u8, u8, "a number between 0 and 255");
+    read_json!(read_u32, as_u32, u32, "a positive number");
+    read_json!(read_u64, as_u64, u64, "a positive number");
+    read_json!(read_usize, as_usize, usize, "a positive number");
+    read_json!(read_i32, as_i32, i32, "a number");
+    read_json!(read_f32, as_f32, f32, "a decimal number");
+    read_json!(read_bool, as_bool, bool, "true or false");
+    read_json!(read_str, as_str, &str, "a text");
+}
+
+/// A new directory below the temporary directory of the system, which is removed again when it
+/// is dropped
+#[cfg(test)]
+pub struct TempDirectory {
+    pub path: PathBuf,
+}
+
+#[cfg(test)]
+impl TempDirectory {
+    pub fn new() -> TempDirectory {
+        use std::process;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let name = format!("roguelike-test-{}-{}", process::id(), COUNTER.fetch_add(1, Ordering::SeqCst));
+        TempDirectory { path: env::temp_dir().join(name) }
+    }
+
+    /// Save slots inside of the directory
+    pub fn saves(&self) -> SaveDirectory {
+        SaveDirectory::new(self.path.clone())
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDirectory {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.path).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecs::component::{AiBehavior, AiMemory, MonsterAi};
+    use game::simulation::Simulation;
+    use settings::Settings;
+    use game::input::InputAction;
+    use game::state::GameState;
+
+    fn metadata(floor_number: u8, timestamp: u64) -> SaveMetadata {
+        SaveMetadata { level: 1, floor_number, play_time: 0, timestamp }
+    }
+
+    /// Write a save file which only contains metadata into a slot
+    fn write_slot(saves: &SaveDirectory, slot: usize, metadata: &SaveMetadata) {
+        fs::create_dir_all(&saves.path).unwrap();
+        let json = object!("version" => SAVE_FORMAT_VERSION, "metadata" => metadata.serialize());
+        fs::write(saves.slot_path(slot), json.dump()).unwrap();
+    }
+
+    /// A save file like the first versions of the game wrote it, without a version
+    fn legacy_save() -> JsonValue {
+        object!(
+            "ecs" => object!(
+                "player" => 1,
+                "entities" => array![
+                    object!(
+                        "id" => 1,
+                        "components" => array![
+                            object!("type" => "Level", "data" => object!("level" => 3)),
+                            object!("type" => "Actor", "data" => object!("hp" => 30, "max_hp" => 30))
+                        ]
+                    )
+                ]
+            ),
+            "map" => object!(
+                "width" => 2,
+                "tiles" => array![array![true, true, false], array![false, false, true]]
+            ),
+            "floor_number" => 2
+        )
+    }
+
+    fn is_invalid_value(result: Result<JsonValue, LoadError>) -> bool {
+        match result { Err(LoadError::InvalidValue(_)) => true, _ => false }
+    }
+
+    #[test]
+    fn legacy_save_is_migrated_to_the_current_version() {
+        let json = migrate(legacy_save()).unwrap();
+
+        assert_eq!(json["version"], SAVE_FORMAT_VERSION);
+        assert_eq!(SaveMetadata::deserialize(&json["metadata"]).unwrap(), SaveMetadata {
+            level: 3, floor_number: 2, play_time: 0, timestamp: 0
+        });
+        assert!(!json["rng"].is_null());
+        assert!(!json["ecs"]["id_generator"].is_null());
+        assert_eq!(json["floors"], JsonValue::new_array());
+        assert_eq!(json["map"]["tiles"], array!["#."]);
+        assert_eq!(json["map"]["explored"], array![array![false, true]]);
+        assert_eq!(json["ecs"]["entities"][0]["components"][1]["data"]["speed"], ACTION_ENERGY);
+    }
+
+    /// A save file of `version` with a single monster on the current floor and one on another
+    fn save_with_monster(version: u32, ai: JsonValue) -> JsonValue {
+        let mut component = object!("type" => "MonsterAi");
+        component["data"] = ai;
+        let mut monster = object!("id" => array![2, 1], "components" => array![]);
+        monster["components"].push(component).unwrap();
+        let mut floor = object!("entities" => array![]);
+        floor["entities"].push(monster.clone()).unwrap();
+
+        let mut json = legacy_save();
+        json["version"] = version.into();
+        json["ecs"]["entities"].push(monster).unwrap();
+        json["floors"] = array![];
+        json["floors"].push(floor).unwrap();
+        json
+    }
+
+    fn migrated_monsters(json: JsonValue) -> Vec<MonsterAi> {
+        let json = migrate(json).unwrap();
+        let floor_monster = &json["floors"][0]["entities"][0]["components"][0]["data"];
+        let monster = &json["ecs"]["entities"][1]["components"][0]["data"];
+
+        vec![MonsterAi::deserialize(monster).unwrap(), MonsterAi::deserialize(floor_monster).unwrap()]
+    }
+
+    #[test]
+    fn confused_monsters_of_version_6_forget_their_target() {
+        let json = save_with_monster(6, object!("id" => array![2, 1], "target" => array![3, 1],
+                                                "chase_target" => true));
+
+        for ai in migrated_monsters(json) {
+            assert!(ai.has_no_target());
+        }
+    }
+
+    #[test]
+    fn monsters_of_version_7_fight_in_melee() {
+        let json = save_with_monster(7, object!("id" => array![2, 1], "target" => JsonValue::Null,
+                                                "chase_target" => false));
+
+        for ai in migrated_monsters(json) {
+            assert_eq!(ai.behavior, AiBehavior::Melee);
+        }
+    }
+
+    #[test]
+    fn monsters_of_version_8_are_steady() {
+        let json = save_with_monster(8, object!("id" => array![2, 1], "target" => JsonValue::Null,
+                                                "chase_target" => false, "behavior" => object!("type" => "Melee")));
+
+        for ai in migrated_monsters(json) {
+            assert!(!ai.is_fleeing());
+        }
+    }
+
+    #[test]
+    fn monsters_of_version_9_remember_nothing() {
+        let json = save_with_monster(9, object!("id" => array![2, 1], "target" => JsonValue::Null,
+                                                "chase_target" => false, "behavior" => object!("type" => "Melee"),
+                                                "morale" => "Fleeing"));
+
+        for ai in migrated_monsters(json) {
+            assert!(ai.is_fleeing());
+            assert_eq!(ai.memory(), AiMemory::Forgotten);
+        }
+    }
+
+    #[test]
+    fn current_version_is_left_as_it_is() {
+        let mut json = legacy_save();
+        json["version"] = SAVE_FORMAT_VERSION.into();
+
+        assert_eq!(migrate(json.clone()).unwrap(), json);
+    }
+
+    #[test]
+    fn invalid_versions_are_rejected() {
+        let with_version = |version: JsonValue| {
+            let mut json = legacy_save();
+            json["version"] = version;
+            migrate(json)
+        };
+
+        assert!(is_invalid_value(with_version(0.into())));
+        assert!(is_invalid_value(with_version("seven".into())));
+        assert!(is_invalid_value(with_version((-1).into())));
+        assert!(match with_version((SAVE_FORMAT_VERSION + 1).into()) {
+            Err(LoadError::UnsupportedVersion(version)) => version == SAVE_FORMAT_VERSION + 1,
+            _ => false
+        });
+    }
+
+    #[test]
+    fn legacy_save_file_is_migrated_when_loaded() {
+        let directory = TempDirectory::new();
+        let saves = directory.saves();
+        fs::create_dir_all(&saves.path).unwrap();
+        fs::write(saves.slot_path(0), legacy_save().dump()).unwrap();
+
+        let json = saves.load(0).unwrap().json;
+        assert_eq!(json["version"], SAVE_FORMAT_VERSION);
+        assert_eq!(saves.load_metadata(0).unwrap().level, 3);
+    }
+
+    #[test]
+    fn slots_are_listed_in_order() {
+        let directory = TempDirectory::new();
+        let saves = directory.saves();
+        write_slot(&saves, 0, &metadata(2, 100));
+        write_slot(&saves, 2, &metadata(4, 50));
+        fs::write(saves.slot_path(3), "not a save file").unwrap();
+
+        let slots = saves.list_slots();
+        assert_eq!(slots.len(), SAVE_SLOT_COUNT);
+        assert_eq!(slots[0].as_ref().unwrap(), &metadata(2, 100));
+        assert!(match slots[1] { Err(LoadError::NoSaveFile) => true, _ => false });
+        assert_eq!(slots[2].as_ref().unwrap(), &metadata(4, 50));
+        assert!(match slots[3] { Err(LoadError::Parse(_)) => true, _ => false });
+    }
+
+    #[test]
+    fn listed_slots_follow_changes() {
+        let directory = TempDirectory::new();
+        let saves = directory.saves();
+        write_slot(&saves, 0, &metadata(2, 100));
+        assert_eq!(saves.load_metadata(0).unwrap(), metadata(2, 100));
+
+        saves.delete(0).unwrap();
+        assert!(match saves.load_metadata(0) { Err(LoadError::NoSaveFile) => true, _ => false });
+    }
+
+    #[test]
+    fn free_slot_is_the_first_empty_slot() {
+        let directory = TempDirectory::new();
+        let saves = directory.saves();
+        assert_eq!(saves.free_slot(), Some(0));
+
+        write_slot(&saves, 0, &metadata(1, 1));
+        write_slot(&saves, 2, &metadata(1, 1));
+        assert_eq!(saves.free_slot(), Some(1));
+
+        (0..SAVE_SLOT_COUNT).for_each(|slot| write_slot(&saves, slot, &metadata(1, 1)));
+        assert_eq!(saves.free_slot(), None);
+    }
+
+    #[test]
+    fn latest_slot_skips_broken_saves() {
+        let directory = TempDirectory::new();
+        let saves = directory.saves();
+        assert_eq!(saves.latest_slot(), None);
+
+        write_slot(&saves, 0, &metadata(1, 300));
+        write_slot(&saves, 1, &metadata(1, 500));
+        write_slot(&saves, 3, &metadata(1, 100));
+        assert_eq!(saves.latest_slot(), Some(1));
+
+        fs::write(saves.slot_path(1), "not a save file").unwrap();
+        assert_eq!(saves.latest_slot(), Some(0));
+    }
+
+    #[test]
+    fn saves_replace_the_slot_and_keep_a_backup() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+        let saves = sim.save_directory().saves();
+
+        // Every new floor is saved
+        sim.descend();
+        assert_eq!(saves.load_metadata(0).unwrap().floor_number, 2);
+        assert!(!saves.backup_path(0).exists());
+        assert!(!saves.temp_path(0).exists());
+
+        sim.descend();
+        assert_eq!(saves.load_metadata(0).unwrap().floor_number, 3);
+        let backup = load_file(&saves.backup_path(0)).unwrap();
+        assert_eq!(SaveMetadata::deserialize(&backup["metadata"]).unwrap().floor_number, 2);
+        assert!(!saves.temp_path(0).exists());
+    }
+
+    #[test]
+    fn backup_is_loaded_if_the_save_is_broken() {
+        let directory = TempDirectory::new();
+        let saves = directory.saves();
+        write_slot(&saves, 0, &metadata(2, 100));
+        fs::rename(saves.slot_path(0), saves.backup_path(0)).unwrap();
+        fs::write(saves.slot_path(0), "not a save file").unwrap();
+
+        let save = saves.load(0).unwrap();
+        assert!(match save.broken_save { Some(LoadError::Parse(_)) => true, _ => false });
+        assert_eq!(SaveMetadata::deserialize(&save.json["metadata"]).unwrap(), metadata(2, 100));
+        assert_eq!(saves.load_metadata(0).unwrap(), metadata(2, 100));
+
+        // Without a usable backup, the error of the save itself is reported
+        fs::write(saves.backup_path(0), "not a save file either").unwrap();
+        assert!(match saves.load(0) { Err(LoadError::Parse(_)) => true, _ => false });
+    }
+
+    #[test]
+    fn damaged_save_is_replaced_by_its_backup_when_continuing() {
+        let settings = Settings::new();
+        let mut sim = Simulation::without_monsters(&settings);
+        let saves = sim.save_directory().saves();
+        sim.descend();
+        sim.descend();
+
+        sim.send_all(vec![InputAction::Exit, InputAction::SelectOption('a')]);
+        assert_eq!(sim.state(), GameState::MainMenu);
+        fs::write(saves.slot_path(0), "not a save file").unwrap();
+
+        sim.send(InputAction::SelectOption('c'));
+        assert_eq!(sim.state(), GameState::PlayersTurn);
+        assert_eq!(sim.floor_number(), 3);
+        assert!(sim.messages().iter().any(|m| m.starts_with("The saved game is damaged")));
+
+        // The damaged save doesn't overwrite the backup it was replaced by
+        sim.descend();
+        assert_eq!(saves.load_metadata(0).unwrap().floor_number, 4);
+        let backup = load_file(&saves.backup_path(0)).unwrap();
+        assert_eq!(SaveMetadata::deserialize(&backup["metadata"]).unwrap().floor_number, 3);
+    }
+
+    #[test]
+    fn deleting_removes_the_backup_as_well() {
+        let directory = TempDirectory::new();
+        let saves = directory.saves();
+        write_slot(&saves, 0, &metadata(2, 100));
+        fs::copy(saves.slot_path(0), saves.backup_path(0)).unwrap();
+
+        saves.delete(0).unwrap();
+        assert!(!saves.slot_path(0).exists());
+        assert!(!saves.backup_path(0).exists());
+
+        // An empty slot is deleted already
+        saves.delete(0).unwrap();
+    }
+
+    #[test]
+    fn failed_deletion_is_reported() {
+        let directory = TempDirectory::new();
+        let saves = directory.saves();
+        fs::create_dir_all(saves.slot_path(0)).unwrap();
+
+        assert!(saves.delete(0).is_err());
+    }
+}
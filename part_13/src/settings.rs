@@ -4,6 +4,9 @@ use tcod::FontType;
 use core::borrow::Borrow;
 use std::borrow::Cow;
 
+use savegame::SaveFormat;
+use map_objects::generator::MapGeneratorKind;
+
 pub struct Settings {
     screen_width: i32,
     screen_height: i32,
@@ -20,6 +23,7 @@ pub struct Settings {
     message_x_offset: i32,
     message_y_offset: i32,
 
+    /// The size of the whole map, which can be larger than the part shown on the screen
     map_width: i32,
     map_height: i32,
 
@@ -34,14 +38,54 @@ pub struct Settings {
     /// Maximum number of attempts to generate the minimum room number
     max_attempts_min_rooms: i32,
 
+    /// The generator for each floor, as pairs of generator and the first floor it is used on
+    map_generators: Vec<(MapGeneratorKind, u8)>,
+
+    /// Chance in percent that a tile of a cave starts as wall
+    cave_wall_chance: u32,
+    /// Number of times the noise of a cave is smoothed
+    cave_smoothing_steps: u32,
+
+    /// Minimum width and height of the parts a map is split into by the binary space partition
+    bsp_min_leaf_size: i32,
+
+    /// Percentage of the map which is dug out by the drunkard's walk
+    drunkard_floor_percent: u32,
+    /// Number of steps of a single walker of the drunkard's walk
+    drunkard_walk_length: u32,
+
+    /// Chance in percent that the opening of a room gets a door
+    door_chance: u32,
+    /// Number of pools of shallow water, lava and of chasms on a floor
+    water_pools: Vec<(i32, i32)>,
+    lava_pools: Vec<(i32, i32)>,
+    chasms: Vec<(i32, i32)>,
+    /// Number of walls between two passages which can be broken through
+    cracked_walls: Vec<(i32, i32)>,
+
     fov_algorithm: FovAlgorithm,
     fov_light_walls: bool,
     fov_radius: i32,
 
     ai_distance: f64,
+    /// Monsters flee once their hp drop to this percentage of their max hp
+    flee_hp_percent: u32,
+    /// Fleeing monsters regain their courage once they are healed to this percentage
+    courage_hp_percent: u32,
+    /// Number of turns a monster searches for its target after losing sight of it
+    monster_search_turns: u32,
 
     max_monsters_per_room: Vec<(i32, i32)>,
     max_items_per_room: Vec<(i32, i32)>,
+    /// Number of vaults which are placed on a floor, if there is enough room for them
+    vaults_per_floor: Vec<(i32, i32)>,
+
+    /// Number of turns after which the game is saved automatically. `0` saves only when
+    /// entering a new floor.
+    autosave_interval: u32,
+
+    /// The format new save files are written in
+    save_format: SaveFormat,
 }
 
 impl Settings {
@@ -65,12 +109,31 @@ impl Settings {
             min_rooms: 10,
             max_attempts_room: 10,
             max_attempts_min_rooms: 300,
+            map_generators: vec![(MapGeneratorKind::Rooms, 1), (MapGeneratorKind::BinarySpacePartition, 2),
+                                 (MapGeneratorKind::Caves, 3), (MapGeneratorKind::DrunkardsWalk, 4),
+                                 (MapGeneratorKind::Rooms, 5)],
+            cave_wall_chance: 45,
+            cave_smoothing_steps: 5,
+            bsp_min_leaf_size: 10,
+            drunkard_floor_percent: 40,
+            drunkard_walk_length: 200,
+            door_chance: 50,
+            water_pools: vec![(1,1),(2,3)],
+            lava_pools: vec![(1,3),(2,6)],
+            chasms: vec![(1,4)],
+            cracked_walls: vec![(2,1),(4,5)],
             fov_algorithm: FovAlgorithm::Basic,
             fov_light_walls: true,
             fov_radius: 10,
             ai_distance: 12.0,
+            flee_hp_percent: 25,
+            courage_hp_percent: 50,
+            monster_search_turns: 5,
             max_monsters_per_room: vec![(2,1),(3,4),(5,6)],
             max_items_per_room: vec![(10,1),(2,4)],
+            vaults_per_floor: vec![(1,2),(2,5)],
+            autosave_interval: 100,
+            save_format: SaveFormat::CompressedBinary,
         }
     }
 
@@ -134,6 +197,11 @@ impl Settings {
         (self.message_x(), self.message_y_offset)
     }
 
+    /// The size of the part of the map shown on the screen, which is everything above the panel
+    pub fn viewport_dimensions(&self) -> (i32, i32) {
+        (self.screen_width, self.panel_y())
+    }
+
     pub fn map_width(&self) -> i32 {
         self.map_width
     }
@@ -164,6 +232,55 @@ impl Settings {
     }
 
 
+    /// The generator used for a floor
+    pub fn map_generator(&self, floor_number: u8) -> MapGeneratorKind {
+        self.map_generators.iter()
+            .filter(|(_, first_floor)| floor_number >= *first_floor)
+            .map(|(generator, _)| *generator)
+            .last()
+            .unwrap_or(MapGeneratorKind::Rooms)
+    }
+
+    pub fn cave_wall_chance(&self) -> u32 {
+        self.cave_wall_chance
+    }
+
+    pub fn cave_smoothing_steps(&self) -> u32 {
+        self.cave_smoothing_steps
+    }
+
+    pub fn bsp_min_leaf_size(&self) -> i32 {
+        self.bsp_min_leaf_size
+    }
+
+    pub fn drunkard_floor_percent(&self) -> u32 {
+        self.drunkard_floor_percent
+    }
+
+    pub fn drunkard_walk_length(&self) -> u32 {
+        self.drunkard_walk_length
+    }
+
+    pub fn door_chance(&self) -> u32 {
+        self.door_chance
+    }
+
+    pub fn water_pools(&self) -> Cow<Vec<(i32, i32)>> {
+        Cow::Borrowed(&self.water_pools)
+    }
+
+    pub fn lava_pools(&self) -> Cow<Vec<(i32, i32)>> {
+        Cow::Borrowed(&self.lava_pools)
+    }
+
+    pub fn chasms(&self) -> Cow<Vec<(i32, i32)>> {
+        Cow::Borrowed(&self.chasms)
+    }
+
+    pub fn cracked_walls(&self) -> Cow<Vec<(i32, i32)>> {
+        Cow::Borrowed(&self.cracked_walls)
+    }
+
     pub fn fov_algorithm(&self) -> FovAlgorithm {
         self.fov_algorithm
     }
@@ -180,6 +297,18 @@ impl Settings {
         self.ai_distance
     }
 
+    pub fn flee_hp_percent(&self) -> u32 {
+        self.flee_hp_percent
+    }
+
+    pub fn courage_hp_percent(&self) -> u32 {
+        self.courage_hp_percent
+    }
+
+    pub fn monster_search_turns(&self) -> u32 {
+        self.monster_search_turns
+    }
+
     pub fn max_monsters_per_room(&self) -> Cow<Vec<(i32, i32)>> {
         Cow::Borrowed(&self.max_monsters_per_room)
     }
@@ -187,4 +316,20 @@ impl Settings {
     pub fn max_items_per_room(&self) -> Cow<Vec<(i32, i32)>> {
         Cow::Borrowed(&self.max_items_per_room)
     }
+
+    pub fn vaults_per_floor(&self) -> Cow<Vec<(i32, i32)>> {
+        Cow::Borrowed(&self.vaults_per_floor)
+    }
+
+    pub fn autosave_interval(&self) -> u32 {
+        self.autosave_interval
+    }
+
+    pub fn save_format(&self) -> SaveFormat {
+        self.save_format
+    }
+
+    pub fn set_save_format(&mut self, save_format: SaveFormat) {
+        self.save_format = save_format;
+    }
 }
\ No newline at end of file
@@ -0,0 +1,331 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use tcod::input::{Event, EventFlags, Key, KeyCode, KEY_PRESS};
+use tcod::Color;
+use tcod::BackgroundFlag;
+use tcod::TextAlignment;
+
+use settings::Settings;
+use terminal::{Terminal, HeadlessTerminal};
+use terminal::headless::Cell;
+
+/// Time to wait for the rest of an escape sequence before a single escape counts as a key press
+const ESCAPE_SEQUENCE_TIMEOUT: Duration = Duration::from_millis(10);
+/// Time to wait for input before the next frame is drawn
+const FRAME_TIME: Duration = Duration::from_millis(16);
+
+/// Turns the bytes read from stdin into key presses
+struct KeyReader {
+    input: Receiver<u8>,
+    /// A byte which was read ahead, but belongs to the next key press
+    pending: Option<u8>,
+}
+
+impl KeyReader {
+    fn new(input: Receiver<u8>) -> KeyReader {
+        KeyReader { input, pending: None }
+    }
+
+    fn next_byte(&mut self, timeout: Duration) -> Result<u8, RecvTimeoutError> {
+        match self.pending.take() {
+            Some(byte) => Ok(byte),
+            None => self.input.recv_timeout(timeout)
+        }
+    }
+
+    /// Translate the bytes of a single key press into a tcod `Key`, so the input is handled
+    /// exactly like the input of the tcod window. Escape sequences of keys the game doesn't use
+    /// are skipped as a whole.
+    fn read_key(&mut self, byte: u8) -> Option<Key> {
+        match byte {
+            0x1b => self.read_escape_sequence(),
+            b'\r' | b'\n' => Some(key(KeyCode::Enter, '\0')),
+            0x7f | 0x08 => Some(key(KeyCode::Backspace, '\0')),
+            b' '..=b'~' => Some(key(KeyCode::Char, byte as char)),
+            _ => None
+        }
+    }
+
+    fn read_escape_sequence(&mut self) -> Option<Key> {
+        match self.input.recv_timeout(ESCAPE_SEQUENCE_TIMEOUT) {
+            // Control Sequence Introducer: parameters and intermediate bytes, up to a final byte
+            Ok(b'[') => {
+                loop {
+                    match self.input.recv_timeout(ESCAPE_SEQUENCE_TIMEOUT) {
+                        Ok(byte @ 0x40..=0x7e) => return arrow_key(byte),
+                        Ok(0x20..=0x3f) => (),
+                        Ok(byte) => {
+                            self.pending = Some(byte);
+                            return None;
+                        }
+                        Err(_) => return None
+                    }
+                }
+            }
+            // Single Shift Three, followed by exactly one byte
+            Ok(b'O') => {
+                match self.input.recv_timeout(ESCAPE_SEQUENCE_TIMEOUT) {
+                    Ok(byte) => arrow_key(byte),
+                    Err(_) => None
+                }
+            }
+            // No escape sequence, so the byte is the next key press
+            Ok(byte) => {
+                self.pending = Some(byte);
+                Some(key(KeyCode::Escape, '\0'))
+            }
+            Err(_) => Some(key(KeyCode::Escape, '\0'))
+        }
+    }
+}
+
+fn key(code: KeyCode, printable: char) -> Key {
+    Key { code, printable, pressed: true, ..Default::default() }
+}
+
+/// The arrow key of the final byte of an escape sequence
+fn arrow_key(byte: u8) -> Option<Key> {
+    match byte {
+        b'A' => Some(key(KeyCode::Up, '\0')),
+        b'B' => Some(key(KeyCode::Down, '\0')),
+        b'C' => Some(key(KeyCode::Right, '\0')),
+        b'D' => Some(key(KeyCode::Left, '\0')),
+        _ => None
+    }
+}
+
+/// A `Terminal` which draws into the text terminal the game was started from, using ANSI escape
+/// codes, and reads the keyboard from stdin. Useful when no window can be opened, e.g. over SSH.
+///
+/// All drawing goes into an in-memory grid first. On `flush`, only the cells which changed since
+/// the last flush are written to the terminal.
+pub struct AnsiTerminal {
+    grid: HeadlessTerminal,
+    /// The cells as they are currently shown, or `None` if the screen needs to be redrawn
+    shown: Option<Vec<Cell>>,
+    keys: KeyReader,
+    /// The terminal settings before the game started, to restore them on exit
+    saved_mode: Option<String>,
+    closed: bool,
+}
+
+impl AnsiTerminal {
+    pub fn new(settings: &Settings) -> AnsiTerminal {
+        let saved_mode = Self::stty(&["-g"]).map(|mode| mode.trim().to_string());
+        Self::stty(&["raw", "-echo"]);
+
+        // stdin can't be read without blocking, so it is read on a separate thread
+        let (sender, input) = mpsc::channel();
+        thread::spawn(move || {
+            for byte in io::stdin().bytes() {
+                match byte {
+                    Ok(byte) => if sender.send(byte).is_err() { break },
+                    Err(_) => break
+                }
+            }
+        });
+
+        // Hide the cursor and clear the screen
+        print!("\x1b[?25l\x1b[2J");
+
+        AnsiTerminal {
+            grid: HeadlessTerminal::new(settings.screen_width(), settings.screen_height()),
+            shown: None,
+            keys: KeyReader::new(input),
+            saved_mode,
+            closed: false,
+        }
+    }
+
+    /// Run `stty` on the terminal of the game and return its output
+    fn stty(args: &[&str]) -> Option<String> {
+        Command::new("stty")
+            .args(args)
+            .stdin(Stdio::inherit())
+            .output()
+            .ok()
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+    }
+
+    fn color_code(color: Color) -> String {
+        format!("{};{};{}", color.r, color.g, color.b)
+    }
+}
+
+impl Drop for AnsiTerminal {
+    fn drop(&mut self) {
+        // Reset the colors, show the cursor and clear the screen
+        print!("\x1b[0m\x1b[?25h\x1b[2J\x1b[H");
+        io::stdout().flush().ok();
+
+        match self.saved_mode {
+            Some(ref mode) => Self::stty(&[mode]),
+            None => Self::stty(&["sane"])
+        };
+    }
+}
+
+impl Terminal for AnsiTerminal {
+    fn width(&self) -> i32 {
+        self.grid.width()
+    }
+
+    fn height(&self) -> i32 {
+        self.grid.height()
+    }
+
+    fn clear(&mut self) {
+        self.grid.clear();
+    }
+
+    fn set_default_foreground(&mut self, color: Color) {
+        self.grid.set_default_foreground(color);
+    }
+
+    fn set_default_background(&mut self, color: Color) {
+        self.grid.set_default_background(color);
+    }
+
+    fn put_char(&mut self, x: i32, y: i32, glyph: char, flag: BackgroundFlag) {
+        self.grid.put_char(x, y, glyph, flag);
+    }
+
+    fn set_char_background(&mut self, x: i32, y: i32, color: Color, flag: BackgroundFlag) {
+        self.grid.set_char_background(x, y, color, flag);
+    }
+
+    fn rect(&mut self, x: i32, y: i32, width: i32, height: i32, clear: bool, flag: BackgroundFlag) {
+        self.grid.rect(x, y, width, height, clear, flag);
+    }
+
+    fn print(&mut self, x: i32, y: i32, alignment: TextAlignment, text: &str) {
+        self.grid.print(x, y, alignment, text);
+    }
+
+    fn print_rect(&mut self, x: i32, y: i32, width: i32, height: i32, alignment: TextAlignment, text: &str) {
+        self.grid.print_rect(x, y, width, height, alignment, text);
+    }
+
+    fn get_height_rect(&self, width: i32, text: &str) -> i32 {
+        self.grid.get_height_rect(width, text)
+    }
+
+    fn draw_image(&mut self, _path: &str) {}
+
+    fn flush(&mut self) {
+        let mut output = String::new();
+        let mut shown = vec![];
+
+        for y in 0..self.grid.height() {
+            for x in 0..self.grid.width() {
+                let cell = *self.grid.cell(x, y).unwrap();
+                let index = shown.len();
+
+                let changed = match self.shown {
+                    Some(ref cells) => cells[index] != cell,
+                    None => true
+                };
+
+                if changed {
+                    let glyph = if cell.glyph == '\0' { ' ' } else { cell.glyph };
+                    output += &format!("\x1b[{};{}H\x1b[38;2;{}m\x1b[48;2;{}m{}",
+                                       y + 1, x + 1,
+                                       Self::color_code(cell.foreground),
+                                       Self::color_code(cell.background),
+                                       glyph);
+                }
+                shown.push(cell);
+            }
+        }
+
+        self.shown = Some(shown);
+
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        stdout.write_all(output.as_bytes()).ok();
+        stdout.flush().ok();
+    }
+
+    fn poll_event(&mut self) -> Option<(EventFlags, Event)> {
+        let byte = match self.keys.next_byte(FRAME_TIME) {
+            Ok(byte) => byte,
+            Err(RecvTimeoutError::Timeout) => return None,
+            Err(RecvTimeoutError::Disconnected) => {
+                self.closed = true;
+                return None;
+            }
+        };
+
+        // Ctrl+C doesn't send a signal in raw mode, so it closes the game instead
+        if byte == 0x03 {
+            self.closed = true;
+            return None;
+        }
+
+        self.keys.read_key(byte).map(|key| (KEY_PRESS, Event::Key(key)))
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn is_fullscreen(&self) -> bool {
+        false
+    }
+
+    fn set_fullscreen(&mut self, _fullscreen: bool) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decode all key presses of the bytes which arrive on stdin
+    fn decode(bytes: &[u8]) -> Vec<(KeyCode, char)> {
+        let (sender, input) = mpsc::channel();
+        bytes.iter().for_each(|byte| sender.send(*byte).unwrap());
+
+        let mut keys = KeyReader::new(input);
+        let mut decoded = vec![];
+        while let Ok(byte) = keys.next_byte(ESCAPE_SEQUENCE_TIMEOUT) {
+            if let Some(key) = keys.read_key(byte) {
+                decoded.push((key.code, key.printable));
+            }
+        }
+        decoded
+    }
+
+    #[test]
+    fn plain_keys_are_decoded() {
+        assert_eq!(decode(b"g>\r\x7f"), vec![
+            (KeyCode::Char, 'g'), (KeyCode::Char, '>'), (KeyCode::Enter, '\0'), (KeyCode::Backspace, '\0')
+        ]);
+    }
+
+    #[test]
+    fn arrow_keys_are_decoded() {
+        assert_eq!(decode(b"\x1b[A\x1bOB\x1b[1;2C\x1b[D"), vec![
+            (KeyCode::Up, '\0'), (KeyCode::Down, '\0'), (KeyCode::Right, '\0'), (KeyCode::Left, '\0')
+        ]);
+    }
+
+    #[test]
+    fn unknown_sequences_are_skipped_as_a_whole() {
+        // F5 and Page Up, followed by a key press
+        assert_eq!(decode(b"\x1b[15~\x1b[5~i"), vec![(KeyCode::Char, 'i')]);
+        assert_eq!(decode(b"\x1bOPi"), vec![(KeyCode::Char, 'i')]);
+    }
+
+    #[test]
+    fn single_escape_is_a_key_press() {
+        assert_eq!(decode(b"\x1b"), vec![(KeyCode::Escape, '\0')]);
+
+        // The key after the escape isn't lost, e.g. when Alt is held
+        assert_eq!(decode(b"\x1bi"), vec![(KeyCode::Escape, '\0'), (KeyCode::Char, 'i')]);
+        assert_eq!(decode(b"\x1b\x1b[A"), vec![(KeyCode::Escape, '\0'), (KeyCode::Up, '\0')]);
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::VecDeque;
+
+use tcod::input::{Event, EventFlags};
+use tcod::Color;
+use tcod::colors;
+use tcod::BackgroundFlag;
+use tcod::TextAlignment;
+
+use textwrap::wrap;
+
+use terminal::Terminal;
+
+/// A single character cell of a `HeadlessTerminal`
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Cell {
+    pub glyph: char,
+    pub foreground: Color,
+    pub background: Color,
+}
+
+impl Cell {
+    fn empty(background: Color) -> Cell {
+        Cell {
+            glyph: ' ',
+            foreground: colors::WHITE,
+            background,
+        }
+    }
+}
+
+/// A `Terminal` which keeps all cells in memory instead of showing them in a window.
+///
+/// The input is taken from a queue of events. Once all queued events were handled, the terminal
+/// counts as closed, so a game running on it always comes to an end.
+pub struct HeadlessTerminal {
+    width: i32,
+    height: i32,
+    cells: Vec<Cell>,
+    foreground: Color,
+    background: Color,
+    events: VecDeque<(EventFlags, Event)>,
+    fullscreen: bool,
+}
+
+impl HeadlessTerminal {
+    pub fn new(width: i32, height: i32) -> HeadlessTerminal {
+        HeadlessTerminal {
+            width,
+            height,
+            cells: vec![Cell::empty(colors::BLACK); (width * height) as usize],
+            foreground: colors::WHITE,
+            background: colors::BLACK,
+            events: VecDeque::new(),
+            fullscreen: false,
+        }
+    }
+
+    /// Add an event to the end of the input queue
+    #[cfg(test)]
+    pub fn push_event(&mut self, event: (EventFlags, Event)) {
+        self.events.push_back(event);
+    }
+
+    /// Get a cell, or `None` if the position is outside of the terminal
+    pub fn cell(&self, x: i32, y: i32) -> Option<&Cell> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            None
+        } else {
+            self.cells.get((y * self.width + x) as usize)
+        }
+    }
+
+    fn cell_mut(&mut self, x: i32, y: i32) -> Option<&mut Cell> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            None
+        } else {
+            self.cells.get_mut((y * self.width + x) as usize)
+        }
+    }
+
+    /// The characters of a single row
+    #[cfg(test)]
+    pub fn row(&self, y: i32) -> String {
+        (0..self.width).filter_map(|x| self.cell(x, y)).map(|c| c.glyph).collect()
+    }
+
+    /// The characters of all rows, separated by line breaks
+    #[cfg(test)]
+    pub fn text(&self) -> String {
+        (0..self.height).map(|y| self.row(y)).collect::<Vec<String>>().join("\n")
+    }
+
+    fn set_background(cell: &mut Cell, color: Color, flag: BackgroundFlag) {
+        match flag {
+            BackgroundFlag::None => (),
+            _ => cell.background = color
+        }
+    }
+}
+
+impl Terminal for HeadlessTerminal {
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn clear(&mut self) {
+        let background = self.background;
+        self.cells.iter_mut().for_each(|c| *c = Cell::empty(background));
+    }
+
+    fn set_default_foreground(&mut self, color: Color) {
+        self.foreground = color;
+    }
+
+    fn set_default_background(&mut self, color: Color) {
+        self.background = color;
+    }
+
+    fn put_char(&mut self, x: i32, y: i32, glyph: char, flag: BackgroundFlag) {
+        let (foreground, background) = (self.foreground, self.background);
+        if let Some(cell) = self.cell_mut(x, y) {
+            cell.glyph = glyph;
+            cell.foreground = foreground;
+            Self::set_background(cell, background, flag);
+        }
+    }
+
+    fn set_char_background(&mut self, x: i32, y: i32, color: Color, flag: BackgroundFlag) {
+        if let Some(cell) = self.cell_mut(x, y) {
+            Self::set_background(cell, color, flag);
+        }
+    }
+
+    fn rect(&mut self, x: i32, y: i32, width: i32, height: i32, clear: bool, flag: BackgroundFlag) {
+        let background = self.background;
+        for cell_x in x..x + width {
+            for cell_y in y..y + height {
+                if let Some(cell) = self.cell_mut(cell_x, cell_y) {
+                    if clear {
+                        cell.glyph = ' ';
+                    }
+                    Self::set_background(cell, background, flag);
+                }
+            }
+        }
+    }
+
+    fn print(&mut self, x: i32, y: i32, alignment: TextAlignment, text: &str) {
+        let length = text.chars().count() as i32;
+        let start = match alignment {
+            TextAlignment::Left => x,
+            TextAlignment::Center => x - length / 2,
+            TextAlignment::Right => x - length + 1,
+        };
+
+        for (i, glyph) in text.chars().enumerate() {
+            self.put_char(start + i as i32, y, glyph, BackgroundFlag::None);
+        }
+    }
+
+    fn print_rect(&mut self, x: i32, y: i32, width: i32, height: i32, alignment: TextAlignment, text: &str) {
+        for (i, line) in wrap(text, width as usize).iter().enumerate().take(height as usize) {
+            self.print(x, y + i as i32, alignment, line);
+        }
+    }
+
+    fn get_height_rect(&self, width: i32, text: &str) -> i32 {
+        wrap(text, width as usize).len() as i32
+    }
+
+    fn draw_image(&mut self, _path: &str) {}
+
+    fn flush(&mut self) {}
+
+    fn poll_event(&mut self) -> Option<(EventFlags, Event)> {
+        self.events.pop_front()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    fn is_fullscreen(&self) -> bool {
+        self.fullscreen
+    }
+
+    fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.fullscreen = fullscreen;
+    }
+}
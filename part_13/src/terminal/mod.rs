@@ -0,0 +1,138 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use tcod::Color;
+use tcod::BackgroundFlag;
+use tcod::TextAlignment;
+use tcod::input::{Event, EventFlags};
+
+pub mod tcod_terminal;
+pub mod headless;
+pub mod ansi;
+
+pub use self::tcod_terminal::TcodTerminal;
+pub use self::headless::HeadlessTerminal;
+pub use self::ansi::AnsiTerminal;
+
+/// A grid of character cells the game is drawn on, and which provides the input of the player.
+///
+/// All coordinates are absolute cell positions. The game runs on a `TcodTerminal` which opens a
+/// window, on an `AnsiTerminal` inside a text terminal, or on a `HeadlessTerminal`, which keeps
+/// the cells in memory.
+pub trait Terminal {
+    fn width(&self) -> i32;
+
+    fn height(&self) -> i32;
+
+    /// Clear all cells, using the default background color
+    fn clear(&mut self);
+
+    fn set_default_foreground(&mut self, color: Color);
+
+    fn set_default_background(&mut self, color: Color);
+
+    /// Draw a character in the default foreground color
+    fn put_char(&mut self, x: i32, y: i32, glyph: char, flag: BackgroundFlag);
+
+    fn set_char_background(&mut self, x: i32, y: i32, color: Color, flag: BackgroundFlag);
+
+    /// Fill a rectangle with the default background color. If `clear` is set, the characters
+    /// inside the rectangle are removed as well.
+    fn rect(&mut self, x: i32, y: i32, width: i32, height: i32, clear: bool, flag: BackgroundFlag);
+
+    /// Print a single line of text in the default foreground color
+    fn print(&mut self, x: i32, y: i32, alignment: TextAlignment, text: &str);
+
+    /// Print a text, wrapped into a rectangle
+    fn print_rect(&mut self, x: i32, y: i32, width: i32, height: i32, alignment: TextAlignment, text: &str);
+
+    /// The number of lines a text needs if it gets wrapped to the given width
+    fn get_height_rect(&self, width: i32, text: &str) -> i32;
+
+    /// Draw an image over the whole terminal. Backends which can't show images ignore this.
+    fn draw_image(&mut self, path: &str);
+
+    /// Show everything which was drawn since the last flush
+    fn flush(&mut self);
+
+    /// Get the next input event, if there is one
+    fn poll_event(&mut self) -> Option<(EventFlags, Event)>;
+
+    /// Check if the terminal was closed, which ends the game
+    fn is_closed(&self) -> bool;
+
+    fn is_fullscreen(&self) -> bool;
+
+    fn set_fullscreen(&mut self, fullscreen: bool);
+}
+
+/// A shared `Terminal`, so the caller can still look at it while the game runs on it
+impl<T: Terminal> Terminal for Rc<RefCell<T>> {
+    fn width(&self) -> i32 {
+        self.borrow().width()
+    }
+
+    fn height(&self) -> i32 {
+        self.borrow().height()
+    }
+
+    fn clear(&mut self) {
+        self.borrow_mut().clear()
+    }
+
+    fn set_default_foreground(&mut self, color: Color) {
+        self.borrow_mut().set_default_foreground(color)
+    }
+
+    fn set_default_background(&mut self, color: Color) {
+        self.borrow_mut().set_default_background(color)
+    }
+
+    fn put_char(&mut self, x: i32, y: i32, glyph: char, flag: BackgroundFlag) {
+        self.borrow_mut().put_char(x, y, glyph, flag)
+    }
+
+    fn set_char_background(&mut self, x: i32, y: i32, color: Color, flag: BackgroundFlag) {
+        self.borrow_mut().set_char_background(x, y, color, flag)
+    }
+
+    fn rect(&mut self, x: i32, y: i32, width: i32, height: i32, clear: bool, flag: BackgroundFlag) {
+        self.borrow_mut().rect(x, y, width, height, clear, flag)
+    }
+
+    fn print(&mut self, x: i32, y: i32, alignment: TextAlignment, text: &str) {
+        self.borrow_mut().print(x, y, alignment, text)
+    }
+
+    fn print_rect(&mut self, x: i32, y: i32, width: i32, height: i32, alignment: TextAlignment, text: &str) {
+        self.borrow_mut().print_rect(x, y, width, height, alignment, text)
+    }
+
+    fn get_height_rect(&self, width: i32, text: &str) -> i32 {
+        self.borrow().get_height_rect(width, text)
+    }
+
+    fn draw_image(&mut self, path: &str) {
+        self.borrow_mut().draw_image(path)
+    }
+
+    fn flush(&mut self) {
+        self.borrow_mut().flush()
+    }
+
+    fn poll_event(&mut self) -> Option<(EventFlags, Event)> {
+        self.borrow_mut().poll_event()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.borrow().is_closed()
+    }
+
+    fn is_fullscreen(&self) -> bool {
+        self.borrow().is_fullscreen()
+    }
+
+    fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.borrow_mut().set_fullscreen(fullscreen)
+    }
+}
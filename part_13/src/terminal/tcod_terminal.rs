@@ -0,0 +1,102 @@
+use tcod::console::{Console, Root};
+use tcod::image::{self, Image};
+use tcod::input::{self, Event, EventFlags};
+use tcod::Color;
+use tcod::BackgroundFlag;
+use tcod::TextAlignment;
+
+use settings::Settings;
+use terminal::Terminal;
+
+/// A `Terminal` which draws into a window using libtcod
+pub struct TcodTerminal {
+    root: Root,
+}
+
+impl TcodTerminal {
+    /// Open the game window
+    pub fn new(settings: &Settings) -> TcodTerminal {
+        let root = Root::initializer()
+            .size(settings.screen_width(), settings.screen_height())
+            .title(settings.title())
+            .font(settings.font_path(), settings.font_layout())
+            .font_type(settings.font_type())
+            .init();
+
+        TcodTerminal {
+            root,
+        }
+    }
+}
+
+impl Terminal for TcodTerminal {
+    fn width(&self) -> i32 {
+        self.root.width()
+    }
+
+    fn height(&self) -> i32 {
+        self.root.height()
+    }
+
+    fn clear(&mut self) {
+        self.root.clear();
+    }
+
+    fn set_default_foreground(&mut self, color: Color) {
+        self.root.set_default_foreground(color);
+    }
+
+    fn set_default_background(&mut self, color: Color) {
+        self.root.set_default_background(color);
+    }
+
+    fn put_char(&mut self, x: i32, y: i32, glyph: char, flag: BackgroundFlag) {
+        self.root.put_char(x, y, glyph, flag);
+    }
+
+    fn set_char_background(&mut self, x: i32, y: i32, color: Color, flag: BackgroundFlag) {
+        self.root.set_char_background(x, y, color, flag);
+    }
+
+    fn rect(&mut self, x: i32, y: i32, width: i32, height: i32, clear: bool, flag: BackgroundFlag) {
+        self.root.rect(x, y, width, height, clear, flag);
+    }
+
+    fn print(&mut self, x: i32, y: i32, alignment: TextAlignment, text: &str) {
+        self.root.print_ex(x, y, BackgroundFlag::None, alignment, text);
+    }
+
+    fn print_rect(&mut self, x: i32, y: i32, width: i32, height: i32, alignment: TextAlignment, text: &str) {
+        self.root.print_rect_ex(x, y, width, height, BackgroundFlag::None, alignment, text);
+    }
+
+    fn get_height_rect(&self, width: i32, text: &str) -> i32 {
+        self.root.get_height_rect(0, 0, width, self.root.height(), text)
+    }
+
+    fn draw_image(&mut self, path: &str) {
+        if let Ok(image) = Image::from_file(path) {
+            image::blit_2x(&image, (0, 0), (-1, -1), &mut self.root, (0, 0));
+        }
+    }
+
+    fn flush(&mut self) {
+        self.root.flush();
+    }
+
+    fn poll_event(&mut self) -> Option<(EventFlags, Event)> {
+        input::check_for_event(EventFlags::all())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.root.window_closed()
+    }
+
+    fn is_fullscreen(&self) -> bool {
+        self.root.is_fullscreen()
+    }
+
+    fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.root.set_fullscreen(fullscreen);
+    }
+}